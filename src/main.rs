@@ -1,118 +1,536 @@
 use anyhow::{Context as _, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use dialoguer::console::style;
 use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
 use dotenv::dotenv;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use tokio::runtime::Runtime;
+use tokio::sync::Notify;
 
 mod wizard;
 
-use wizard::context::{Context, Persona};
-use wizard::llm::{LlmClient, LlmConfig};
-use wizard::question::QuestionType;
-use wizard::session::{Session, SessionManager};
-use wizard::template::TemplateRepository;
+use wizard::atomic::write_atomic;
+use wizard::config::Config;
+use wizard::context::{Answer, Context, ContextMeta, HistoryCap, HistoryCapPolicy, Persona};
+use wizard::doctor::{self, CheckResult, CheckStatus};
+use wizard::language::{self, LanguageCheck};
+use wizard::llm::{
+    DocStyle, InterviewFlow, LlmClient, LlmConfig, TemperatureSchedule, format_models_table,
+};
+use wizard::metrics::SessionStats;
+use wizard::moderation::{Moderation, ModerationOutcome};
+use wizard::output::{
+    ConfidenceLevel, ConfidenceStyle, OutputFormat, OutputGenerator, raw_output_path,
+};
+use wizard::prefill::{self, Prefill};
+use wizard::progress::ProgressMode;
+use wizard::question::{Question, QuestionSource, QuestionType};
+use wizard::report;
+use wizard::session::{
+    self, ConfidenceGate, DEFAULT_EXHAUSTION_THRESHOLD, QuestionTimeoutAction, Session,
+    SessionManager, SessionState,
+};
+use wizard::template::{Template, TemplateRepository, TemplateSummary};
+use wizard::transcript::Transcript;
 
 /// LLM-Powered Dynamic Project Definition Wizard
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
+    /// Load environment variables from this file; can be repeated, later files take precedence
+    #[clap(long = "env-file", global = true)]
+    env_files: Vec<PathBuf>,
+
+    /// Use plain timestamped log lines and no prompts, answering from --answers (automatic when stdout is not a terminal)
+    #[clap(long, global = true)]
+    ci: bool,
+
+    /// Turn off colours and other styling (also when NO_COLOR is set)
+    #[clap(long, global = true)]
+    no_color: bool,
+
     #[clap(subcommand)]
     command: Commands,
 }
 
+/// Arguments for starting a new wizard session
+#[derive(Args)]
+struct NewArgs {
+    /// Starting hints for the wizard (`-` reads them from stdin)
+    #[clap(short = 'i', long)]
+    hints: Option<String>,
+
+    /// File to read the starting hints from, e.g. an existing brief
+    #[clap(long, conflicts_with = "hints")]
+    hints_file: Option<PathBuf>,
+
+    /// Domain for the project
+    #[clap(short, long)]
+    domain: Option<String>,
+
+    /// Maximum number of questions
+    #[clap(short, long, default_value = "10")]
+    questions: usize,
+
+    /// Use a template
+    #[clap(short, long)]
+    template: Option<String>,
+
+    /// Persona mode
+    #[clap(short, long)]
+    persona: Option<String>,
+
+    /// Reference document (text/markdown) to build on; can be repeated
+    #[clap(long = "context-file")]
+    context_files: Vec<PathBuf>,
+
+    /// Start from the hints, domain, persona and metadata of a previous session, without its answers
+    #[clap(long, value_name = "SESSION", conflicts_with = "template")]
+    from_session: Option<PathBuf>,
+
+    /// Ask only the template's initial questions and build a basic definition without the LLM
+    #[clap(long)]
+    offline: bool,
+
+    #[clap(flatten)]
+    wizard: WizardArgs,
+}
+
+/// Options shared by every command that runs the wizard
+#[derive(Args)]
+struct WizardArgs {
+    /// Output file for the project definition
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Ask how certain you are after each answer
+    #[clap(long)]
+    ask_certainty: bool,
+
+    /// Keep asking follow-up questions until every section is projected to reach this confidence (1-5)
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+    min_confidence: Option<u8>,
+
+    /// Number of questions answered before --min-confidence checks the sections (default: the question limit)
+    #[clap(long)]
+    min_questions: Option<usize>,
+
+    /// Maximum number of follow-up questions asked by --min-confidence
+    #[clap(long, default_value = "3")]
+    max_followups: usize,
+
+    /// After a vague free-text answer, ask a clarifying question about it before moving on
+    #[clap(long)]
+    clarify: bool,
+
+    /// Maximum number of clarifying questions asked by --clarify
+    #[clap(long, default_value = "3")]
+    max_clarifiers: usize,
+
+    /// Generate the project definition in several smaller requests
+    #[clap(long)]
+    split_generation: bool,
+
+    /// Generate each definition section in its own request, running up to N at a time
+    #[clap(long, value_name = "N")]
+    parallel_sections: Option<usize>,
+
+    /// Generate N questions at once and choose which to answer, queuing the rest
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u8).range(2..=10))]
+    batch_questions: Option<u8>,
+
+    /// Show only the first N options of a multiple choice question, with an entry revealing the rest
+    #[clap(long, value_name = "N")]
+    max_options_display: Option<usize>,
+
+    /// Stream the project definition, writing it to <OUTPUT>.raw.md as it arrives
+    #[clap(long, requires = "output", conflicts_with = "parallel_sections")]
+    stream: bool,
+
+    /// How confidence levels are marked in the output (defaults to the config, then emoji)
+    #[clap(long, value_enum)]
+    confidence_style: Option<ConfidenceStyle>,
+
+    /// Generate the project definition without per-section confidence scores
+    #[clap(long)]
+    no_confidence: bool,
+
+    /// Maximum number of answers kept in the session history
+    #[clap(long, value_name = "N")]
+    history_cap: Option<usize>,
+
+    /// What to do when the history cap is reached
+    #[clap(long, value_enum, default_value = "refuse", requires = "history_cap")]
+    history_cap_policy: HistoryCapPolicy,
+
+    /// Tell the model which topics were already covered so it doesn't circle back to them
+    #[clap(long)]
+    avoid_repeats: bool,
+
+    /// Fixed sampling seed for reproducible questions (only honoured by some providers)
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// Voice of the project definition document (default: the usual professional tone)
+    #[clap(long, value_enum)]
+    doc_style: Option<DocStyle>,
+
+    /// Cover every area before going into detail (breadth) or explore each topic fully first (depth)
+    #[clap(long, value_enum)]
+    flow: Option<InterviewFlow>,
+
+    /// Use the model settings of a named profile from the configuration file
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// Never ask about this topic (repeat for several topics)
+    #[clap(long = "avoid", value_name = "TOPIC")]
+    avoid: Vec<String>,
+
+    /// Language to write the project definition in (e.g. German, Español, fra)
+    #[clap(long)]
+    language: Option<String>,
+
+    /// Warn when the generated project definition doesn't appear to be in --language
+    #[clap(long, requires = "language")]
+    check_language: bool,
+
+    /// Append the project definition to this portfolio document, alongside other projects
+    #[clap(long, value_name = "PORTFOLIO")]
+    append: Option<PathBuf>,
+
+    /// File format of the saved project definition
+    #[clap(long, value_enum, default_value_t = OutputFormat::Markdown)]
+    format: OutputFormat,
+
+    /// Add an appendix with session metrics (such as the mix of question types) to the output
+    #[clap(long)]
+    metrics: bool,
+
+    /// Also save the session metrics as JSON to this file
+    #[clap(long, value_name = "PATH")]
+    metrics_json: Option<PathBuf>,
+
+    /// Hide the command list and the command hint shown above each question
+    #[clap(long)]
+    quiet: bool,
+
+    /// Model to use, overriding the configured model (and, when continuing, the session's model)
+    #[clap(long)]
+    model: Option<String>,
+
+    /// Only run the interview and export the questions and answers, skipping the final document
+    #[clap(long)]
+    questions_only: bool,
+
+    /// Replace the answers to sensitive questions by [REDACTED] in the saved session file
+    #[clap(long)]
+    redact_sensitive: bool,
+
+    /// Append every prompt sent to the LLM and its raw response to this JSON Lines file
+    #[clap(long, value_name = "PATH")]
+    transcript: Option<PathBuf>,
+
+    /// Stop asking questions after this many seconds and generate from the answers so far
+    #[clap(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Abort instead of generating the definition when --timeout is reached
+    #[clap(long)]
+    strict_timeout: bool,
+
+    /// Time to answer each question in seconds, e.g. to time-box answers in a workshop
+    #[clap(long, value_name = "SECONDS")]
+    question_timeout: Option<u64>,
+
+    /// What happens to a question that isn't answered within --question-timeout
+    #[clap(long, value_enum)]
+    on_question_timeout: Option<QuestionTimeoutAction>,
+
+    /// Have the persona keep a one-line note after each answer, fed into later prompts
+    #[clap(long)]
+    running_notes: bool,
+
+    /// JSON file of known answers, keyed by question id or text, to pre-fill the prompts with
+    #[clap(long, value_name = "PATH")]
+    prefill: Option<PathBuf>,
+
+    /// JSON file of answers, keyed by question id or text, to answer without prompting (required in CI mode)
+    #[clap(long, value_name = "PATH")]
+    answers: Option<PathBuf>,
+
+    /// Offer to end the interview after this many repetitive questions in a row (0 = never)
+    #[clap(long, value_name = "N")]
+    exhaustion_threshold: Option<usize>,
+
+    /// Also save the project definition as a PDF
+    #[cfg(feature = "pdf")]
+    #[clap(long)]
+    pdf: Option<PathBuf>,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Start a new wizard session
-    New {
-        /// Starting hints for the wizard
-        #[clap(short = 'i', long)]
-        hints: Option<String>,
-
-        /// Domain for the project
+    New(NewArgs),
+    /// Continue an existing wizard session
+    Continue {
+        /// Path to the session file
         #[clap(short, long)]
-        domain: Option<String>,
-
-        /// Maximum number of questions
-        #[clap(short, long, default_value = "10")]
-        questions: usize,
+        session: PathBuf,
 
-        /// Use a template
+        #[clap(flatten)]
+        wizard: WizardArgs,
+    },
+    /// Ask follow-up questions about the weakest sections of a completed session, then regenerate
+    Refine {
+        /// Path to the completed session file
         #[clap(short, long)]
-        template: Option<String>,
+        session: PathBuf,
+
+        /// Number of lowest-confidence sections to ask about
+        #[clap(long, default_value_t = 3)]
+        sections: usize,
 
-        /// Persona mode
+        #[clap(flatten)]
+        wizard: WizardArgs,
+    },
+    /// Check that a saved session file is well-formed
+    ValidateSession {
+        /// Path to the session file
+        path: PathBuf,
+    },
+    /// Write a readable brief of a session's hints, setup and answers to share with others
+    Brief {
+        /// Path to the session file
         #[clap(short, long)]
-        persona: Option<String>,
+        session: PathBuf,
 
-        /// Output file for the project definition
+        /// File to write the brief to (printed if not given)
         #[clap(short, long)]
         output: Option<PathBuf>,
     },
-    /// Continue an existing wizard session
-    Continue {
+    /// Write a single HTML report of a session's definition, transcript and metrics
+    Report {
         /// Path to the session file
         #[clap(short, long)]
         session: PathBuf,
 
-        /// Output file for the project definition
+        /// HTML file to write the report to
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+    /// Merge the answers of several sessions into one, e.g. partial interviews from a workshop
+    Merge {
+        /// Session files to merge; for a question answered in several, the last file wins
+        #[clap(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+
+        /// File to write the merged session to
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+    /// Generate a project definition from answers collected elsewhere, without an interview
+    Generate {
+        /// JSON file with the answers, in the format written by --questions-only
+        answers: PathBuf,
+
+        /// JSON file with the project setup: starting_hints, domain, project_name, persona, metadata
+        #[clap(long)]
+        meta: Option<PathBuf>,
+
+        /// File to write the project definition to (printed if not given)
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Re-score the confidence and open questions of a hand-edited project definition
+    Rescore {
+        /// Markdown project definition to re-score
+        input: PathBuf,
+
+        /// File to write the re-scored definition to (printed if not given)
         #[clap(short, long)]
         output: Option<PathBuf>,
     },
+    /// Search the answers of all saved sessions in a directory
+    Search {
+        /// Directory containing session files
+        dir: PathBuf,
+
+        /// Text to search for (case-insensitive)
+        query: String,
+    },
+    /// Show how the average confidence of a session's definition changed with each generation
+    Trend {
+        /// Path to the session file
+        #[clap(short, long)]
+        session: PathBuf,
+    },
+    /// Summarize all saved sessions in a directory: domains, personas, questions and confidence
+    Stats {
+        /// Directory containing session files
+        dir: PathBuf,
+
+        /// Print the statistics as JSON
+        #[clap(long)]
+        json: bool,
+    },
     /// List available templates
-    Templates,
+    Templates {
+        /// Print the templates as JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Include the full template questions in the JSON output
+        #[clap(long, requires = "json")]
+        full: bool,
+    },
+    /// Show the full details of a template
+    TemplateInfo {
+        /// Name of the template
+        name: String,
+    },
     /// List available domains
-    Domains,
+    Domains {
+        /// Language or locale of the domain list (e.g. German, de), from the system locale if not given
+        #[clap(long)]
+        language: Option<String>,
+    },
+    /// List the models available from the provider
+    Models {
+        /// Pick a model and save it to the configuration file
+        #[clap(long)]
+        pick: bool,
+    },
+    /// Check the API key, API connection, configuration file and templates
+    Doctor,
 }
 
 fn main() -> Result<()> {
-    // Load environment variables from .env file
-    dotenv().ok();
-
     // Parse command line arguments
     let cli = Cli::parse();
 
+    // Load environment variables from env files
+    load_env_files(&cli.env_files)?;
+
+    // Leave out styling for terminals and logs that don't want it
+    if colors_disabled(cli.no_color, std::env::var_os("NO_COLOR").as_deref()) {
+        dialoguer::console::set_colors_enabled(false);
+        dialoguer::console::set_colors_enabled_stderr(false);
+    }
+
+    // Use line-based progress for CI logs and redirected output
+    let progress = ProgressMode::detect(cli.ci);
+
     // Create tokio runtime
     let runtime = Runtime::new().context("Failed to create Tokio runtime")?;
 
     // Execute the command
-    match cli.command {
-        Commands::New {
-            hints,
-            domain,
-            questions,
-            template,
-            persona,
-            output,
-        } => runtime.block_on(new_session(
-            hints, domain, questions, template, persona, output,
-        )),
-        Commands::Continue { session, output } => {
-            runtime.block_on(continue_session(session, output))
+    let result = match cli.command {
+        Commands::New(args) => runtime.block_on(new_session(args, progress)),
+        Commands::Continue { session, wizard } => {
+            runtime.block_on(continue_session(session, wizard, progress))
         }
-        Commands::Templates => list_templates(),
-        Commands::Domains => list_domains(),
+        Commands::Refine {
+            session,
+            sections,
+            wizard,
+        } => runtime.block_on(refine_session(session, sections, wizard, progress)),
+        Commands::ValidateSession { path } => validate_session(path),
+        Commands::Brief { session, output } => export_brief(session, output),
+        Commands::Report { session, output } => export_report(session, output),
+        Commands::Merge { inputs, output } => merge_sessions(inputs, output),
+        Commands::Generate {
+            answers,
+            meta,
+            output,
+        } => runtime.block_on(generate_from_answers(answers, meta, output)),
+        Commands::Rescore { input, output } => runtime.block_on(rescore_definition(input, output)),
+        Commands::Search { dir, query } => search_sessions(dir, query),
+        Commands::Trend { session } => show_confidence_trend(session),
+        Commands::Stats { dir, json } => show_session_stats(dir, json),
+        Commands::Templates { json, full } => list_templates(json, full),
+        Commands::TemplateInfo { name } => show_template_info(&name),
+        Commands::Domains { language } => list_domains(language),
+        Commands::Models { pick } => runtime.block_on(list_models(pick, progress)),
+        Commands::Doctor => runtime.block_on(run_doctor()),
+    };
+
+    // Exit like the default Ctrl+C handler would have, now that the terminal is restored
+    if result.as_ref().is_err_and(|e| e.is::<Interrupted>()) {
+        std::process::exit(130);
+    }
+    result
+}
+
+/// Check whether styling is turned off, with `--no-color` or a non-empty `NO_COLOR`
+fn colors_disabled(no_color: bool, no_color_env: Option<&std::ffi::OsStr>) -> bool {
+    no_color || no_color_env.is_some_and(|value| !value.is_empty())
+}
+
+/// Load environment variables from explicit env files, then the default `.env`
+///
+/// Variables already set are never overwritten, so the process environment wins
+/// over every file and later explicit files win over earlier ones.
+fn load_env_files(env_files: &[PathBuf]) -> Result<()> {
+    for path in env_files.iter().rev() {
+        dotenv::from_path(path)
+            .with_context(|| format!("Failed to load env file: {}", path.display()))?;
     }
+
+    dotenv().ok();
+    Ok(())
 }
 
 /// Start a new wizard session
-async fn new_session(
-    hints: Option<String>,
-    domain: Option<String>,
-    max_questions: usize,
-    template_name: Option<String>,
-    persona_name: Option<String>,
-    output_path: Option<PathBuf>,
-) -> Result<()> {
+async fn new_session(args: NewArgs, progress: ProgressMode) -> Result<()> {
+    let NewArgs {
+        hints,
+        hints_file,
+        domain,
+        questions: max_questions,
+        template: template_name,
+        persona: persona_name,
+        context_files,
+        from_session,
+        offline,
+        wizard,
+    } = args;
+
+    if offline && template_name.is_none() {
+        anyhow::bail!(
+            "--offline needs a template (--template <NAME>): without the LLM, only a template's questions can be asked"
+        );
+    }
+
     println!("🧙 Starting LLM-Powered Project Definition Wizard");
 
+    let hints = load_hints(hints, hints_file)?;
+
     // Create LLM client
-    let llm_client = create_llm_client()?;
+    let llm_client = create_llm_client(wizard.profile.as_deref())?
+        .with_model(wizard.model.clone())
+        .with_seed(wizard.seed)
+        .with_avoid_covered_topics(wizard.avoid_repeats)
+        .with_omit_confidence(wizard.no_confidence)
+        .with_language(wizard.language.clone())
+        .with_doc_style(wizard.doc_style)
+        .with_flow(wizard.flow)
+        .with_avoided_topics(wizard.avoid.clone())
+        .with_transcript(open_transcript(&wizard)?);
+    warn_about_output_limits(&llm_client);
 
     // Create repository
     let repo = TemplateRepository::new();
 
     // Create session
+    let mut offline_template = None;
     let mut session = if let Some(template_name) = template_name {
         // Create session from template
         let template = repo
@@ -122,21 +540,42 @@ async fn new_session(
         println!("Using template: {}", template.name);
         println!("Description: {}", template.description);
 
+        if offline {
+            if template.initial_questions.is_empty() {
+                anyhow::bail!(
+                    "Template '{}' has no initial questions to ask offline",
+                    template.name
+                );
+            }
+            offline_template = Some(template.clone());
+        }
+
         Session::from_template(template)
     } else {
-        // Create new session
-        let mut context = Context::new();
+        // Create new session, reusing the setup of a previous one if requested
+        let mut context = match &from_session {
+            Some(path) => {
+                let previous = Session::load_from_file(path).with_context(|| {
+                    format!("Failed to load session to reuse: {}", path.display())
+                })?;
+                println!("Reusing the setup of session {}", path.display());
+                Context::setup_from(&previous.context)
+            }
+            None => Context::new(),
+        };
 
         // Set starting hints if provided
         if let Some(hints) = hints {
-            context = Context::with_hints(hints);
+            context.starting_hints = Some(hints);
         }
 
         // Set domain if provided
         if let Some(domain_str) = domain {
-            // Validate domain
-            let domains = repo.get_all_domains();
-            let domain_valid = domains.iter().any(|d| d.to_string() == domain_str);
+            // Validate domain, accepting the English names as well as localized ones
+            let domains =
+                repo.get_domains_for(domain_locale(wizard.language.as_deref()).as_deref());
+            let domain_valid =
+                domains.contains(&domain_str) || repo.get_all_domains().contains(&domain_str);
 
             if !domain_valid {
                 println!(
@@ -150,7 +589,7 @@ async fn new_session(
                 println!();
             }
 
-            context = Context::with_domain(domain_str);
+            context.domain = Some(domain_str);
         }
 
         Session::with_context(context)
@@ -167,256 +606,3835 @@ async fn new_session(
             _ => Persona::Default,
         };
 
-        println!(
-            "Using persona: {}",
-            match persona {
-                Persona::Default => "Default",
-                Persona::ProductManager => "Product Manager",
-                Persona::LlmArchitect => "LLM Architect",
-                Persona::UxDesigner => "UX Designer",
-                Persona::ComplianceOfficer => "Compliance Officer",
-            }
-        );
+        println!("Using persona: {}", persona);
 
         session.context.persona = persona;
     }
 
+    // Pin the model so continuing the session keeps using it
+    session.model = Some(llm_client.model_settings());
+
+    // Attach reference documents, seeding the domain and hints from their front matter
+    for path in context_files {
+        let had_domain = session.context.domain.is_some();
+        session
+            .context
+            .attach_file(&path)
+            .with_context(|| format!("Failed to read context file: {}", path.display()))?;
+        println!("Attached reference document: {}", path.display());
+        if let Some(domain) = session.context.domain.as_ref().filter(|_| !had_domain) {
+            println!("Using domain from its front matter: {}", domain);
+        }
+    }
+
+    // Ask the template's questions without calling the LLM
+    if let Some(template) = offline_template {
+        return run_offline_interview(session, &template, llm_client, wizard, progress);
+    }
+
     // Run the wizard
-    run_wizard(session, llm_client, output_path).await
+    run_wizard(session, llm_client, wizard, progress).await
+}
+
+/// Maximum number of characters of starting hints kept from stdin or a file
+const MAX_HINTS_CHARS: usize = 4000;
+
+/// Resolve the starting hints from `--hints` (`-` for stdin) or `--hints-file`
+fn load_hints(hints: Option<String>, hints_file: Option<PathBuf>) -> Result<Option<String>> {
+    let (text, source) = match (hints, hints_file) {
+        (Some(hints), _) if hints == "-" => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .context("Failed to read hints from stdin")?;
+            (text, "stdin".to_string())
+        }
+        (Some(hints), _) => return Ok(Some(hints)),
+        (None, Some(path)) => {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read hints file: {}", path.display()))?;
+            (text, path.display().to_string())
+        }
+        (None, None) => return Ok(None),
+    };
+
+    let text = text.trim();
+    if text.is_empty() {
+        println!("Warning: no hints found in {}", source);
+        return Ok(None);
+    }
+
+    // Keep very long briefs from crowding out the rest of the prompt
+    match text.char_indices().nth(MAX_HINTS_CHARS) {
+        Some((end, _)) => {
+            println!(
+                "Warning: hints from {} are longer than {} characters and were truncated",
+                source, MAX_HINTS_CHARS
+            );
+            Ok(Some(text[..end].to_string()))
+        }
+        None => Ok(Some(text.to_string())),
+    }
 }
 
 /// Continue an existing wizard session
-async fn continue_session(session_path: PathBuf, output_path: Option<PathBuf>) -> Result<()> {
+async fn continue_session(
+    session_path: PathBuf,
+    wizard: WizardArgs,
+    progress: ProgressMode,
+) -> Result<()> {
     println!("🧙 Continuing LLM-Powered Project Definition Wizard");
 
     // Load session
-    let session = Session::load_from_file(session_path).context("Failed to load session file")?;
+    let mut session = Session::open(session_path).context("Failed to load session file")?;
+
+    // Offer to retry the step that failed last time
+    if session.state == SessionState::Error {
+        println!(
+            "This session stopped with an error: {}",
+            session.error.as_deref().unwrap_or("unknown error")
+        );
+
+        // Without prompts, the failed step is retried
+        let retry = !progress.is_interactive()
+            || prompt_with_retry(|| {
+                Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Do you want to retry the failed step?")
+                    .default(true)
+                    .interact()
+            })?;
+
+        if !retry {
+            return Ok(());
+        }
+
+        session.clear_error();
+    }
 
     // Create LLM client
-    let llm_client = create_llm_client()?;
+    let llm_client = create_session_llm_client(&mut session, &wizard)?;
 
     // Run the wizard
-    run_wizard(session, llm_client, output_path).await
+    run_wizard(session, llm_client, wizard, progress).await
 }
 
-/// List available templates
-fn list_templates() -> Result<()> {
-    println!("🧙 Available Templates");
+/// Refine a completed session by asking about its lowest-confidence sections
+async fn refine_session(
+    session_path: PathBuf,
+    sections: usize,
+    wizard: WizardArgs,
+    progress: ProgressMode,
+) -> Result<()> {
+    println!("🧙 Refining LLM-Powered Project Definition");
 
-    let repo = TemplateRepository::new();
-    let templates = repo.get_all_templates();
+    // Load session
+    let mut session = Session::open(session_path).context("Failed to load session file")?;
 
-    if templates.is_empty() {
-        println!("No templates available");
-    } else {
-        for (i, template) in templates.iter().enumerate() {
-            println!("{}. {} ({})", i + 1, template.name, template.domain);
-            println!("   {}", template.description);
-            println!();
+    let Some(definition) = &session.definition else {
+        anyhow::bail!("The session has no stored project definition; complete it before refining");
+    };
+
+    // Queue the weakest sections to be asked about first
+    let focus_areas = definition.weakest_sections(sections);
+    if focus_areas.is_empty() {
+        if !definition.sections.is_empty() {
+            anyhow::bail!(
+                "The project definition has no confidence scores (generated with --no-confidence), so there are no weak sections to refine"
+            );
         }
+        anyhow::bail!("The project definition has no sections to refine");
     }
 
-    Ok(())
+    println!("Asking follow-up questions about:");
+    for area in &focus_areas {
+        println!("- {}", area);
+    }
+    println!();
+    session.focus_areas = focus_areas;
+
+    // Create LLM client
+    let llm_client = create_session_llm_client(&mut session, &wizard)?;
+
+    // Run the wizard
+    run_wizard(session, llm_client, wizard, progress).await
 }
 
-/// List available domains
-fn list_domains() -> Result<()> {
-    println!("🧙 Available Domains");
+/// Warn about models whose output limit is below the configured max_tokens
+fn warn_about_output_limits(llm_client: &LlmClient) {
+    for warning in llm_client.max_tokens_warnings() {
+        println!("Warning: {}", warning);
+    }
+}
 
-    let repo = TemplateRepository::new();
-    let domains = repo.get_all_domains();
+/// Create the LLM client for an existing session, defaulting to the model it was created with
+fn create_session_llm_client(session: &mut Session, wizard: &WizardArgs) -> Result<LlmClient> {
+    let mut llm_client = create_llm_client(wizard.profile.as_deref())?
+        .with_transcript(open_transcript(wizard)?)
+        .with_seed(wizard.seed)
+        .with_avoid_covered_topics(wizard.avoid_repeats)
+        .with_omit_confidence(wizard.no_confidence)
+        .with_language(wizard.language.clone())
+        .with_doc_style(wizard.doc_style)
+        .with_flow(wizard.flow)
+        .with_avoided_topics(wizard.avoid.clone());
 
-    if domains.is_empty() {
-        println!("No domains available");
-    } else {
-        for (i, domain) in domains.iter().enumerate() {
-            println!("{}. {}", i + 1, domain);
+    match (&session.model, &wizard.model) {
+        (Some(pinned), Some(model)) if &pinned.model != model => {
+            println!(
+                "Warning: this session was created with model '{}'; continuing with '{}' as requested.",
+                pinned.model, model
+            );
+        }
+        (Some(pinned), None) if wizard.profile.is_some() => {
+            let configured = llm_client.model_settings();
+            if configured.model != pinned.model {
+                println!(
+                    "Warning: this session was created with model '{}'; continuing with '{}' from the profile.",
+                    pinned.model, configured.model
+                );
+            }
+        }
+        (Some(pinned), None) => {
+            let configured = llm_client.model_settings();
+            if configured.model != pinned.model {
+                println!(
+                    "Warning: using the session's model '{}' instead of the configured '{}'. Pass --model to override.",
+                    pinned.model, configured.model
+                );
+            }
+            llm_client = llm_client.with_model_settings(pinned);
         }
+        _ => {}
     }
+    llm_client = llm_client.with_model(wizard.model.clone());
+    session.model = Some(llm_client.model_settings());
+    warn_about_output_limits(&llm_client);
 
-    Ok(())
+    Ok(llm_client)
 }
 
-/// Create an LLM client
-fn create_llm_client() -> Result<LlmClient> {
-    // Get API key from environment
-    let api_key = std::env::var("OPENROUTER_API_KEY").ok();
+/// Open the transcript requested with --transcript, if any
+fn open_transcript(wizard: &WizardArgs) -> Result<Option<Transcript>> {
+    wizard
+        .transcript
+        .as_deref()
+        .map(Transcript::open)
+        .transpose()
+        .map_err(Into::into)
+}
 
-    // Create config
-    let config = LlmConfig {
-        api_key,
-        ..LlmConfig::default()
-    };
+/// Validate a saved session file
+fn validate_session(session_path: PathBuf) -> Result<()> {
+    println!("🧙 Validating session {}", session_path.display());
 
-    // Create client
-    LlmClient::with_config(config)
-}
+    let session = match Session::load_from_file(&session_path) {
+        Ok(session) => session,
+        Err(e) => {
+            println!("❌ FAIL: could not load session: {}", e);
+            anyhow::bail!("Session validation failed");
+        }
+    };
 
-/// Run the wizard
-async fn run_wizard(
-    session: Session,
-    llm_client: LlmClient,
-    output_path: Option<PathBuf>,
-) -> Result<()> {
-    let mut session_manager = SessionManager::new(session, llm_client);
-    session_manager.start();
+    let validation = session.validate();
 
-    let theme = ColorfulTheme::default();
+    for error in &validation.errors {
+        println!("❌ {}", error);
+    }
+    for warning in &validation.warnings {
+        println!("⚠️  {}", warning);
+    }
 
-    println!(
-        "Starting wizard session with {} questions",
-        session_manager.max_questions()
-    );
-    println!("Type 'back' to go back to a previous question");
-    println!("Type 'quit' to exit the wizard");
-    println!();
+    if validation.is_valid() {
+        println!(
+            "✅ PASS: {} answers, {} warning(s)",
+            session.context.history.len(),
+            validation.warnings.len()
+        );
+        Ok(())
+    } else {
+        println!("❌ FAIL: {} error(s)", validation.errors.len());
+        anyhow::bail!("Session validation failed");
+    }
+}
 
-    // Question loop
-    loop {
-        // Check if we've reached the maximum number of questions
-        let current_count = session_manager.question_count();
-        let max_questions = session_manager.max_questions();
+/// Print or save the brief of a saved session
+fn export_brief(session_path: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let session = Session::load_from_file(&session_path)
+        .with_context(|| format!("Failed to load session: {}", session_path.display()))?;
+    let brief = session.context.to_brief_markdown();
 
-        if current_count >= max_questions {
-            println!("Maximum number of questions reached");
-            break;
+    match output {
+        Some(path) => {
+            write_atomic(&path, brief)?;
+            println!("Brief saved to {}", path.display());
         }
+        None => print!("{}", brief),
+    }
+    Ok(())
+}
 
-        // Generate next question
-        let question = match session_manager.generate_next_question().await {
-            Ok(q) => q,
-            Err(e) => {
-                println!("Error generating question: {}", e);
-                break;
-            }
-        };
+/// Write a self-contained HTML report of a saved session
+fn export_report(session_path: PathBuf, output: PathBuf) -> Result<()> {
+    let session = Session::load_from_file(&session_path)
+        .with_context(|| format!("Failed to load session: {}", session_path.display()))?;
+
+    write_atomic(&output, report::render(&session))?;
+    println!("Report saved to {}", output.display());
+    Ok(())
+}
 
-        // Display question
+/// Merge several saved sessions into a new one that can be continued or generated from
+fn merge_sessions(inputs: Vec<PathBuf>, output: PathBuf) -> Result<()> {
+    let mut merged: Option<Session> = None;
+    for path in &inputs {
+        let session = Session::load_from_file(path)
+            .with_context(|| format!("Failed to load session: {}", path.display()))?;
         println!(
-            "Question {}/{}: {}",
-            current_count + 1,
-            max_questions,
-            question.text
+            "Merging {} ({} answers)",
+            path.display(),
+            session.context.history.len()
         );
 
-        if let Some(help_text) = &question.help_text {
-            println!("Hint: {}", help_text);
-        }
-
-        // Get user response based on question type
-        let response = match question.question_type {
-            QuestionType::MultipleChoice => {
-                if let Some(options) = &question.options {
-                    let selection = Select::with_theme(&theme)
-                        .items(options)
-                        .default(0)
-                        .interact()
-                        .context("Failed to get user input")?;
-                    options[selection].clone()
-                } else {
-                    "Invalid question: missing options".to_string()
-                }
+        match &mut merged {
+            Some(merged) => {
+                merged.context.merge(&session.context);
+                merged.max_questions = merged.max_questions.max(session.max_questions);
             }
-            QuestionType::YesNo => {
-                let confirmed = Confirm::with_theme(&theme)
-                    .with_prompt("Yes or No?")
-                    .default(true)
-                    .interact()
-                    .context("Failed to get user input")?;
-                if confirmed {
-                    "Yes".to_string()
-                } else {
-                    "No".to_string()
-                }
+            None => {
+                let mut first = Session::with_context(session.context);
+                first.max_questions = session.max_questions;
+                first.model = session.model;
+                merged = Some(first);
             }
-            QuestionType::RatingScale => {
-                if let Some((min, max)) = question.scale {
-                    let options: Vec<String> = (min..=max).map(|n| format!("{}", n)).collect();
-                    let selection = Select::with_theme(&theme)
-                        .items(&options)
-                        .default(0)
-                        .interact()
-                        .context("Failed to get user input")?;
-                    options[selection].clone()
-                } else {
-                    "Invalid question: missing scale".to_string()
+        }
+    }
+
+    let Some(merged) = merged else {
+        anyhow::bail!("No sessions to merge");
+    };
+    merged
+        .save_to_file(&output)
+        .with_context(|| format!("Failed to save session: {}", output.display()))?;
+    println!(
+        "Merged session with {} answers saved to {}",
+        merged.context.history.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Re-score a project definition after it was edited by hand
+async fn rescore_definition(input: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let markdown = std::fs::read_to_string(&input)
+        .with_context(|| format!("Failed to read definition: {}", input.display()))?;
+    let settings = Config::load_or_default();
+    let generator = OutputGenerator::new(create_llm_client(None)?)
+        .with_duplicate_sections(settings.duplicate_sections.unwrap_or_default());
+
+    println!("🧙 Re-scoring {}", input.display());
+    let before = generator.parse_markdown_definition(&markdown)?;
+    let definition = generator.rescore_definition(&markdown).await?;
+
+    for (old, new) in before.sections.iter().zip(&definition.sections) {
+        if old.confidence as u8 != new.confidence as u8 {
+            println!(
+                "  {}: {}/5 → {}/5",
+                new.title, old.confidence as u8, new.confidence as u8
+            );
+        }
+    }
+
+    let mut style = settings.markdown_style.unwrap_or_default();
+    if let Some(confidence_style) = settings.confidence_style {
+        style.confidence = confidence_style;
+    }
+    let rescored = definition.to_styled_markdown(&style);
+    match output {
+        Some(path) => {
+            write_atomic(&path, rescored)?;
+            println!("Re-scored definition saved to {}", path.display());
+        }
+        None => print!("{}", rescored),
+    }
+    Ok(())
+}
+
+/// Generate a project definition from an answers file, without a session
+async fn generate_from_answers(
+    answers_path: PathBuf,
+    meta_path: Option<PathBuf>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let answers: Vec<Answer> = serde_json::from_str(
+        &std::fs::read_to_string(&answers_path)
+            .with_context(|| format!("Failed to read answers: {}", answers_path.display()))?,
+    )
+    .with_context(|| format!("Invalid answers file: {}", answers_path.display()))?;
+    let meta: ContextMeta = match &meta_path {
+        Some(path) => serde_json::from_str(
+            &std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read project setup: {}", path.display()))?,
+        )
+        .with_context(|| format!("Invalid project setup file: {}", path.display()))?,
+        None => ContextMeta::default(),
+    };
+
+    let settings = Config::load_or_default();
+    let generator = OutputGenerator::new(create_llm_client(None)?)
+        .with_duplicate_sections(settings.duplicate_sections.unwrap_or_default());
+
+    println!(
+        "🧙 Generating a project definition from {} answers",
+        answers.len()
+    );
+    let definition = generator.generate_from_answers(answers, meta).await?;
+
+    let mut style = settings.markdown_style.unwrap_or_default();
+    if let Some(confidence_style) = settings.confidence_style {
+        style.confidence = confidence_style;
+    }
+    let markdown = definition.to_styled_markdown(&style);
+    match output {
+        Some(path) => {
+            write_atomic(&path, markdown)?;
+            println!("Project definition saved to {}", path.display());
+        }
+        None => print!("{}", markdown),
+    }
+    Ok(())
+}
+
+/// Search saved sessions for answers mentioning a query
+fn search_sessions(dir: PathBuf, query: String) -> Result<()> {
+    println!(
+        "🧙 Searching sessions in {} for \"{}\"",
+        dir.display(),
+        query
+    );
+
+    let results = session::search_sessions(&dir, &query)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+
+    for (path, reason) in &results.skipped {
+        println!("⚠️  Skipped {}: {}", path.display(), reason);
+    }
+
+    if results.matches.is_empty() {
+        println!("No matches found");
+        return Ok(());
+    }
+
+    for found in &results.matches {
+        println!();
+        println!("{} (Q{})", found.path.display(), found.number);
+        println!("  Q: {}", found.question);
+        println!("  A: {}", found.response);
+    }
+
+    println!();
+    println!("{} match(es) found", results.matches.len());
+    Ok(())
+}
+
+/// Print aggregate statistics for the saved sessions in a directory
+fn show_session_stats(dir: PathBuf, json: bool) -> Result<()> {
+    let loaded = session::load_sessions_in_dir(&dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    let stats = SessionStats::from_sessions(loaded.sessions.iter().map(|(_, session)| session));
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    println!("🧙 Session statistics for {}", dir.display());
+    for (path, reason) in &loaded.skipped {
+        println!("⚠️  Skipped {}: {}", path.display(), reason);
+    }
+    println!();
+
+    println!("Sessions: {}", stats.sessions);
+    println!("Average questions asked: {:.1}", stats.average_questions);
+    match stats.average_confidence {
+        Some(confidence) => println!(
+            "Average definition confidence: {:.1}/5 (from {} definitions)",
+            confidence, stats.definitions
+        ),
+        None => println!("Average definition confidence: no stored definitions"),
+    }
+
+    print_count_table("Domain", &stats.domains);
+    print_count_table("Persona", &stats.personas);
+
+    println!();
+    println!("{:<24} {:>9}", "Question type", "Questions");
+    for (label, count) in stats.question_types_by_frequency() {
+        println!("{:<24} {:>9}", label, count);
+    }
+
+    Ok(())
+}
+
+/// Print the average confidence of each definition generated for a session, e.g. across refinements
+fn show_confidence_trend(session_path: PathBuf) -> Result<()> {
+    let session = Session::load_from_file(&session_path)
+        .with_context(|| format!("Failed to load session: {}", session_path.display()))?;
+
+    println!("🧙 Confidence trend for {}", session_path.display());
+    if session.confidence_history.is_empty() {
+        println!("No definitions with confidence scores have been generated for this session yet");
+        return Ok(());
+    }
+
+    println!();
+    println!(
+        "{:<4} {:<24} {:>10} {:>7}",
+        "#", "Generated", "Confidence", "Change"
+    );
+    let mut previous: Option<f32> = None;
+    for (index, snapshot) in session.confidence_history.iter().enumerate() {
+        let change = previous
+            .map(|previous| format!("{:+.1}", snapshot.average - previous))
+            .unwrap_or_default();
+        println!(
+            "{:<4} {:<24} {:>8.1}/5 {:>7}",
+            index + 1,
+            snapshot.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            snapshot.average,
+            change
+        );
+        previous = Some(snapshot.average);
+    }
+
+    if let Some(change) = session.confidence_change() {
+        println!();
+        let generations = session.confidence_history.len();
+        if change.abs() < 0.05 {
+            println!("Confidence held steady over {} generations", generations);
+        } else {
+            let direction = if change > 0.0 { "improved" } else { "declined" };
+            println!(
+                "Confidence {} by {:+.1} over {} generations",
+                direction, change, generations
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Print a two-column table of session counts, most common first
+fn print_count_table(heading: &str, counts: &BTreeMap<String, usize>) {
+    let mut rows: Vec<(&String, &usize)> = counts.iter().collect();
+    rows.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+
+    println!();
+    println!("{:<24} {:>9}", heading, "Sessions");
+    for (name, count) in rows {
+        println!("{:<24} {:>9}", name, count);
+    }
+}
+
+/// Serialize templates to JSON, as summaries unless `full` includes their questions
+fn templates_json(templates: &[Template], full: bool) -> Result<String> {
+    Ok(if full {
+        serde_json::to_string_pretty(templates)?
+    } else {
+        let summaries: Vec<TemplateSummary> = templates.iter().map(TemplateSummary::from).collect();
+        serde_json::to_string_pretty(&summaries)?
+    })
+}
+
+/// List available templates
+fn list_templates(json: bool, full: bool) -> Result<()> {
+    let repo = TemplateRepository::new();
+    let templates = repo.get_all_templates();
+
+    if json {
+        println!("{}", templates_json(templates, full)?);
+        return Ok(());
+    }
+
+    println!("🧙 Available Templates");
+
+    if templates.is_empty() {
+        println!("No templates available");
+    } else {
+        for (i, template) in templates.iter().enumerate() {
+            println!("{}. {} ({})", i + 1, template.name, template.domain);
+            println!("   {}", template.description);
+            println!();
+        }
+    }
+
+    Ok(())
+}
+
+/// Show the full details of a template
+fn show_template_info(name: &str) -> Result<()> {
+    let repo = TemplateRepository::new();
+    let template = repo
+        .get_template(name)
+        .context(format!("Template '{}' not found", name))?;
+
+    print!("{}", template_info(template)?);
+    Ok(())
+}
+
+/// Format the full details of a template: domain, hints, initial questions and metadata
+fn template_info(template: &Template) -> Result<String> {
+    let mut info = String::new();
+    writeln!(info, "🧙 Template: {}", template.name)?;
+    writeln!(info, "{}", template.description)?;
+    writeln!(info)?;
+    writeln!(info, "Domain: {}", template.domain)?;
+    writeln!(info, "Starting hints: {}", template.starting_hints)?;
+
+    writeln!(info)?;
+    if template.initial_questions.is_empty() {
+        writeln!(info, "Initial questions: none")?;
+    } else {
+        writeln!(info, "Initial questions:")?;
+        for (i, question) in template.initial_questions.iter().enumerate() {
+            writeln!(
+                info,
+                "{}. {} ({})",
+                i + 1,
+                question.text,
+                question.question_type
+            )?;
+            if let Some(options) = &question.options {
+                for option in options {
+                    writeln!(info, "   - {}", option)?;
                 }
             }
-            QuestionType::FreeText => {
-                let input: String = Input::with_theme(&theme)
-                    .with_prompt("Your answer")
-                    .interact_text()
-                    .context("Failed to get user input")?;
-
-                // Check for special commands
-                if input.trim().to_lowercase() == "back" {
-                    // Go back to previous question
-                    match session_manager.go_back() {
-                        Ok(_) => {
-                            println!("Going back to previous question");
-                            continue;
-                        }
-                        Err(e) => {
-                            println!("Cannot go back: {}", e);
-                            continue;
-                        }
-                    }
-                } else if input.trim().to_lowercase() == "quit" {
-                    // Exit the wizard
-                    println!("Exiting wizard");
-                    return Ok(());
-                }
-
-                input
+            if let Some((min, max)) = question.scale {
+                writeln!(info, "   Scale: {}-{}", min, max)?;
             }
-        };
+            if let Some(help_text) = &question.help_text {
+                writeln!(info, "   Hint: {}", help_text)?;
+            }
+            if let Some(reason) = &question.reason {
+                writeln!(info, "   Why: {}", reason)?;
+            }
+            if let Some(default_answer) = &question.default_answer {
+                writeln!(info, "   Suggested answer: {}", default_answer)?;
+            }
+            if let Some((question_id, answer)) = &question.depends_on {
+                let condition = template
+                    .initial_questions
+                    .iter()
+                    .find(|other| other.id == *question_id)
+                    .map_or(question_id.as_str(), |other| other.text.as_str());
+                writeln!(
+                    info,
+                    "   Only asked if \"{}\" was answered: {}",
+                    condition, answer
+                )?;
+            }
+        }
+    }
 
-        // Answer the question
-        if let Err(e) = session_manager.answer_question(response) {
-            println!("Error answering question: {}", e);
-            break;
+    writeln!(info)?;
+    if template.metadata.is_empty() {
+        writeln!(info, "Metadata: none")?;
+    } else {
+        writeln!(info, "Metadata:")?;
+        for (key, value) in &template.metadata {
+            writeln!(info, "  {}: {}", key, value)?;
         }
+    }
 
-        println!();
+    Ok(info)
+}
+
+/// Get the locale whose domain list to use: the requested language, else the system locale
+fn domain_locale(language: Option<&str>) -> Option<String> {
+    if let Some(language) = language {
+        return Some(language.to_string());
     }
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty() && locale != "C" && locale != "POSIX")
+}
 
-    // Generate project definition
-    println!("Generating project definition...");
-    let markdown = match session_manager.generate_project_definition().await {
-        Ok(md) => md,
+/// List available domains
+fn list_domains(language: Option<String>) -> Result<()> {
+    println!("🧙 Available Domains");
+
+    let repo = TemplateRepository::new();
+    let domains = repo.get_domains_for(domain_locale(language.as_deref()).as_deref());
+
+    if domains.is_empty() {
+        println!("No domains available");
+    } else {
+        for (i, domain) in domains.iter().enumerate() {
+            println!("{}. {}", i + 1, domain);
+        }
+    }
+
+    Ok(())
+}
+
+/// List available models and optionally save one to the configuration
+async fn list_models(pick: bool, progress: ProgressMode) -> Result<()> {
+    if pick {
+        require_prompts(progress, "Picking a model (--pick)")?;
+    }
+
+    println!("🧙 Available Models");
+
+    let llm_client = create_llm_client(None)?;
+    let models = match llm_client.list_models().await {
+        Ok(models) => models,
         Err(e) => {
-            println!("Error generating project definition: {}", e);
-            return Err(e);
+            println!("Could not list models from the provider: {}", e);
+            println!(
+                "Set the model manually in {}",
+                Config::default_path().display()
+            );
+            return Ok(());
         }
     };
 
-    // Display project definition
-    println!("\n{}\n", markdown);
+    if models.is_empty() {
+        println!("No models available");
+        return Ok(());
+    }
 
-    // Save to file if output path is provided
-    if let Some(path) = output_path {
-        println!("Saving project definition to {}", path.display());
-        session_manager.export_output(path)?;
+    print!("{}", format_models_table(&models));
+
+    if pick {
+        let ids: Vec<&str> = models.iter().map(|model| model.id.as_str()).collect();
+        let selection = prompt_with_retry(|| {
+            Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select the model to use")
+                .items(&ids)
+                .default(0)
+                .interact()
+        })?;
+
+        let mut config = Config::load_or_default();
+        config.model = Some(ids[selection].to_string());
+
+        // Remember the model's output limit so max_tokens can be kept within it
+        if let Some(limit) = models[selection].max_completion_tokens() {
+            config.model_output_limits.insert(
+                ids[selection].to_string(),
+                limit.min(u16::MAX as u64) as u16,
+            );
+        }
+        let path = config.save_to_default_path()?;
+        println!("Saved model {} to {}", ids[selection], path.display());
     }
 
-    // Ask if user wants to save the session
-    let save_session = Confirm::with_theme(&theme)
-        .with_prompt("Do you want to save this session for later?")
-        .default(false)
-        .interact()
-        .context("Failed to get user input")?;
+    Ok(())
+}
 
-    if save_session {
-        let session_path: String = Input::with_theme(&theme)
-            .with_prompt("Enter path to save session")
-            .default("wizard_session.json".to_string())
-            .interact_text()
-            .context("Failed to get user input")?;
+/// Diagnose the setup and print a checklist with hints for anything that fails
+async fn run_doctor() -> Result<()> {
+    println!("🧙 Checking your setup");
 
-        println!("Saving session to {}", session_path);
-        session_manager.session.save_to_file(session_path)?;
+    let api_key = std::env::var(doctor::API_KEY_VAR).ok();
+    let mut results = vec![
+        doctor::check_api_key(api_key.as_deref()),
+        doctor::check_config_file(&Config::default_path()),
+        doctor::check_templates(&TemplateRepository::new()),
+    ];
+    results.push(match create_llm_client(None) {
+        Ok(llm_client) => doctor::check_api(&llm_client).await,
+        Err(e) => CheckResult::fail(
+            "API",
+            format!("{:#}", e),
+            "Fix the PROJECTOR_* environment variable named above",
+        ),
+    });
+
+    for result in &results {
+        println!("{}", result);
     }
 
-    println!("Wizard completed successfully!");
+    let failed = results
+        .iter()
+        .filter(|result| result.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{} check(s) failed", failed);
+    }
+    println!("All checks passed");
     Ok(())
 }
+
+/// Create an LLM client, from a named profile of the configuration file if one is given
+fn create_llm_client(profile: Option<&str>) -> Result<LlmClient> {
+    create_llm_client_from(Config::load_or_default(), profile)
+}
+
+/// Create an LLM client from the given settings, using a named profile of them if one is given
+fn create_llm_client_from(settings: Config, profile: Option<&str>) -> Result<LlmClient> {
+    // Get API key from environment
+    let api_key = std::env::var("OPENROUTER_API_KEY").ok();
+
+    // Get optional temperature schedule (e.g. "1.2:0.4") from environment
+    let temperature_schedule = std::env::var("PROJECTOR_TEMPERATURE_SCHEDULE")
+        .ok()
+        .map(|value| TemperatureSchedule::parse(&value))
+        .transpose()
+        .context("Invalid PROJECTOR_TEMPERATURE_SCHEDULE")?;
+
+    // Get optional comma-separated fallback models from environment
+    let fallback_models: Vec<String> = std::env::var("PROJECTOR_FALLBACK_MODELS")
+        .map(|value| {
+            value
+                .split(',')
+                .map(|model| model.trim().to_string())
+                .filter(|model| !model.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // Get optional response size cap from environment
+    let max_response_bytes: Option<usize> = std::env::var("PROJECTOR_MAX_RESPONSE_BYTES")
+        .ok()
+        .map(|value| value.parse())
+        .transpose()
+        .context("Invalid PROJECTOR_MAX_RESPONSE_BYTES")?;
+
+    // Get optional definition context cap from environment
+    let max_definition_context_chars: Option<usize> =
+        std::env::var("PROJECTOR_DEFINITION_CONTEXT_CHARS")
+            .ok()
+            .map(|value| value.parse())
+            .transpose()
+            .context("Invalid PROJECTOR_DEFINITION_CONTEXT_CHARS")?;
+
+    // Get optional question prompt cap from environment
+    let max_question_prompt_chars: Option<usize> = std::env::var("PROJECTOR_QUESTION_PROMPT_CHARS")
+        .ok()
+        .map(|value| value.parse())
+        .transpose()
+        .context("Invalid PROJECTOR_QUESTION_PROMPT_CHARS")?;
+
+    // Find the profile before the top-level settings are taken apart
+    let profile = match profile {
+        Some(name) => match settings.profiles.get(name) {
+            Some(profile) => Some(profile.clone()),
+            None if settings.profiles.is_empty() => anyhow::bail!(
+                "Unknown profile '{}': the configuration file defines no profiles",
+                name
+            ),
+            None => anyhow::bail!(
+                "Unknown profile '{}'. Available profiles: {}",
+                name,
+                settings
+                    .profiles
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        },
+        None => None,
+    };
+
+    // Create config from the top-level settings, with the profile's settings on top
+    let defaults = LlmConfig::default();
+    let mut config = LlmConfig {
+        model: settings.model.unwrap_or(defaults.model),
+        repair_questions: settings.repair_questions.unwrap_or_default(),
+        json_retry_instruction: settings
+            .json_retry_instruction
+            .unwrap_or(defaults.json_retry_instruction),
+        json_retry_max: settings.json_retry_max.unwrap_or(defaults.json_retry_max),
+        max_tokens: settings.max_tokens.unwrap_or(defaults.max_tokens),
+        stop: settings.stop_sequences.filter(|stop| !stop.is_empty()),
+        avoid_topics: settings.avoid_topics,
+        idempotency_header: settings.idempotency_header,
+        network_retries: settings.network_retries.unwrap_or_default(),
+        ..defaults
+    };
+    if let Some(profile) = profile {
+        profile.apply_to(&mut config);
+    }
+
+    // Environment variables take precedence over the configuration file
+    if api_key.is_some() {
+        config.api_key = api_key;
+    }
+    if temperature_schedule.is_some() {
+        config.temperature_schedule = temperature_schedule;
+    }
+    if !fallback_models.is_empty() {
+        config.fallback_models = fallback_models;
+    }
+    if let Some(max_response_bytes) = max_response_bytes {
+        config.max_response_bytes = max_response_bytes;
+    }
+    if let Some(max_definition_context_chars) = max_definition_context_chars {
+        config.max_definition_context_chars = max_definition_context_chars;
+    }
+    if let Some(max_question_prompt_chars) = max_question_prompt_chars {
+        config.max_question_prompt_chars = max_question_prompt_chars;
+    }
+
+    // Output limits describe the models rather than how to use them, so they apply to every profile
+    for (model, limit) in settings.model_output_limits {
+        config.output_limits.entry(model).or_insert(limit);
+    }
+    config.idempotency_header = config.idempotency_header.filter(|header| {
+        let valid = reqwest::header::HeaderName::from_bytes(header.as_bytes()).is_ok();
+        if !valid {
+            println!(
+                "Warning: idempotency_header '{}' is not a valid header name. Not sending idempotency keys.",
+                header
+            );
+        }
+        valid
+    });
+
+    // Create client
+    Ok(LlmClient::with_config(config)?.with_retry_budget(settings.retry_budget))
+}
+
+/// Entry after the options of selection lists that opens the list of session commands
+const COMMANDS_OPTION: &str = "Commands…";
+
+/// Command that goes back to the previous question
+const BACK_COMMAND: &str = "back";
+
+/// Entry at the top of selection lists that goes back to the previous question
+const BACK_OPTION: &str = "◀ Go back";
+
+/// Entry after the options of a shortened selection list that reveals the rest of them
+const SHOW_MORE_OPTION: &str = "Show more…";
+
+/// Command that shows the answers given so far without advancing the session
+const HISTORY_COMMAND: &str = ":history";
+
+/// Command that tags the last answer (`:tag <name>`)
+const TAG_COMMAND: &str = ":tag";
+
+/// Command that raises the question limit (`:more <n>`)
+const MORE_COMMAND: &str = ":more";
+
+/// Command that edits a previous answer (`:edit <n>`)
+const EDIT_COMMAND: &str = ":edit";
+
+/// Command that skips the current question; it still counts towards the total
+const SKIP_COMMAND: &str = ":skip";
+
+/// Command that asks the LLM why the current question matters
+const WHY_COMMAND: &str = ":why";
+
+/// Command that starts a free-form chat with the model before returning to the questions
+const CHAT_COMMAND: &str = ":chat";
+
+/// Command that ends a free-form chat
+const DONE_COMMAND: &str = ":done";
+
+/// Command that lists every session command with its description
+const HELP_COMMAND: &str = ":help";
+
+/// Command that ends the wizard, offering to save the session first
+const QUIT_COMMAND: &str = "quit";
+
+/// Attempts at showing a prompt before a terminal error ends the session
+const INPUT_ATTEMPTS: usize = 3;
+
+/// Command available while answering questions
+struct SessionCommand {
+    /// What to type to run the command
+    name: &'static str,
+    /// Usage including any argument
+    usage: &'static str,
+    /// What the command does
+    description: &'static str,
+}
+
+/// Commands available while answering questions, in the order they are listed
+const SESSION_COMMANDS: &[SessionCommand] = &[
+    SessionCommand {
+        name: BACK_COMMAND,
+        usage: "back",
+        description: "Go back to the previous question (type it, or pick \"◀ Go back\" from a list)",
+    },
+    SessionCommand {
+        name: HISTORY_COMMAND,
+        usage: ":history",
+        description: "Review your answers so far",
+    },
+    SessionCommand {
+        name: TAG_COMMAND,
+        usage: ":tag <name>",
+        description: "Tag your last answer",
+    },
+    SessionCommand {
+        name: MORE_COMMAND,
+        usage: ":more <n>",
+        description: "Allow n more questions",
+    },
+    SessionCommand {
+        name: EDIT_COMMAND,
+        usage: ":edit <n>",
+        description: "Edit answer n",
+    },
+    SessionCommand {
+        name: WHY_COMMAND,
+        usage: ":why",
+        description: "Explain why this question matters, with an example answer",
+    },
+    SessionCommand {
+        name: CHAT_COMMAND,
+        usage: ":chat",
+        description: "Clarify things with the model in free form, then type :done to return",
+    },
+    SessionCommand {
+        name: SKIP_COMMAND,
+        usage: ":skip",
+        description: "Skip this question (it still counts towards the total)",
+    },
+    SessionCommand {
+        name: HELP_COMMAND,
+        usage: ":help",
+        description: "Show this list of commands",
+    },
+    SessionCommand {
+        name: QUIT_COMMAND,
+        usage: "quit",
+        description: "Exit the wizard, offering to save the session (type it, or press Esc in a list)",
+    },
+];
+
+/// One-line summary of the session commands shown dimmed above each question
+fn command_hint() -> String {
+    let names: Vec<&str> = SESSION_COMMANDS
+        .iter()
+        .map(|command| command.name)
+        .collect();
+    style(format!("Commands: {}", names.join("  ")))
+        .dim()
+        .to_string()
+}
+
+/// Line shown dimmed above a question with the reason for asking it
+fn reason_line(reason: &str) -> String {
+    style(format!("Why: {}", reason)).dim().to_string()
+}
+
+/// Print every session command with its description
+fn print_help() {
+    print!("{}", help_text());
+}
+
+/// List every session command with its description
+fn help_text() -> String {
+    let mut help = String::from("Commands (type them, or pick the `:` commands from a list):\n");
+    for command in SESSION_COMMANDS {
+        help.push_str(&format!(
+            "  {:<12} {}\n",
+            command.usage, command.description
+        ));
+    }
+    help.push_str(&format!(
+        "  {:<12} While the next question is being generated: cancel it and revise your last answer\n",
+        "Ctrl+C"
+    ));
+    help
+}
+
+/// Error ending the wizard when Ctrl+C is pressed outside cancellable work
+#[derive(Debug)]
+struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Ctrl+C handling: cancels the work in `cancellable`, and asks the wizard to exit at any other time
+#[derive(Clone)]
+struct Interrupts {
+    /// Whether cancellable work is running
+    cancellable: Arc<AtomicBool>,
+    /// Notified when Ctrl+C is pressed during cancellable work
+    cancelled: Arc<Notify>,
+    /// Notified when Ctrl+C is pressed at any other time
+    exit: Arc<Notify>,
+}
+
+impl Interrupts {
+    /// Create the interrupt state without listening for Ctrl+C
+    fn new() -> Self {
+        Self {
+            cancellable: Arc::new(AtomicBool::new(false)),
+            cancelled: Arc::new(Notify::new()),
+            exit: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Start listening for Ctrl+C
+    fn install() -> Self {
+        let interrupts = Self::new();
+        let listener = interrupts.clone();
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                listener.interrupt();
+            }
+        });
+        interrupts
+    }
+
+    /// Handle a Ctrl+C: cancel the running cancellable work, or ask for an exit
+    ///
+    /// Listening replaced the default handler, so the exit is left to the task
+    /// waiting in `exit_requested`, which can restore the terminal first.
+    fn interrupt(&self) {
+        if self.cancellable.load(Ordering::SeqCst) {
+            self.cancelled.notify_waiters();
+        } else {
+            self.exit.notify_one();
+        }
+    }
+
+    /// Wait until Ctrl+C is pressed outside cancellable work
+    async fn exit_requested(&self) {
+        self.exit.notified().await
+    }
+
+    /// Run a future until it completes or Ctrl+C is pressed, returning `None` if cancelled
+    ///
+    /// A cancelled future is dropped, which aborts any request it was waiting for.
+    async fn cancellable<F: std::future::Future>(&self, future: F) -> Option<F::Output> {
+        let cancelled = self.cancelled.notified();
+        tokio::pin!(cancelled);
+        cancelled.as_mut().enable();
+
+        self.cancellable.store(true, Ordering::SeqCst);
+        let output = tokio::select! {
+            output = future => Some(output),
+            _ = cancelled => None,
+        };
+        self.cancellable.store(false, Ordering::SeqCst);
+        output
+    }
+}
+
+/// Split a `:command <argument>` response into its argument if it is the given command
+fn command_argument<'a>(response: &'a str, command: &str) -> Option<&'a str> {
+    response
+        .trim()
+        .strip_prefix(command)
+        .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+        .map(str::trim)
+}
+
+/// Check whether a response asks to go back, typed or picked from the commands list
+fn is_back_command(response: &str) -> bool {
+    response.trim().eq_ignore_ascii_case(BACK_COMMAND)
+}
+
+/// Fail when prompts are disabled, naming what needed them
+fn require_prompts(progress: ProgressMode, needed_by: &str) -> Result<()> {
+    if !progress.is_interactive() {
+        anyhow::bail!(
+            "{} needs interactive prompts, which are disabled in CI mode (--ci or stdout is not a terminal)",
+            needed_by
+        );
+    }
+    Ok(())
+}
+
+/// Run an interactive prompt, showing it again after a transient terminal error
+fn prompt_with_retry<T>(mut prompt: impl FnMut() -> dialoguer::Result<T>) -> Result<T> {
+    let mut attempt = 1;
+    loop {
+        match prompt() {
+            Ok(value) => return Ok(value),
+            Err(dialoguer::Error::IO(e))
+                if attempt < INPUT_ATTEMPTS && is_transient_input_error(&e) =>
+            {
+                println!("Warning: Could not read your input ({}). Asking again.", e);
+                attempt += 1;
+            }
+            Err(e) => return Err(e).context("Failed to get user input"),
+        }
+    }
+}
+
+/// Check whether a terminal error is worth retrying the prompt for
+fn is_transient_input_error(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut
+    )
+}
+
+/// Get the options to show in a selection list, and whether some are hidden
+///
+/// Only the first `max_display` options are shown until the list is expanded; 0 shows them all.
+fn displayed_options(
+    options: &[String],
+    max_display: Option<usize>,
+    expanded: bool,
+) -> (&[String], bool) {
+    match max_display {
+        Some(max) if max > 0 && !expanded && options.len() > max => (&options[..max], true),
+        _ => (options, false),
+    }
+}
+
+/// Check whether a selection list shows every option from the start, as the default one would be hidden
+fn starts_expanded(max_display: Option<usize>, default: usize) -> bool {
+    max_display.is_some_and(|max| max > 0 && default >= max)
+}
+
+/// Show a selection list of the options between a go-back entry and an entry opening the session commands
+///
+/// With `max_display`, longer lists start with only the first options and a
+/// show-more entry that shows the list again with all of them.
+fn select_with_commands(
+    theme: &ColorfulTheme,
+    options: &[String],
+    default: usize,
+    max_display: Option<usize>,
+) -> Result<String> {
+    let mut expanded = starts_expanded(max_display, default);
+    loop {
+        let (shown, hidden) = displayed_options(options, max_display, expanded);
+        let selection = select_shown_options(theme, shown, hidden, default)?;
+        if selection != SHOW_MORE_OPTION {
+            return Ok(selection);
+        }
+        expanded = true;
+    }
+}
+
+/// What picking an entry of a selection list means
+#[derive(Debug, PartialEq)]
+enum ListChoice {
+    /// The go-back entry was picked
+    Back,
+    /// An option (or the show-more entry) was picked
+    Item(String),
+    /// The entry opening the session commands was picked
+    Commands,
+    /// The list was left with Esc
+    Quit,
+}
+
+/// Build the entries of a selection list: the go-back entry, the options, a show-more entry if some are hidden, and the commands entry
+fn selection_items(options: &[String], hidden: bool) -> Vec<String> {
+    let mut items = vec![BACK_OPTION.to_string()];
+    items.extend_from_slice(options);
+    if hidden {
+        items.push(SHOW_MORE_OPTION.to_string());
+    }
+    items.push(COMMANDS_OPTION.to_string());
+    items
+}
+
+/// Interpret the entry picked from a selection list, `None` meaning the list was left with Esc
+fn list_choice(items: &[String], selection: Option<usize>) -> ListChoice {
+    match selection {
+        None => ListChoice::Quit,
+        Some(selection) if items[selection] == BACK_OPTION => ListChoice::Back,
+        Some(selection) if items[selection] == COMMANDS_OPTION => ListChoice::Commands,
+        Some(selection) => ListChoice::Item(items[selection].clone()),
+    }
+}
+
+/// Show one rendering of a selection list, with a show-more entry if some options are hidden
+fn select_shown_options(
+    theme: &ColorfulTheme,
+    options: &[String],
+    hidden: bool,
+    default: usize,
+) -> Result<String> {
+    let items = selection_items(options, hidden);
+
+    loop {
+        // The default refers to the options, which come after the go-back entry
+        let selection = prompt_with_retry(|| {
+            Select::with_theme(theme)
+                .items(&items)
+                .default(default + 1)
+                .interact_opt()
+        })?;
+
+        match list_choice(&items, selection) {
+            ListChoice::Back => return Ok(BACK_COMMAND.to_string()),
+            ListChoice::Item(item) => return Ok(item),
+            // Leaving the list with Esc quits the wizard
+            ListChoice::Quit => return Ok(QUIT_COMMAND.to_string()),
+            ListChoice::Commands => {
+                if let Some(command) = select_command(theme)? {
+                    return Ok(command.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Show the session commands to pick one from, or `None` to return to the question
+fn select_command(theme: &ColorfulTheme) -> Result<Option<&'static str>> {
+    let items: Vec<String> = SESSION_COMMANDS
+        .iter()
+        .map(|command| format!("{:<12} {}", command.usage, command.description))
+        .collect();
+
+    let selection = prompt_with_retry(|| {
+        Select::with_theme(theme)
+            .with_prompt("Command (Esc to return to the question)")
+            .items(&items)
+            .default(0)
+            .interact_opt()
+    })?;
+    Ok(selection.map(|selection| SESSION_COMMANDS[selection].name))
+}
+
+/// Get the option selected at first: the one matching a known answer, else the first
+fn default_option(prefilled: Option<&str>, options: &[String]) -> usize {
+    prefilled
+        .and_then(|answer| prefill::option_index(answer, options))
+        .unwrap_or(0)
+}
+
+/// Ask for the response to a question with the prompt matching its type
+fn prompt_response(
+    theme: &ColorfulTheme,
+    question: &Question,
+    prefilled: Option<&str>,
+    max_options_display: Option<usize>,
+) -> Result<String> {
+    let default_option = |options: &[String]| default_option(prefilled, options);
+
+    Ok(match question.question_type {
+        QuestionType::MultipleChoice => match question.options.as_deref() {
+            // Don't make the user pick the only available option
+            Some([only]) => {
+                println!("Only one option, answering: {}", only);
+                only.clone()
+            }
+            Some(options) => {
+                select_with_commands(theme, options, default_option(options), max_options_display)?
+            }
+            None => "Invalid question: missing options".to_string(),
+        },
+        QuestionType::YesNo => {
+            let options = question.choices().unwrap_or_default();
+            select_with_commands(theme, &options, default_option(&options), None)?
+        }
+        QuestionType::RatingScale => {
+            if let Some((min, max)) = question.scale {
+                let options: Vec<String> = (min..=max).map(|n| format!("{}", n)).collect();
+                select_with_commands(theme, &options, default_option(&options), None)?
+            } else {
+                "Invalid question: missing scale".to_string()
+            }
+        }
+        QuestionType::FreeText => {
+            prompt_with_retry(|| free_text_input(theme, question, prefilled).interact_text())?
+        }
+    })
+}
+
+/// Text a free text prompt starts with
+#[derive(Debug, PartialEq)]
+enum FreeTextPrefill<'a> {
+    /// A known answer, typed into the prompt for the user to change
+    Initial(&'a str),
+    /// The question's suggested answer, accepted by pressing Enter
+    Default(&'a str),
+}
+
+/// Get what a free text prompt starts with: a known answer, else the suggested answer
+fn free_text_prefill<'a>(
+    question: &'a Question,
+    prefilled: Option<&'a str>,
+) -> Option<FreeTextPrefill<'a>> {
+    prefilled.map(FreeTextPrefill::Initial).or_else(|| {
+        question
+            .default_answer
+            .as_deref()
+            .map(FreeTextPrefill::Default)
+    })
+}
+
+/// Build the prompt for a free text answer, pre-filled as `free_text_prefill` describes
+fn free_text_input<'a>(
+    theme: &'a ColorfulTheme,
+    question: &Question,
+    prefilled: Option<&str>,
+) -> Input<'a, String> {
+    let input = Input::<String>::with_theme(theme).with_prompt("Your answer");
+    match free_text_prefill(question, prefilled) {
+        Some(FreeTextPrefill::Initial(answer)) => input.with_initial_text(answer),
+        Some(FreeTextPrefill::Default(answer)) => input.default(answer.to_string()),
+        None => input,
+    }
+}
+
+/// Get the response recorded for a question that wasn't answered in time
+fn timed_out_response(
+    action: QuestionTimeoutAction,
+    question: &Question,
+    prefilled: Option<&str>,
+) -> String {
+    let default = prefilled.or(question.default_answer.as_deref());
+    match (action, default) {
+        (QuestionTimeoutAction::AcceptDefault, Some(answer)) => answer.to_string(),
+        _ => SKIP_COMMAND.to_string(),
+    }
+}
+
+/// Ask for the response to a question, giving up on it when the time limit is reached
+///
+/// A selection list can't be withdrawn once it is shown, so timed questions are
+/// asked as a typed line instead, which is only read once it has been entered.
+/// When time is up the wizard moves on at once, leaving nothing waiting for input.
+async fn prompt_response_within(
+    limit: Duration,
+    action: QuestionTimeoutAction,
+    question: &Question,
+    prefilled: Option<&str>,
+    max_options_display: Option<usize>,
+) -> Result<String> {
+    // Questions answered without input (a single option, or a broken one) need no time limit
+    let choices = question.choices();
+    if !matches!(question.question_type, QuestionType::FreeText)
+        && choices.as_ref().is_none_or(|choices| choices.len() < 2)
+    {
+        return prompt_response(
+            &ColorfulTheme::default(),
+            question,
+            prefilled,
+            max_options_display,
+        );
+    }
+
+    let default = timed_default(question, choices.as_deref(), prefilled);
+    if let Some(choices) = &choices {
+        for (number, choice) in choices.iter().enumerate() {
+            println!("  {}. {}", number + 1, choice);
+        }
+    }
+    match &default {
+        Some(default) => print!("Your answer [{}] ({} seconds): ", default, limit.as_secs()),
+        None => print!("Your answer ({} seconds): ", limit.as_secs()),
+    }
+    std::io::Write::flush(&mut std::io::stdout())?;
+
+    let line = answer_within(limit, action, question, prefilled, read_entered_line()).await?;
+    Ok(timed_line_response(
+        question,
+        choices.as_deref(),
+        default.as_deref(),
+        &line,
+    ))
+}
+
+/// Get the answer an empty line accepts: the known answer, else the suggested or first option
+fn timed_default(
+    question: &Question,
+    choices: Option<&[String]>,
+    prefilled: Option<&str>,
+) -> Option<String> {
+    match choices {
+        Some(choices) => choices.get(default_option(prefilled, choices)).cloned(),
+        None => free_text_prefill(question, prefilled).map(|prefill| match prefill {
+            FreeTextPrefill::Initial(answer) | FreeTextPrefill::Default(answer) => {
+                answer.to_string()
+            }
+        }),
+    }
+}
+
+/// Interpret a typed answer to a timed question
+///
+/// An empty line accepts the default. Options can be typed or picked by number,
+/// except on rating scales where the number is the rating; anything else, such
+/// as a session command, is kept as typed.
+fn timed_line_response(
+    question: &Question,
+    choices: Option<&[String]>,
+    default: Option<&str>,
+    line: &str,
+) -> String {
+    let line = line.trim();
+    if line.is_empty() {
+        return default.unwrap_or_default().to_string();
+    }
+
+    let Some(choices) = choices else {
+        return line.to_string();
+    };
+    if let Some(choice) = choices
+        .iter()
+        .find(|choice| choice.eq_ignore_ascii_case(line))
+    {
+        return choice.clone();
+    }
+    if !matches!(question.question_type, QuestionType::RatingScale)
+        && let Ok(number) = line.parse::<usize>()
+        && let Some(choice) = number.checked_sub(1).and_then(|index| choices.get(index))
+    {
+        return choice.clone();
+    }
+    line.to_string()
+}
+
+/// Wait for the answer to a question, recording the timeout response if time runs out first
+async fn answer_within<F: std::future::Future<Output = Result<String>>>(
+    limit: Duration,
+    action: QuestionTimeoutAction,
+    question: &Question,
+    prefilled: Option<&str>,
+    input: F,
+) -> Result<String> {
+    match tokio::time::timeout(limit, input).await {
+        Ok(response) => response,
+        Err(_) => {
+            let response = timed_out_response(action, question, prefilled);
+            if response == SKIP_COMMAND {
+                println!(
+                    "\nTime is up after {} seconds; skipping the question",
+                    limit.as_secs()
+                );
+            } else {
+                println!(
+                    "\nTime is up after {} seconds; answering: {}",
+                    limit.as_secs(),
+                    response
+                );
+            }
+            Ok(response)
+        }
+    }
+}
+
+/// Read a line from the terminal once it has been entered, without blocking a thread
+///
+/// The terminal hands over input a line at a time, so the line is ready as soon as
+/// stdin is readable. Dropping the future stops waiting for it.
+#[cfg(unix)]
+async fn read_entered_line() -> Result<String> {
+    let stdin =
+        tokio::io::unix::AsyncFd::with_interest(std::io::stdin(), tokio::io::Interest::READABLE)
+            .context("Timed questions need a terminal")?;
+
+    let mut ready = stdin.readable().await?;
+    let mut line = String::new();
+    if ready.get_inner().read_line(&mut line)? == 0 {
+        anyhow::bail!("The input was closed before the question was answered");
+    }
+    ready.clear_ready();
+    Ok(line)
+}
+
+/// Read a line from the terminal on a blocking thread
+///
+/// Without readiness notifications for stdin, a line abandoned at the time limit is
+/// still read, and discarded, once it has been entered.
+#[cfg(not(unix))]
+async fn read_entered_line() -> Result<String> {
+    tokio::task::spawn_blocking(|| {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map(|_| line)
+    })
+    .await?
+    .context("Failed to read the answer")
+}
+
+/// Run a future until it completes or the deadline passes, returning `None` at the deadline
+async fn before_deadline<F: std::future::Future>(
+    deadline: Option<tokio::time::Instant>,
+    future: F,
+) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Report that the session time limit was reached, aborting in strict mode
+fn stop_at_time_limit(
+    session_manager: &SessionManager,
+    theme: &ColorfulTheme,
+    progress: ProgressMode,
+    seconds: u64,
+    strict: bool,
+) -> Result<()> {
+    println!("The session time limit of {} seconds was reached", seconds);
+    if strict {
+        offer_to_save_session(session_manager, theme, progress)?;
+        anyhow::bail!(
+            "Session time limit of {} seconds exceeded (--strict-timeout)",
+            seconds
+        );
+    }
+
+    println!(
+        "Generating the project definition from the {} answers collected",
+        session_manager.question_count()
+    );
+    Ok(())
+}
+
+/// Exchange free-form messages with the model until the user types :done
+///
+/// Each message is recorded as a free-text answer to the model's previous message.
+async fn chat_freely(
+    session_manager: &mut SessionManager,
+    theme: &ColorfulTheme,
+    progress: &ProgressMode,
+) -> Result<()> {
+    println!(
+        "Chatting freely with the model; your messages are kept as answers. Type {} to return to the questions.",
+        DONE_COMMAND
+    );
+    let mut prompt = session::CHAT_OPENER.to_string();
+    println!("\n{}", prompt);
+
+    loop {
+        let message: String = prompt_with_retry(|| {
+            Input::<String>::with_theme(theme)
+                .with_prompt("You")
+                .interact_text()
+        })?;
+        if message.trim().eq_ignore_ascii_case(DONE_COMMAND) {
+            println!("Back to the questions\n");
+            return Ok(());
+        }
+        if !passes_moderation(session_manager, message.trim()).await {
+            continue;
+        }
+
+        progress.log("Waiting for the model's reply...");
+        match session_manager.chat(&prompt, message.trim()).await {
+            Ok(reply) => prompt = reply,
+            Err(e) => {
+                println!("Could not continue the chat: {}", e);
+                prompt = session::CHAT_OPENER.to_string();
+            }
+        }
+        println!("\n{}", prompt);
+    }
+}
+
+/// Check an answer under the moderation policy, telling the user why it was flagged
+///
+/// Returns false if the answer was refused and must not be sent to the model.
+async fn passes_moderation(session_manager: &SessionManager, response: &str) -> bool {
+    match session_manager.moderate_answer(response).await {
+        ModerationOutcome::Allowed => true,
+        ModerationOutcome::Warned(reason) => {
+            println!("Warning: this answer is sent anyway, but {}", reason);
+            true
+        }
+        ModerationOutcome::Refused(reason) => {
+            println!(
+                "This answer was not sent because {}. Please rephrase it.",
+                reason
+            );
+            false
+        }
+    }
+}
+
+/// Print the numbered questions and answers given so far
+fn print_history(context: &Context) {
+    println!();
+    if context.history.is_empty() {
+        println!("No questions answered yet");
+    } else {
+        println!("Answers so far:");
+        for (i, answer) in context.history.iter().enumerate() {
+            println!("{}. {}", i + 1, answer.question.text);
+            println!("   → {}", answer.response);
+            if !answer.tags.is_empty() {
+                println!("   Tags: {}", answer.tags.join(", "));
+            }
+            if !answer.revisions.is_empty() {
+                println!("   Edited {} time(s)", answer.revisions.len());
+            }
+        }
+    }
+    println!();
+}
+
+/// Ask for a new response to a previously answered question
+fn prompt_edited_response(theme: &ColorfulTheme, answer: &Answer) -> Result<String> {
+    println!("Editing: {}", answer.question.text);
+    println!("Current answer: {}", answer.response);
+
+    match answer.question.choices() {
+        Some(options) => {
+            let current = options
+                .iter()
+                .position(|option| *option == answer.response)
+                .unwrap_or(0);
+            let selection = prompt_with_retry(|| {
+                Select::with_theme(theme)
+                    .items(&options)
+                    .default(current)
+                    .interact()
+            })?;
+            Ok(options[selection].clone())
+        }
+        None => prompt_with_retry(|| {
+            Input::with_theme(theme)
+                .with_prompt("New answer")
+                .with_initial_text(answer.response.clone())
+                .interact_text()
+        }),
+    }
+}
+
+/// Let the user revise their last answer after cancelling the generation of the next question
+async fn revise_last_answer(
+    session_manager: &mut SessionManager,
+    theme: &ColorfulTheme,
+) -> Result<()> {
+    let Some(index) = session_manager.session.context.history.len().checked_sub(1) else {
+        println!("There is no previous question to go back to");
+        return Ok(());
+    };
+
+    let edited = prompt_edited_response(theme, &session_manager.session.context.history[index])?;
+    if !passes_moderation(session_manager, &edited).await {
+        return Ok(());
+    }
+    match session_manager.session.context.edit_answer(index, edited) {
+        Ok(true) => {
+            session_manager.protect_sensitive_answers();
+            println!("Answer {} updated", index + 1);
+        }
+        Ok(false) => println!("Answer {} unchanged", index + 1),
+        Err(e) => println!("Cannot edit answer: {}", e),
+    }
+    Ok(())
+}
+
+/// Run the wizard, stopping it when Ctrl+C is pressed outside question generation
+async fn run_wizard(
+    session: Session,
+    llm_client: LlmClient,
+    options: WizardArgs,
+    progress: ProgressMode,
+) -> Result<()> {
+    // Ctrl+C while a question is being generated goes back to the last answer
+    let interrupts = Interrupts::install();
+    tokio::select! {
+        result = run_wizard_steps(session, llm_client, options, progress, &interrupts) => result,
+        _ = interrupts.exit_requested() => {
+            // The wizard is dropped by now, releasing the session lock; a prompt or
+            // spinner may have hidden the cursor, so show it again before exiting
+            let _ = dialoguer::console::Term::stdout().show_cursor();
+            println!();
+            Err(Interrupted.into())
+        }
+    }
+}
+
+/// Run the wizard's question loop and generate the definition
+async fn run_wizard_steps(
+    mut session: Session,
+    llm_client: LlmClient,
+    options: WizardArgs,
+    progress: ProgressMode,
+    interrupts: &Interrupts,
+) -> Result<()> {
+    if options.answers.is_none() {
+        require_prompts(progress, "Answering questions without --answers <PATH>")?;
+    }
+
+    let WizardArgs {
+        output: output_path,
+        ask_certainty,
+        min_confidence,
+        min_questions,
+        max_followups,
+        clarify,
+        max_clarifiers,
+        split_generation,
+        parallel_sections,
+        batch_questions,
+        max_options_display,
+        stream,
+        confidence_style,
+        no_confidence,
+        seed: _,
+        model: _,
+        doc_style: _,
+        flow: _,
+        avoid: _,
+        profile: _,
+        language,
+        check_language,
+        quiet,
+        append,
+        format,
+        metrics,
+        metrics_json,
+        avoid_repeats: _,
+        history_cap,
+        history_cap_policy,
+        questions_only,
+        timeout,
+        strict_timeout,
+        question_timeout,
+        on_question_timeout,
+        exhaustion_threshold,
+        running_notes,
+        prefill,
+        answers,
+        transcript: _,
+        redact_sensitive,
+        #[cfg(feature = "pdf")]
+        pdf,
+    } = options;
+
+    let settings = Config::load_or_default();
+    let max_options_display = max_options_display.or(settings.max_options_display);
+    let prefill = load_prefill(prefill.as_deref())?;
+    let answers = load_answers(answers.as_deref())?;
+    let confidence_style = confidence_style
+        .or(settings.confidence_style)
+        .unwrap_or_default();
+
+    // Wall-clock limit for the interview, counted from the start of the wizard
+    let time_limit = timeout.or(settings.session_timeout).map(|seconds| {
+        (
+            seconds,
+            tokio::time::Instant::now() + Duration::from_secs(seconds),
+        )
+    });
+    // Time-box for each answer, and what to do with a question left unanswered
+    let question_time_limit = question_timeout
+        .or(settings.question_timeout)
+        .map(Duration::from_secs);
+    let question_timeout_action = on_question_timeout
+        .or(settings.question_timeout_action)
+        .unwrap_or_default();
+    let default_confidence = match settings.default_confidence {
+        Some(value) => ConfidenceLevel::from_value(value).unwrap_or_else(|| {
+            println!(
+                "Warning: default_confidence must be between 1 and 5, got {}. Using 3.",
+                value
+            );
+            ConfidenceLevel::default()
+        }),
+        None => ConfidenceLevel::default(),
+    };
+
+    if let Some(max_answers) = history_cap {
+        session.context.history_cap = Some(HistoryCap {
+            max_answers,
+            policy: history_cap_policy,
+        });
+    }
+
+    let mut session_manager = SessionManager::new(session, llm_client.with_progress(progress))
+        .with_markdown_style(settings.markdown_style.clone().unwrap_or_default())
+        .with_split_generation(split_generation)
+        .with_parallel_sections(parallel_sections)
+        .with_stream(stream)
+        .with_confidence_style(confidence_style)
+        .with_default_confidence(default_confidence)
+        .with_omit_confidence(no_confidence)
+        .with_duplicate_sections(settings.duplicate_sections.unwrap_or_default())
+        .with_exhaustion_threshold(
+            exhaustion_threshold
+                .or(settings.exhaustion_threshold)
+                .unwrap_or(DEFAULT_EXHAUSTION_THRESHOLD),
+        )
+        .with_output_format(format)
+        .with_metrics_appendix(metrics)
+        .with_running_notes(running_notes)
+        .with_moderation(load_moderation(&settings)?)
+        .with_batch_questions(batch_questions.map(usize::from));
+    if let Some(path) = &output_path {
+        session_manager = session_manager.with_raw_output_path(raw_output_path(path));
+    }
+    session_manager.session.redact_sensitive = redact_sensitive;
+    session_manager.start();
+
+    let domains =
+        TemplateRepository::new().get_domains_for(domain_locale(language.as_deref()).as_deref());
+
+    println!(
+        "Starting wizard session with {} questions",
+        session_manager.max_questions()
+    );
+    if !quiet {
+        print_help();
+    }
+    println!();
+
+    let mut interview = Interview {
+        session_manager,
+        theme: ColorfulTheme::default(),
+        progress,
+        interrupts,
+        prefill,
+        answers,
+        domains,
+        ask_certainty,
+        min_confidence,
+        min_questions,
+        max_followups,
+        clarify,
+        max_clarifiers,
+        batch_questions: batch_questions.is_some(),
+        max_options_display,
+        quiet,
+        question_time_limit,
+        question_timeout_action,
+        followups_asked: 0,
+        clarifiers_asked: 0,
+        unclear: None,
+    };
+    // The time limit covers the whole interview, including the checks after each answer
+    let deadline = time_limit.map(|(_, deadline)| deadline);
+    let end = match before_deadline(deadline, interview.run()).await {
+        Some(end) => end?,
+        None => {
+            let seconds = time_limit.map_or(0, |(seconds, _)| seconds);
+            stop_at_time_limit(
+                &interview.session_manager,
+                &interview.theme,
+                progress,
+                seconds,
+                strict_timeout,
+            )?;
+            InterviewEnd::Complete
+        }
+    };
+    let Interview {
+        mut session_manager,
+        theme,
+        ..
+    } = interview;
+
+    // Exit the wizard, keeping the answers if the user wants to
+    if end == InterviewEnd::Quit {
+        println!("Exiting wizard");
+        offer_to_save_session(&session_manager, &theme, progress)?;
+        return Ok(());
+    }
+
+    // Export the interview instead of generating the document
+    if questions_only {
+        export_interview(&session_manager, output_path.as_deref())?;
+        offer_to_save_session(&session_manager, &theme, progress)?;
+        println!("Wizard completed successfully!");
+        return Ok(());
+    }
+
+    // Generate project definition
+    progress.log("Generating project definition...");
+    let markdown = match session_manager.generate_project_definition().await {
+        Ok(md) => md,
+        Err(e) => {
+            println!("Error generating project definition: {}", e);
+            if let Some(path) = &output_path {
+                let raw_path = raw_output_path(path);
+                if raw_path.exists() {
+                    println!("The raw LLM response was saved to {}", raw_path.display());
+                }
+            }
+            offer_to_save_session(&session_manager, &theme, progress)?;
+            return Err(e.into());
+        }
+    };
+
+    // Display project definition
+    println!("\n{}\n", markdown);
+
+    // Warn when a weak model ignored the requested language
+    if check_language
+        && let Some(requested) = &language
+        && let Some(warning) = language_warning(&markdown, requested)
+    {
+        println!("{}", warning);
+    }
+
+    // Save to file if output path is provided, otherwise offer to save it
+    let output_path = match output_path {
+        Some(path) => Some(path),
+        None => prompt_output_path(&theme, progress, format, session_manager.file_stem())?,
+    };
+    if let Some(path) = output_path {
+        println!("Saving project definition to {}", path.display());
+        session_manager.export_output(&path)?;
+        remember_output_dir(&path);
+    }
+
+    if let Some(path) = metrics_json {
+        println!("Saving session metrics to {}", path.display());
+        session_manager.export_metrics(path)?;
+    }
+
+    if let Some(path) = append {
+        println!("Appending project definition to {}", path.display());
+        session_manager.append_to_portfolio(path)?;
+    }
+
+    #[cfg(feature = "pdf")]
+    if let Some(path) = pdf {
+        println!("Saving PDF to {}", path.display());
+        session_manager.export_pdf(path)?;
+    }
+
+    // Ask if user wants to save the session
+    offer_to_save_session(&session_manager, &theme, progress)?;
+
+    println!("Wizard completed successfully!");
+    Ok(())
+}
+
+/// How the question loop goes on after one of its steps
+enum Step<T> {
+    /// Go on to the next step with this value
+    Next(T),
+    /// Start over with the next question
+    Restart,
+    /// End the interview and generate the project definition
+    Finish,
+    /// Leave the wizard without generating the project definition
+    Quit,
+}
+
+impl<T> Step<T> {
+    /// How the interview ends when a step stops the question loop
+    fn end(self) -> InterviewEnd {
+        match self {
+            Step::Quit => InterviewEnd::Quit,
+            _ => InterviewEnd::Complete,
+        }
+    }
+}
+
+/// How an interview ended
+#[derive(Debug, PartialEq)]
+enum InterviewEnd {
+    /// The questions are done, so the project definition is generated
+    Complete,
+    /// The user left the wizard
+    Quit,
+}
+
+/// What a response given instead of an answer did
+#[derive(Debug, PartialEq)]
+enum CommandOutcome {
+    /// The response is not a command, so it answers the question
+    Answer,
+    /// The command is done and the question is shown again
+    AskAgain,
+    /// The session went back to the previous question
+    WentBack,
+    /// The user asked to leave the wizard
+    Quit,
+}
+
+/// What the next question is about
+struct QuestionPlan {
+    /// What the last answer left unclear, for a clarifying question
+    clarifying: Option<String>,
+    /// Weak section of a refined session the question is about
+    focus_area: Option<String>,
+    /// Section the question follows up on: the focus area, or one the confidence gate found
+    followup_area: Option<String>,
+}
+
+/// The question loop of a wizard session, with the options it runs with
+struct Interview<'a> {
+    /// The session the questions are asked for
+    session_manager: SessionManager,
+    /// Theme of the prompts
+    theme: ColorfulTheme,
+    /// How progress is reported, and whether prompts may be shown
+    progress: ProgressMode,
+    /// Ctrl+C handling while a question is generated
+    interrupts: &'a Interrupts,
+    /// Known answers to pre-fill the prompts with
+    prefill: Option<Prefill>,
+    /// Answers to give without prompting
+    answers: Option<Prefill>,
+    /// Domains the session's domain can be inferred from
+    domains: Vec<String>,
+    /// Whether to ask how certain the user is about each answer
+    ask_certainty: bool,
+    /// Confidence every section should reach before the interview ends early
+    min_confidence: Option<u8>,
+    /// Questions answered before the confidence gate is checked
+    min_questions: Option<usize>,
+    /// Follow-up questions the confidence gate may ask
+    max_followups: usize,
+    /// Whether to ask a clarifying question after a vague answer
+    clarify: bool,
+    /// Clarifying questions that may be asked
+    max_clarifiers: usize,
+    /// Whether questions are generated in batches to pick from
+    batch_questions: bool,
+    /// Options of a multiple choice question shown before a show-more entry
+    max_options_display: Option<usize>,
+    /// Whether to hide the command hint above each question
+    quiet: bool,
+    /// Time-box for each answer
+    question_time_limit: Option<Duration>,
+    /// What to do with a question left unanswered in time
+    question_timeout_action: QuestionTimeoutAction,
+    /// Follow-up questions asked by the confidence gate
+    followups_asked: usize,
+    /// Clarifying questions asked
+    clarifiers_asked: usize,
+    /// What the last answer left unclear, to be clarified next
+    unclear: Option<String>,
+}
+
+impl Interview<'_> {
+    /// Ask questions until the interview ends
+    async fn run(&mut self) -> Result<InterviewEnd> {
+        loop {
+            let plan = match self.plan_question().await? {
+                Step::Next(plan) => plan,
+                Step::Restart => continue,
+                step => return Ok(step.end()),
+            };
+            let question = match self.generate_question(&plan).await? {
+                Step::Next(question) => question,
+                Step::Restart => continue,
+                step => return Ok(step.end()),
+            };
+            let question = match self.choose_question(question, &plan)? {
+                Step::Next(question) => question,
+                Step::Restart => continue,
+                step => return Ok(step.end()),
+            };
+            let response = match self.ask_for_response(&question).await? {
+                Step::Next(response) => response,
+                Step::Restart => continue,
+                step => return Ok(step.end()),
+            };
+            match self.record_response(&plan, response)? {
+                Step::Next(()) => self.after_answer(&plan).await,
+                Step::Restart => {}
+                step => return Ok(step.end()),
+            }
+        }
+    }
+
+    /// Decide what the next question is about, or end the interview
+    async fn plan_question(&mut self) -> Result<Step<QuestionPlan>> {
+        // Check if we've reached the maximum number of questions
+        let current_count = self.session_manager.question_count();
+        let max_questions = self.session_manager.max_questions();
+
+        // A vague answer is clarified first, even when the last question was reached
+        let clarifying = self.unclear.take();
+
+        // Questions about a refined session's weakest sections come next
+        let focus_area = self
+            .session_manager
+            .next_focus_area()
+            .filter(|_| clarifying.is_none());
+        let mut followup_area = focus_area.clone();
+        if clarifying.is_none() && followup_area.is_none() {
+            // Once enough questions are answered, check whether any section is still too weak
+            if let Some(threshold) = self
+                .min_confidence
+                .filter(|_| self.followups_asked < self.max_followups)
+            {
+                let min_questions = self.min_questions.unwrap_or(max_questions);
+                if current_count >= min_questions {
+                    self.progress
+                        .log("Checking whether more information is needed...");
+                }
+                match self
+                    .session_manager
+                    .confidence_gate(threshold, min_questions)
+                    .await
+                {
+                    Ok(ConfidenceGate::NotReached) => {}
+                    Ok(ConfidenceGate::FollowUp(area)) => {
+                        println!("Asking a follow-up question about: {}", area);
+                        self.followups_asked += 1;
+                        followup_area = Some(area);
+                    }
+                    Ok(ConfidenceGate::Passed) => {
+                        println!(
+                            "All sections are projected to reach {}/5 confidence",
+                            threshold
+                        );
+                        // Finish early once the minimum is reached; at the limit, offer more
+                        if current_count < max_questions {
+                            return Ok(Step::Finish);
+                        }
+                    }
+                    Err(e) => println!("Error checking section confidence: {}", e),
+                }
+            }
+
+            if followup_area.is_none() && current_count >= max_questions {
+                println!("Maximum number of questions reached");
+                if !self.progress.is_interactive() {
+                    return Ok(Step::Finish);
+                }
+
+                // Let the user extend the interview rather than being cut off
+                let more: usize = prompt_with_retry(|| {
+                    Input::with_theme(&self.theme)
+                        .with_prompt("Add more questions? (0 to finish)")
+                        .default(0)
+                        .interact_text()
+                })?;
+
+                if more == 0 {
+                    return Ok(Step::Finish);
+                }
+
+                self.session_manager.extend_max_questions(more);
+                return Ok(Step::Restart);
+            }
+        }
+
+        Ok(Step::Next(QuestionPlan {
+            clarifying,
+            focus_area,
+            followup_area,
+        }))
+    }
+
+    /// Generate the planned question, offering to try again if that fails
+    async fn generate_question(&mut self, plan: &QuestionPlan) -> Result<Step<Question>> {
+        self.progress.log(&format!(
+            "Generating question {}/{}... (Ctrl+C to go back)",
+            self.session_manager.question_count() + 1,
+            self.session_manager.max_questions()
+        ));
+        let session_manager = &mut self.session_manager;
+        let generation = self.interrupts.cancellable(async {
+            match (&plan.clarifying, &plan.followup_area) {
+                (Some(missing), _) => session_manager.generate_clarifying_question(missing).await,
+                (None, Some(area)) => session_manager.generate_followup_question(area).await,
+                (None, None) => session_manager.generate_next_question().await,
+            }
+            .cloned()
+        });
+        let Some(question) = generation.await else {
+            // Nothing was recorded for the cancelled question, so no question is used up
+            println!("Question generation cancelled");
+            if plan.clarifying.is_some() {
+                self.clarifiers_asked -= 1;
+            } else if plan.focus_area.is_none() && plan.followup_area.is_some() {
+                self.followups_asked -= 1;
+            }
+            if self.progress.is_interactive() {
+                revise_last_answer(&mut self.session_manager, &self.theme).await?;
+            }
+            return Ok(Step::Restart);
+        };
+
+        match question {
+            Ok(question) => Ok(Step::Next(question)),
+            Err(e) => {
+                println!("Error generating question: {}", e);
+                if self.session_manager.retries_remaining() == Some(0) {
+                    println!("The session's retry budget is used up; no more automatic retries");
+                }
+
+                // Let the user decide whether another attempt is worth it
+                let retry = self.progress.is_interactive()
+                    && prompt_with_retry(|| {
+                        Confirm::with_theme(&self.theme)
+                            .with_prompt("Do you want to try again?")
+                            .default(true)
+                            .interact()
+                    })?;
+                if retry {
+                    self.session_manager.session.clear_error();
+                    self.unclear = plan.clarifying.clone();
+                    return Ok(Step::Restart);
+                }
+
+                offer_to_save_session(&self.session_manager, &self.theme, self.progress)?;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Offer to end an exhausted interview, and let the user pick from a batch of questions
+    fn choose_question(
+        &mut self,
+        mut question: Question,
+        plan: &QuestionPlan,
+    ) -> Result<Step<Question>> {
+        // Offer to stop when the model keeps asking what was already covered
+        if self.session_manager.seems_exhausted() {
+            println!(
+                "The last questions mostly repeat earlier ones; the model may have run out of new questions"
+            );
+            let finish = !self.progress.is_interactive()
+                || prompt_with_retry(|| {
+                    Confirm::with_theme(&self.theme)
+                        .with_prompt("End the interview now and generate the project definition?")
+                        .default(true)
+                        .interact()
+                })?;
+            if finish {
+                return Ok(Step::Finish);
+            }
+            self.session_manager.reset_exhaustion();
+        }
+
+        // Let the user pick which of the batch of questions to answer now
+        if self.batch_questions
+            && self.progress.is_interactive()
+            && plan.clarifying.is_none()
+            && plan.followup_area.is_none()
+            && !self.session_manager.queued_questions().is_empty()
+        {
+            let candidates: Vec<&str> = std::iter::once(question.text.as_str())
+                .chain(
+                    self.session_manager
+                        .queued_questions()
+                        .iter()
+                        .map(|queued| queued.text.as_str()),
+                )
+                .collect();
+            let choice = prompt_with_retry(|| {
+                Select::with_theme(&self.theme)
+                    .with_prompt(
+                        "Which question do you want to answer now? (the others stay queued)",
+                    )
+                    .items(&candidates)
+                    .default(0)
+                    .interact()
+            })?;
+            if choice > 0 {
+                question = self
+                    .session_manager
+                    .choose_queued_question(choice - 1)?
+                    .clone();
+            }
+        }
+
+        Ok(Step::Next(question))
+    }
+
+    /// Show the question and get the response, showing it again after commands that don't answer it
+    async fn ask_for_response(&mut self, question: &Question) -> Result<Step<String>> {
+        loop {
+            // Remind the user of the commands
+            if !self.quiet {
+                println!("{}", command_hint());
+            }
+
+            // Display question, with the reason for asking it dimmed above it
+            if let Some(reason) = &question.reason {
+                println!("{}", reason_line(reason));
+            }
+            println!(
+                "{}: {}",
+                self.session_manager.question_position(),
+                question.text
+            );
+
+            if let Some(help_text) = &question.help_text {
+                println!("Hint: {}", help_text);
+            }
+
+            // Start from the known answer, if there is one
+            let prefilled = self
+                .prefill
+                .as_ref()
+                .and_then(|prefill| prefill.answer_for(question));
+            if let Some(answer) = prefilled {
+                println!("Known answer: {}", answer);
+            }
+
+            // Take the answer from the answers file without prompting, if it has one
+            if let Some(answers) = &self.answers {
+                match answer_from_file(answers, question, self.progress) {
+                    Some(answer) if answer == SKIP_COMMAND => return Ok(Step::Next(answer)),
+                    // A refused answer is skipped, as asking again would read the same answer
+                    Some(answer) if !passes_moderation(&self.session_manager, &answer).await => {
+                        return Ok(Step::Next(SKIP_COMMAND.to_string()));
+                    }
+                    Some(answer) => return Ok(Step::Next(answer)),
+                    None => {}
+                }
+            }
+
+            // Get user response based on question type, within the time limit if there is one
+            let response = match self.question_time_limit {
+                Some(limit) => {
+                    prompt_response_within(
+                        limit,
+                        self.question_timeout_action,
+                        question,
+                        prefilled,
+                        self.max_options_display,
+                    )
+                    .await?
+                }
+                None => {
+                    prompt_response(&self.theme, question, prefilled, self.max_options_display)?
+                }
+            };
+
+            match self.handle_command(&response).await? {
+                CommandOutcome::Answer => {}
+                CommandOutcome::AskAgain => continue,
+                CommandOutcome::WentBack => return Ok(Step::Restart),
+                CommandOutcome::Quit => return Ok(Step::Quit),
+            }
+
+            // Check the answer before it is sent, asking again if it is refused
+            if response.trim().to_lowercase() != SKIP_COMMAND
+                && !passes_moderation(&self.session_manager, &response).await
+            {
+                continue;
+            }
+
+            return Ok(Step::Next(response));
+        }
+    }
+
+    /// Run the session command given instead of an answer, if the response is one
+    async fn handle_command(&mut self, response: &str) -> Result<CommandOutcome> {
+        // Exit the wizard, keeping the answers if the user wants to
+        if response.trim().eq_ignore_ascii_case(QUIT_COMMAND) {
+            return Ok(CommandOutcome::Quit);
+        }
+
+        // Go back to the previous question, whatever the question type
+        if is_back_command(response) {
+            match self.session_manager.go_back() {
+                Ok(_) => println!("Going back to previous question"),
+                Err(e) => println!("Cannot go back: {}", e),
+            }
+            return Ok(CommandOutcome::WentBack);
+        }
+
+        if response.trim().to_lowercase() == WHY_COMMAND {
+            self.progress.log("Asking why this question matters...");
+            match self.session_manager.explain_current_question().await {
+                Ok(explanation) => println!("\n{}\n", explanation),
+                Err(e) => println!("Could not explain the question: {}", e),
+            }
+            return Ok(CommandOutcome::AskAgain);
+        }
+
+        if response.trim().to_lowercase() == HELP_COMMAND {
+            print_help();
+            return Ok(CommandOutcome::AskAgain);
+        }
+
+        if response.trim().to_lowercase() == CHAT_COMMAND {
+            chat_freely(&mut self.session_manager, &self.theme, &self.progress).await?;
+            return Ok(CommandOutcome::AskAgain);
+        }
+
+        if response.trim().to_lowercase() == HISTORY_COMMAND {
+            print_history(&self.session_manager.session.context);
+            return Ok(CommandOutcome::AskAgain);
+        }
+
+        if let Some(tag) = command_argument(response, TAG_COMMAND) {
+            let tag = if tag.is_empty() {
+                prompt_with_retry(|| {
+                    Input::with_theme(&self.theme)
+                        .with_prompt("Tag for your last answer")
+                        .interact_text()
+                })?
+            } else {
+                tag.to_string()
+            };
+
+            if self.session_manager.session.context.tag_last_answer(&tag) {
+                println!("Tagged your last answer with '{}'", tag.trim());
+            } else {
+                println!("There is no answer to tag yet");
+            }
+            return Ok(CommandOutcome::AskAgain);
+        }
+
+        if let Some(number) = command_argument(response, EDIT_COMMAND) {
+            let number = match number.parse::<usize>() {
+                Ok(number) => number,
+                Err(_) if number.is_empty() => prompt_with_retry(|| {
+                    Input::with_theme(&self.theme)
+                        .with_prompt("Number of the answer to edit")
+                        .interact_text()
+                })?,
+                Err(_) => {
+                    println!("Usage: {} <answer number>", EDIT_COMMAND);
+                    return Ok(CommandOutcome::AskAgain);
+                }
+            };
+
+            let Some(answer) = number
+                .checked_sub(1)
+                .and_then(|index| self.session_manager.session.context.history.get(index))
+            else {
+                println!("There is no answer number {}", number);
+                return Ok(CommandOutcome::AskAgain);
+            };
+
+            let edited = prompt_edited_response(&self.theme, answer)?;
+            if !passes_moderation(&self.session_manager, &edited).await {
+                return Ok(CommandOutcome::AskAgain);
+            }
+            match self
+                .session_manager
+                .session
+                .context
+                .edit_answer(number - 1, edited)
+            {
+                Ok(true) => {
+                    self.session_manager.protect_sensitive_answers();
+                    println!("Answer {} updated", number);
+                }
+                Ok(false) => println!("Answer {} unchanged", number),
+                Err(e) => println!("Cannot edit answer: {}", e),
+            }
+            return Ok(CommandOutcome::AskAgain);
+        }
+
+        if let Some(count) = command_argument(response, MORE_COMMAND) {
+            let more = match count.parse::<usize>() {
+                Ok(more) => more,
+                Err(_) if count.is_empty() => prompt_with_retry(|| {
+                    Input::with_theme(&self.theme)
+                        .with_prompt("How many more questions?")
+                        .default(5)
+                        .interact_text()
+                })?,
+                Err(_) => {
+                    println!("Usage: {} <number of questions>", MORE_COMMAND);
+                    return Ok(CommandOutcome::AskAgain);
+                }
+            };
+
+            self.session_manager.extend_max_questions(more);
+            println!(
+                "The session now has up to {} questions",
+                self.session_manager.max_questions()
+            );
+            return Ok(CommandOutcome::AskAgain);
+        }
+
+        Ok(CommandOutcome::Answer)
+    }
+
+    /// Record the response as an answer or a skipped question
+    fn record_response(&mut self, plan: &QuestionPlan, response: String) -> Result<Step<()>> {
+        // Record skipped questions without asking for certainty
+        if response.trim().to_lowercase() == SKIP_COMMAND {
+            if let Err(e) = self.session_manager.skip_question() {
+                println!("Error skipping question: {}", e);
+                return Ok(Step::Finish);
+            }
+            if plan.focus_area.is_some() {
+                self.session_manager.complete_focus_area();
+            }
+            println!("Question skipped");
+            println!();
+            return Ok(Step::Restart);
+        }
+
+        // Ask how certain the user is about the answer
+        let self_confidence = if self.ask_certainty && self.progress.is_interactive() {
+            let options: Vec<String> = (1..=5).map(|n| format!("{}", n)).collect();
+            let selection = prompt_with_retry(|| {
+                Select::with_theme(&self.theme)
+                    .with_prompt(
+                        "How certain are you about this answer? (1 = guessing, 5 = certain)",
+                    )
+                    .items(&options)
+                    .default(2)
+                    .interact()
+            })?;
+            Some(selection as u8 + 1)
+        } else {
+            None
+        };
+
+        // Answer the question
+        if let Err(e) = self
+            .session_manager
+            .answer_question_with_confidence(response, self_confidence)
+        {
+            println!("Error answering question: {}", e);
+            return Ok(Step::Finish);
+        }
+
+        if plan.focus_area.is_some() {
+            self.session_manager.complete_focus_area();
+        }
+        Ok(Step::Next(()))
+    }
+
+    /// Check the last answer for vagueness and update what the answers so far reveal
+    async fn after_answer(&mut self, plan: &QuestionPlan) {
+        // Clarify a vague answer before moving on, but not the answer to a clarifier
+        if self.clarify && plan.clarifying.is_none() && self.clarifiers_asked < self.max_clarifiers
+        {
+            self.progress
+                .log("Checking whether the answer needs clarifying...");
+            match self.session_manager.assess_last_answer().await {
+                Ok(Some(missing)) => {
+                    println!("Asking a clarifying question about: {}", missing);
+                    self.clarifiers_asked += 1;
+                    self.unclear = Some(missing);
+                }
+                Ok(None) => {}
+                Err(e) => println!("Could not check the answer: {}", e),
+            }
+        }
+
+        // Infer the domain from the first answers if none was given
+        match self.session_manager.infer_domain(&self.domains).await {
+            Ok(Some(domain)) => println!("Inferred domain: {}", domain),
+            Ok(None) => {}
+            Err(e) => println!("Could not infer the domain: {}", e),
+        }
+
+        // Give the project a working name for the document title and file names
+        match self.session_manager.name_project().await {
+            Ok(Some(name)) => println!("Project name: {}", name),
+            Ok(None) => {}
+            Err(e) => println!("Could not name the project: {}", e),
+        }
+
+        // Let the persona note what the answer means for the project
+        if let Err(e) = self.session_manager.update_running_notes().await {
+            println!("Could not update the running notes: {}", e);
+        }
+
+        println!();
+    }
+}
+
+/// Describe a mismatch between the requested language and the one the definition is written in
+fn language_warning(markdown: &str, requested: &str) -> Option<String> {
+    match language::check_language(markdown, requested) {
+        LanguageCheck::Mismatch { detected } => Some(format!(
+            "Warning: the project definition appears to be written in {} rather than {}",
+            detected, requested
+        )),
+        LanguageCheck::Inconclusive => Some(format!(
+            "Note: could not confirm that the project definition is written in {}",
+            requested
+        )),
+        LanguageCheck::Match => None,
+    }
+}
+
+/// Print the collected questions and answers and save them if an output path is given
+fn export_interview(session_manager: &SessionManager, output_path: Option<&Path>) -> Result<()> {
+    println!("\n{}", session_manager.session.context.to_qa_markdown());
+
+    if let Some(path) = output_path {
+        println!("Saving questions and answers to {}", path.display());
+        session_manager.export_questions(path)?;
+    }
+    Ok(())
+}
+
+/// Suggest where to save a session: a file named after the project, next to the last saved session
+fn default_session_path(config: &Config, file_stem: Option<String>) -> PathBuf {
+    let last_session_path = config.last_session_path.clone();
+    match file_stem {
+        Some(stem) => {
+            let file_name = format!("{}_session.json", stem);
+            match last_session_path.as_deref().and_then(Path::parent) {
+                Some(dir) => dir.join(file_name),
+                None => PathBuf::from(file_name),
+            }
+        }
+        None => last_session_path.unwrap_or_else(|| PathBuf::from("wizard_session.json")),
+    }
+}
+
+/// Ask the user whether to save the session and save it where they choose
+fn offer_to_save_session(
+    session_manager: &SessionManager,
+    theme: &ColorfulTheme,
+    progress: ProgressMode,
+) -> Result<()> {
+    if !progress.is_interactive() {
+        return Ok(());
+    }
+
+    let default_path =
+        default_session_path(&Config::load_or_default(), session_manager.file_stem());
+
+    let prompt = if session_manager.has_error() {
+        "Do you want to save this session to retry later with `continue`?"
+    } else {
+        "Do you want to save this session for later?"
+    };
+
+    let save_session = prompt_with_retry(|| {
+        Confirm::with_theme(theme)
+            .with_prompt(prompt)
+            .default(session_manager.has_error())
+            .interact()
+    })?;
+
+    if save_session {
+        let session_path: String = prompt_with_retry(|| {
+            Input::with_theme(theme)
+                .with_prompt("Enter path to save session")
+                .default(default_path.display().to_string())
+                .interact_text()
+        })?;
+
+        println!("Saving session to {}", session_path);
+        session_manager.session.save_to_file(&session_path)?;
+
+        // Offer the same path next time
+        let session_path = std::fs::canonicalize(&session_path).unwrap_or(session_path.into());
+        if let Err(e) =
+            Config::update_default(|config| config.last_session_path = Some(session_path))
+        {
+            println!("Warning: could not remember the session path: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask a template's initial questions and assemble a basic definition without the LLM
+fn run_offline_interview(
+    session: Session,
+    template: &Template,
+    llm_client: LlmClient,
+    options: WizardArgs,
+    progress: ProgressMode,
+) -> Result<()> {
+    if options.answers.is_none() {
+        require_prompts(progress, "Answering questions without --answers <PATH>")?;
+    }
+
+    let settings = Config::load_or_default();
+    let prefill = load_prefill(options.prefill.as_deref())?;
+    let answers = load_answers(options.answers.as_deref())?;
+    let confidence_style = options
+        .confidence_style
+        .or(settings.confidence_style)
+        .unwrap_or_default();
+    let mut session_manager = SessionManager::new(session, llm_client.with_progress(progress))
+        .with_markdown_style(settings.markdown_style.unwrap_or_default())
+        .with_confidence_style(confidence_style)
+        .with_output_format(options.format)
+        .with_metrics_appendix(options.metrics);
+    session_manager.session.redact_sensitive = options.redact_sensitive;
+    let theme = ColorfulTheme::default();
+
+    println!(
+        "Running offline: asking the {} questions of the template without the LLM",
+        template.initial_questions.len()
+    );
+    println!();
+
+    // Each question is asked at most once; conditional questions may be left out
+    let total = template.initial_questions.len();
+    for number in 1..=total {
+        let Some(question) = session_manager.next_template_question(template) else {
+            break;
+        };
+
+        if let Some(reason) = &question.reason {
+            println!("{}", reason_line(reason));
+        }
+        println!("Question {} of up to {}: {}", number, total, question.text);
+        if let Some(help_text) = &question.help_text {
+            println!("Hint: {}", help_text);
+        }
+
+        let prefilled = prefill
+            .as_ref()
+            .and_then(|prefill| prefill.answer_for(question));
+        if let Some(answer) = prefilled {
+            println!("Known answer: {}", answer);
+        }
+        let response = match answers
+            .as_ref()
+            .and_then(|answers| answer_from_file(answers, question, progress))
+        {
+            Some(response) => response,
+            None => prompt_answer(&theme, question, prefilled)?,
+        };
+        let question = question.clone().with_source(QuestionSource::Template);
+        if response == SKIP_COMMAND {
+            session_manager.session.context.skip_question(question)?;
+        } else {
+            session_manager
+                .session
+                .context
+                .add_answer(question, response)?;
+        }
+        println!();
+    }
+
+    let markdown = session_manager.assemble_offline_definition(&template.name);
+    println!("\n{}\n", markdown);
+
+    let output_path = match options.output {
+        Some(path) => Some(path),
+        None => prompt_output_path(
+            &theme,
+            progress,
+            options.format,
+            session_manager.file_stem(),
+        )?,
+    };
+    if let Some(path) = output_path {
+        println!("Saving project definition to {}", path.display());
+        session_manager.export_output(&path)?;
+        remember_output_dir(&path);
+    }
+
+    if let Some(path) = options.metrics_json {
+        println!("Saving session metrics to {}", path.display());
+        session_manager.export_metrics(path)?;
+    }
+
+    offer_to_save_session(&session_manager, &theme, progress)?;
+
+    println!("Wizard completed successfully!");
+    Ok(())
+}
+
+/// Load the known answers to pre-fill the prompts with, if a file was given
+fn load_prefill(path: Option<&Path>) -> Result<Option<Prefill>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+
+    let prefill = Prefill::load_from_file(path)?;
+    if prefill.is_empty() {
+        println!("Warning: {} has no answers to pre-fill", path.display());
+    } else {
+        println!(
+            "Pre-filling {} known answer(s) from {}",
+            prefill.len(),
+            path.display()
+        );
+    }
+    Ok(Some(prefill))
+}
+
+/// Load the answers to give without prompting, if a file was given
+fn load_answers(path: Option<&Path>) -> Result<Option<Prefill>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+
+    let answers = Prefill::load_from_file(path)?;
+    println!(
+        "Answering from {} known answer(s) in {}",
+        answers.len(),
+        path.display()
+    );
+    Ok(Some(answers))
+}
+
+/// Get the answer to a question from the answers file, with choices matched to the question's options
+///
+/// Without prompts, a question the file has no usable answer to is skipped; otherwise it is asked.
+fn answer_from_file(
+    answers: &Prefill,
+    question: &Question,
+    progress: ProgressMode,
+) -> Option<String> {
+    let answer = answers
+        .answer_for(question)
+        .and_then(|answer| match question.choices() {
+            Some(choices) => {
+                prefill::option_index(answer, &choices).map(|index| choices[index].clone())
+            }
+            None => Some(answer.to_string()),
+        });
+
+    match answer {
+        Some(answer) => {
+            println!("Answer: {}", answer);
+            Some(answer)
+        }
+        None if !progress.is_interactive() => {
+            println!("No answer to this question in the answers file, skipping it");
+            Some(SKIP_COMMAND.to_string())
+        }
+        None => None,
+    }
+}
+
+/// Set up the moderation of answers from the configuration file, none if it isn't enabled
+fn load_moderation(settings: &Config) -> Result<Moderation> {
+    let Some(config) = &settings.moderation else {
+        return Ok(Moderation::default());
+    };
+
+    let moderation = Moderation::from_config(config)?;
+    println!(
+        "Moderating answers before they are sent ({:?} policy)",
+        config.policy
+    );
+    Ok(moderation)
+}
+
+/// Ask a question with the prompt matching its type, without session commands
+///
+/// A known answer is pre-selected or pre-typed, for the user to accept or change.
+fn prompt_answer(
+    theme: &ColorfulTheme,
+    question: &Question,
+    prefilled: Option<&str>,
+) -> Result<String> {
+    let options = question.choices().unwrap_or_default();
+
+    // Don't make the user pick the only available option
+    if let [only] = options.as_slice() {
+        println!("Only one option, answering: {}", only);
+        return Ok(only.clone());
+    }
+
+    if options.is_empty() {
+        return prompt_with_retry(|| free_text_input(theme, question, prefilled).interact_text());
+    }
+
+    let selection = prompt_with_retry(|| {
+        Select::with_theme(theme)
+            .items(&options)
+            .default(default_option(prefilled, &options))
+            .interact()
+    })?;
+    Ok(options[selection].clone())
+}
+
+/// Suggest where to save the project definition: a file named after the project, in the last output directory
+fn default_output_path(
+    config: &Config,
+    format: OutputFormat,
+    file_stem: Option<String>,
+) -> PathBuf {
+    let extension = match format {
+        OutputFormat::Markdown => "md",
+        OutputFormat::Text => "txt",
+    };
+    let file_name = format!(
+        "{}.{}",
+        file_stem.as_deref().unwrap_or("project_definition"),
+        extension
+    );
+    match &config.output_dir {
+        Some(dir) => dir.join(file_name),
+        None => PathBuf::from(file_name),
+    }
+}
+
+/// Ask whether to save the project definition when no output file was given
+///
+/// The suggested file is named after the project when it has a name.
+fn prompt_output_path(
+    theme: &ColorfulTheme,
+    progress: ProgressMode,
+    format: OutputFormat,
+    file_stem: Option<String>,
+) -> Result<Option<PathBuf>> {
+    if !progress.is_interactive() {
+        return Ok(None);
+    }
+
+    let save = prompt_with_retry(|| {
+        Confirm::with_theme(theme)
+            .with_prompt("Do you want to save the project definition to a file?")
+            .default(false)
+            .interact()
+    })?;
+    if !save {
+        return Ok(None);
+    }
+
+    let default_path = default_output_path(&Config::load_or_default(), format, file_stem);
+
+    let path: String = prompt_with_retry(|| {
+        Input::with_theme(theme)
+            .with_prompt("Enter path to save the project definition")
+            .default(default_path.display().to_string())
+            .interact_text()
+    })?;
+
+    Ok(Some(PathBuf::from(path)))
+}
+
+/// Remember the directory of a saved project definition for the next save prompt
+fn remember_output_dir(path: &Path) {
+    let Some(dir) = output_dir_of(path) else {
+        return;
+    };
+
+    if let Err(e) = Config::update_default(|config| config.output_dir = Some(dir)) {
+        println!("Warning: could not remember the output directory: {}", e);
+    }
+}
+
+/// Get the absolute directory of a saved output file
+fn output_dir_of(path: &Path) -> Option<PathBuf> {
+    std::fs::canonicalize(path)
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wizard::config::ProfileConfig;
+    use wizard::llm::ModelSettings;
+    use wizard::llm::scripted::Reply;
+
+    /// Scripted reply with a free-text question
+    fn question_reply(text: &str) -> Reply {
+        Reply::content(json!({ "question_type": "FreeText", "question_text": text }).to_string())
+    }
+
+    /// Session with the given number of answered questions
+    fn answered_session(answers: usize) -> Session {
+        let mut session = Session::with_context(Context::new());
+        for i in 1..=answers {
+            session
+                .context
+                .add_answer(
+                    Question::free_text(format!("q{}", i), format!("Question {}?", i)),
+                    "An answer",
+                )
+                .unwrap();
+        }
+        session
+    }
+
+    /// Wizard options of a `continue` command with the given extra arguments
+    fn continue_args(extra: &[&str]) -> WizardArgs {
+        let args = ["projector", "continue", "--session", "session.json"];
+        match Cli::parse_from(args.iter().chain(extra)).command {
+            Commands::Continue { wizard, .. } => wizard,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Interview of the session with the default options, without prompts
+    fn interview(session_manager: SessionManager, interrupts: &Interrupts) -> Interview<'_> {
+        Interview {
+            session_manager,
+            theme: ColorfulTheme::default(),
+            progress: ProgressMode::Lines,
+            interrupts,
+            prefill: None,
+            answers: None,
+            domains: Vec::new(),
+            ask_certainty: false,
+            min_confidence: None,
+            min_questions: None,
+            max_followups: 0,
+            clarify: false,
+            max_clarifiers: 0,
+            batch_questions: false,
+            max_options_display: None,
+            quiet: true,
+            question_time_limit: None,
+            question_timeout_action: QuestionTimeoutAction::default(),
+            followups_asked: 0,
+            clarifiers_asked: 0,
+            unclear: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn history_shows_the_same_question_again() {
+        let (client, _) = LlmClient::scripted([question_reply("What is the budget?")]);
+        let mut session_manager = SessionManager::new(answered_session(2), client);
+        session_manager.start();
+        session_manager.generate_next_question().await.unwrap();
+        let interrupts = Interrupts::new();
+        let mut interview = interview(session_manager, &interrupts);
+        let position = interview.session_manager.question_position();
+        let index = interview.session_manager.session.context.current_index;
+
+        let outcome = interview.handle_command(" :History ").await.unwrap();
+
+        assert_eq!(outcome, CommandOutcome::AskAgain);
+        let session_manager = &interview.session_manager;
+        assert_eq!(
+            session_manager
+                .session
+                .current_question
+                .as_ref()
+                .map(|question| question.text.as_str()),
+            Some("What is the budget?")
+        );
+        assert_eq!(session_manager.question_position(), position);
+        assert_eq!(session_manager.session.context.current_index, index);
+        assert_eq!(session_manager.question_count(), 2);
+    }
+
+    #[test]
+    fn templates_json_lists_each_name_and_domain() {
+        let mut template = Template::new("Triage Bot", "Symptom triage", "Healthcare".into(), "");
+        template.add_question(Question::free_text("q1", "Who are the patients?"));
+        let templates = vec![
+            template,
+            Template::new("Code Explainer", "Explain code", "Software".into(), ""),
+        ];
+
+        let json: serde_json::Value =
+            serde_json::from_str(&templates_json(&templates, false).unwrap()).unwrap();
+
+        let listed: Vec<(&str, &str)> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|template| {
+                (
+                    template["name"].as_str().unwrap(),
+                    template["domain"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [("Triage Bot", "Healthcare"), ("Code Explainer", "Software")]
+        );
+        assert_eq!(json[0]["question_count"], 1);
+        assert!(json[0].get("initial_questions").is_none());
+    }
+
+    #[tokio::test]
+    async fn more_command_raises_the_cap_and_questions_continue() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        let (client, _) = LlmClient::scripted([question_reply("What is the budget?")]);
+        let mut session_manager =
+            SessionManager::new(answered_session(2).with_max_questions(2), client);
+        session_manager.start();
+
+        let more: usize = command_argument(":more 5", MORE_COMMAND)
+            .unwrap()
+            .parse()
+            .unwrap();
+        session_manager.extend_max_questions(more);
+
+        assert_eq!(session_manager.max_questions(), 7);
+        let question = session_manager.generate_next_question().await.unwrap();
+        assert_eq!(question.text, "What is the budget?");
+        session_manager.session.save_to_file(&path).unwrap();
+        assert_eq!(Session::load_from_file(&path).unwrap().max_questions, 7);
+    }
+
+    #[test]
+    fn env_file_variables_reach_the_llm_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("project.env");
+        std::fs::write(
+            &path,
+            "PROJECTOR_FALLBACK_MODELS=\"env/fallback-a, env/fallback-b\"\n",
+        )
+        .unwrap();
+
+        load_env_files(&[path]).unwrap();
+        let client = create_llm_client(None).unwrap();
+
+        assert_eq!(
+            client.config().fallback_models,
+            ["env/fallback-a", "env/fallback-b"]
+        );
+    }
+
+    #[test]
+    fn questions_only_exports_without_generating_a_definition() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("interview.json");
+        let (client, api) = LlmClient::scripted([]);
+        let session_manager = SessionManager::new(answered_session(2), client);
+
+        export_interview(&session_manager, Some(&path)).unwrap();
+
+        let export: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(export.as_array().unwrap().len(), 2);
+        assert_eq!(export[0]["question"]["text"], "Question 1?");
+        assert!(api.received().is_empty());
+        assert!(session_manager.session.definition.is_none());
+    }
+
+    #[test]
+    fn medical_chatbot_info_shows_its_rating_question_and_hipaa_metadata() {
+        let mut template = Template::new(
+            "Medical Chatbot",
+            "Patient-facing symptom assistant",
+            "Healthcare".into(),
+            "A chatbot that helps patients describe their symptoms",
+        );
+        template.add_question(Question::rating_scale(
+            "q1",
+            "How important is it that answers are reviewed by a clinician?",
+            1,
+            5,
+        ));
+        template.add_metadata("compliance", "HIPAA");
+
+        let info = template_info(&template).unwrap();
+
+        assert!(
+            info.contains(
+                "1. How important is it that answers are reviewed by a clinician? (Rating Scale)"
+            ),
+            "{}",
+            info
+        );
+        assert!(info.contains("   Scale: 1-5"), "{}", info);
+        assert!(info.contains("  compliance: HIPAA"), "{}", info);
+    }
+
+    #[test]
+    fn continuing_uses_the_recorded_model_unless_overridden() {
+        let pinned = ModelSettings {
+            model: "pinned/model".to_string(),
+            temperature: 0.2,
+            max_tokens: 1000,
+        };
+        let mut session = answered_session(1);
+        session.model = Some(pinned.clone());
+
+        let client = create_session_llm_client(&mut session, &continue_args(&[])).unwrap();
+        assert_eq!(client.model_settings().model, "pinned/model");
+        assert_eq!(client.model_settings().temperature, 0.2);
+
+        let overridden = continue_args(&["--model", "other/model"]);
+        let client = create_session_llm_client(&mut session, &overridden).unwrap();
+        assert_eq!(client.model_settings().model, "other/model");
+        assert_eq!(session.model.unwrap().model, "other/model");
+    }
+
+    #[test]
+    fn help_lists_every_session_command() {
+        let help = help_text();
+
+        for command in SESSION_COMMANDS {
+            let line = format!("  {:<12} {}", command.usage, command.description);
+            assert!(help.contains(&line), "missing {:?} in:\n{}", line, help);
+        }
+        for name in [BACK_COMMAND, SKIP_COMMAND, HELP_COMMAND, QUIT_COMMAND] {
+            assert!(command_hint().contains(name));
+        }
+    }
+
+    #[test]
+    fn definition_in_another_language_is_warned_about() {
+        let markdown = "## Overview\n\nThe assistant helps patients describe their symptoms \
+            before a visit, so that doctors can prepare and spend more time on treatment. \
+            It should be easy to use for older people and work well on their phones, \
+            and it must never store anything that the patient did not agree to share.\n";
+
+        assert_eq!(
+            language_warning(markdown, "German").as_deref(),
+            Some(
+                "Warning: the project definition appears to be written in English rather than German"
+            )
+        );
+        assert_eq!(language_warning(markdown, "English"), None);
+    }
+
+    #[test]
+    fn one_option_question_is_answered_without_prompting() {
+        let theme = ColorfulTheme::default();
+        let question = Question::multiple_choice("q1", "Which platform?", vec!["Web".to_string()]);
+
+        assert_eq!(
+            prompt_response(&theme, &question, None, None).unwrap(),
+            "Web"
+        );
+        assert_eq!(prompt_answer(&theme, &question, None).unwrap(), "Web");
+    }
+
+    #[test]
+    fn remembered_paths_are_the_next_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        let output_path = dir.path().join("definition.md");
+        let session_path = dir.path().join("wizard_session.json");
+        std::fs::write(&output_path, "# Triage Bot\n").unwrap();
+        std::fs::write(&session_path, "{}").unwrap();
+        let dir = std::fs::canonicalize(dir.path()).unwrap();
+
+        let config = Config {
+            output_dir: output_dir_of(&output_path),
+            last_session_path: std::fs::canonicalize(&session_path).ok(),
+            ..Config::default()
+        };
+        config.save_to_file(&config_path).unwrap();
+        let next_run = Config::load_from_file(&config_path).unwrap();
+
+        assert_eq!(
+            default_output_path(&next_run, OutputFormat::Markdown, Some("triage_bot".into())),
+            dir.join("triage_bot.md")
+        );
+        assert_eq!(
+            default_session_path(&next_run, Some("triage_bot".into())),
+            dir.join("triage_bot_session.json")
+        );
+        assert_eq!(
+            default_session_path(&next_run, None),
+            dir.join("wizard_session.json")
+        );
+        assert_eq!(
+            default_output_path(&Config::default(), OutputFormat::Text, None),
+            PathBuf::from("project_definition.txt")
+        );
+    }
+
+    #[test]
+    fn hints_file_populates_the_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("brief.txt");
+        std::fs::write(&path, "\nA triage assistant for a rural clinic.\n").unwrap();
+        let long_path = dir.path().join("long_brief.txt");
+        std::fs::write(&long_path, "x".repeat(MAX_HINTS_CHARS + 10)).unwrap();
+
+        let mut context = Context::new();
+        context.starting_hints = load_hints(None, Some(path)).unwrap();
+        let long = load_hints(None, Some(long_path)).unwrap().unwrap();
+
+        assert_eq!(
+            context.starting_hints.as_deref(),
+            Some("A triage assistant for a rural clinic.")
+        );
+        assert!(
+            context
+                .get_context_string()
+                .contains("A triage assistant for a rural clinic.")
+        );
+        assert_eq!(long.chars().count(), MAX_HINTS_CHARS);
+    }
+
+    #[test]
+    fn back_entry_of_every_selection_list_goes_back() {
+        let multiple_choice = Question::multiple_choice(
+            "q3",
+            "Which platform?",
+            vec!["Web".to_string(), "iOS".to_string(), "Android".to_string()],
+        );
+        let yes_no = Question::yes_no("q3", "Is it offline-first?");
+        let rating = Question::rating_scale("q3", "How urgent is it?", 1, 5);
+
+        for question in [multiple_choice, yes_no, rating] {
+            let items = selection_items(&question.choices().unwrap(), false);
+            assert_eq!(items[0], BACK_OPTION);
+            assert_eq!(list_choice(&items, Some(0)), ListChoice::Back);
+            assert_eq!(
+                list_choice(&items, Some(1)),
+                ListChoice::Item(question.choices().unwrap()[0].clone())
+            );
+        }
+        assert!(is_back_command(BACK_COMMAND));
+
+        let (client, _) = LlmClient::scripted([]);
+        let mut session_manager = SessionManager::new(answered_session(2), client);
+        session_manager.start();
+        assert_eq!(session_manager.go_back().unwrap().id, "q2");
+    }
+
+    #[test]
+    fn prompt_is_retried_after_a_transient_error() {
+        let mut attempts = 0;
+        let answer = prompt_with_retry(|| {
+            attempts += 1;
+            if attempts == 1 {
+                Err(dialoguer::Error::IO(std::io::Error::from(
+                    ErrorKind::Interrupted,
+                )))
+            } else {
+                Ok("Nurses".to_string())
+            }
+        })
+        .unwrap();
+        assert_eq!(answer, "Nurses");
+        assert_eq!(attempts, 2);
+
+        let mut attempts = 0;
+        let result: Result<String> = prompt_with_retry(|| {
+            attempts += 1;
+            Err(dialoguer::Error::IO(std::io::Error::from(
+                ErrorKind::BrokenPipe,
+            )))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn session_halts_at_the_time_limit() {
+        let (client, _) = LlmClient::scripted([
+            question_reply("What is the budget?").after(Duration::from_secs(5))
+        ]);
+        let mut session_manager = SessionManager::new(answered_session(2), client);
+        session_manager.start();
+        let started = tokio::time::Instant::now();
+        let deadline = started + Duration::from_millis(200);
+
+        let question =
+            before_deadline(Some(deadline), session_manager.generate_next_question()).await;
+
+        assert!(question.is_none());
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+        assert_eq!(session_manager.question_count(), 2);
+
+        // A slow check after an answer is cut off too, keeping the answer
+        let dir = tempfile::tempdir().unwrap();
+        let answers = dir.path().join("answers.json");
+        std::fs::write(
+            &answers,
+            json!({ "What is the budget?": "Ten thousand euros" }).to_string(),
+        )
+        .unwrap();
+        let output = dir.path().join("interview.md");
+        let options = continue_args(&[
+            "--timeout",
+            "1",
+            "--answers",
+            answers.to_str().unwrap(),
+            "--questions-only",
+            "--output",
+            output.to_str().unwrap(),
+        ]);
+        let (client, api) = LlmClient::scripted([
+            question_reply("What is the budget?"),
+            Reply::content("{}").after(Duration::from_secs(10)),
+        ]);
+        let started = tokio::time::Instant::now();
+
+        run_wizard_steps(
+            answered_session(1).with_max_questions(3),
+            client,
+            options,
+            ProgressMode::Lines,
+            &Interrupts::new(),
+        )
+        .await
+        .unwrap();
+
+        let elapsed = started.elapsed();
+        assert!(elapsed < Duration::from_secs(3), "{:?}", elapsed);
+        assert_eq!(api.received().len(), 2);
+        let interview = std::fs::read_to_string(&output).unwrap();
+        assert!(
+            interview.contains("**Answer:** Ten thousand euros"),
+            "{}",
+            interview
+        );
+    }
+
+    #[tokio::test]
+    async fn suggested_answer_prefills_the_free_text_prompt() {
+        let (client, _) = LlmClient::scripted([Reply::content(
+            json!({
+                "question_type": "FreeText",
+                "question_text": "Who are the users?",
+                "default_answer": " Triage nurses "
+            })
+            .to_string(),
+        )]);
+
+        let question = client
+            .generate_question(&Context::new(), 0.0)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            free_text_prefill(&question, None),
+            Some(FreeTextPrefill::Default("Triage nurses"))
+        );
+        assert_eq!(
+            free_text_prefill(&question, Some("Doctors")),
+            Some(FreeTextPrefill::Initial("Doctors"))
+        );
+        let plain = Question::free_text("q1", "What is the goal?");
+        assert_eq!(free_text_prefill(&plain, None), None);
+    }
+
+    #[test]
+    fn prefilled_multiple_choice_defaults_to_the_known_option() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("answers.json");
+        std::fs::write(
+            &path,
+            json!({ "Which platforms must the mobile app support?": " android " }).to_string(),
+        )
+        .unwrap();
+        let question = Question::multiple_choice(
+            "q_1",
+            "Which platforms must the app support?",
+            vec!["iOS".to_string(), "Android".to_string(), "Web".to_string()],
+        );
+        let options = question.choices().unwrap();
+
+        let prefill = load_prefill(Some(&path)).unwrap().unwrap();
+        let prefilled = prefill.answer_for(&question);
+
+        assert_eq!(prefilled, Some(" android "));
+        assert_eq!(default_option(prefilled, &options), 1);
+        assert_eq!(default_option(Some("Desktop"), &options), 0);
+        assert_eq!(default_option(None, &options), 0);
+    }
+
+    #[tokio::test]
+    async fn project_name_flows_into_the_suggested_file_names() {
+        let (client, _) =
+            LlmClient::scripted([Reply::content("**Project name:** \"Task Tracker\"")]);
+        let mut session_manager = SessionManager::new(answered_session(2), client);
+        session_manager.start();
+
+        let name = session_manager.name_project().await.unwrap();
+
+        assert_eq!(name.as_deref(), Some("Task Tracker"));
+        let config = Config::default();
+        assert_eq!(
+            default_output_path(&config, OutputFormat::Markdown, session_manager.file_stem()),
+            PathBuf::from("task_tracker.md")
+        );
+        assert_eq!(
+            default_session_path(&config, session_manager.file_stem()),
+            PathBuf::from("task_tracker_session.json")
+        );
+    }
+
+    #[test]
+    fn merged_sessions_keep_the_later_answer_to_a_shared_question() {
+        let dir = tempfile::tempdir().unwrap();
+        let stakeholder = |answers: &[(&str, &str, &str)], hints: &str, team: &str| {
+            let mut context = Context::new();
+            for (id, text, response) in answers {
+                context
+                    .add_answer(Question::free_text(*id, *text), *response)
+                    .unwrap();
+            }
+            context.starting_hints = Some(hints.to_string());
+            context.metadata.insert(team.to_string(), "yes".to_string());
+            Session::with_context(context)
+        };
+        let first = stakeholder(
+            &[
+                ("q_users", "Who are the users?", "Nurses"),
+                ("q_goal", "What is the goal?", "Faster triage"),
+            ],
+            "A triage assistant for a busy clinic",
+            "nursing",
+        );
+        let second = stakeholder(
+            &[
+                ("q_goal", "What is the goal?", "Shorter waits"),
+                ("q_budget", "What is the budget?", "50k"),
+            ],
+            "Triage",
+            "finance",
+        );
+        let inputs = vec![dir.path().join("a.json"), dir.path().join("b.json")];
+        first.save_to_file(&inputs[0]).unwrap();
+        second.save_to_file(&inputs[1]).unwrap();
+        let output = dir.path().join("merged.json");
+
+        merge_sessions(inputs, output.clone()).unwrap();
+
+        let merged = Session::load_from_file(&output).unwrap().context;
+        let answers: Vec<(&str, &str)> = merged
+            .history
+            .iter()
+            .map(|answer| (answer.question.id.as_str(), answer.response.as_str()))
+            .collect();
+        assert_eq!(
+            answers,
+            [
+                ("q_users", "Nurses"),
+                ("q_goal", "Shorter waits"),
+                ("q_budget", "50k")
+            ]
+        );
+        assert_eq!(merged.current_index, 3);
+        assert_eq!(
+            merged.starting_hints.as_deref(),
+            Some("A triage assistant for a busy clinic")
+        );
+        assert_eq!(
+            merged.metadata.keys().collect::<Vec<_>>(),
+            ["finance", "nursing"]
+        );
+    }
+
+    #[test]
+    fn profile_sets_the_model_settings_and_flags_override_them() {
+        let premium = ProfileConfig {
+            model: Some("anthropic/claude-3.5-sonnet".to_string()),
+            temperature: Some(0.2),
+            ..ProfileConfig::default()
+        };
+        let settings = || Config {
+            model: Some("google/gemma-3-27b-it".to_string()),
+            max_tokens: Some(8192),
+            profiles: BTreeMap::from([("premium".to_string(), premium.clone())]),
+            ..Config::default()
+        };
+
+        let profiled = create_llm_client_from(settings(), Some("premium")).unwrap();
+        let overridden = create_llm_client_from(settings(), Some("premium"))
+            .unwrap()
+            .with_model(Some("openai/gpt-4".to_string()));
+        let unknown = create_llm_client_from(settings(), Some("draft"));
+
+        assert_eq!(profiled.config().model, "anthropic/claude-3.5-sonnet");
+        assert_eq!(profiled.config().temperature, 0.2);
+        assert_eq!(profiled.config().max_tokens, 8192);
+        assert_eq!(overridden.config().model, "openai/gpt-4");
+        assert_eq!(overridden.config().temperature, 0.2);
+        let error = unknown.err().unwrap().to_string();
+        assert!(error.contains("Available profiles: premium"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn ctrl_c_cancels_a_slow_generation_without_using_up_a_question() {
+        let (client, api) = LlmClient::scripted([
+            question_reply("Who are the users?").after(Duration::from_secs(60)),
+            question_reply("What does it replace?"),
+        ]);
+        let mut session_manager = SessionManager::new(answered_session(2), client);
+        session_manager.start();
+        let interrupts = Interrupts::new();
+
+        let interrupter = interrupts.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            interrupter.interrupt();
+        });
+        let generation = interrupts
+            .cancellable(session_manager.generate_next_question())
+            .await;
+
+        assert!(generation.is_none());
+        assert_eq!(api.received().len(), 1);
+        assert_eq!(session_manager.question_count(), 2);
+        assert_eq!(session_manager.session.context.history.len(), 2);
+        assert!(session_manager.session.current_question.is_none());
+
+        let question = interrupts
+            .cancellable(session_manager.generate_next_question())
+            .await
+            .unwrap()
+            .unwrap()
+            .clone();
+        assert_eq!(question.text, "What does it replace?");
+        assert_eq!(session_manager.question_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn ctrl_c_outside_cancellable_work_asks_for_an_exit() {
+        let interrupts = Interrupts::new();
+
+        interrupts.interrupt();
+
+        tokio::time::timeout(Duration::from_secs(1), interrupts.exit_requested())
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn long_option_list_shows_the_first_options_until_expanded() {
+        let options: Vec<String> = (1..=8).map(|i| format!("Option {}", i)).collect();
+
+        let (shown, hidden) = displayed_options(&options, Some(3), false);
+        assert_eq!(shown, &options[..3]);
+        assert!(hidden);
+        let items = selection_items(shown, hidden);
+        assert_eq!(items.len(), 6);
+        assert_eq!(
+            list_choice(&items, Some(4)),
+            ListChoice::Item(SHOW_MORE_OPTION.to_string())
+        );
+
+        let (shown, hidden) = displayed_options(&options, Some(3), true);
+        assert_eq!(shown, &options[..]);
+        assert!(!hidden);
+        assert!(!selection_items(shown, hidden).contains(&SHOW_MORE_OPTION.to_string()));
+
+        for max_display in [None, Some(0), Some(8)] {
+            assert_eq!(
+                displayed_options(&options, max_display, false),
+                (&options[..], false)
+            );
+        }
+    }
+
+    #[test]
+    fn list_starts_expanded_when_the_default_would_be_hidden() {
+        assert!(!starts_expanded(Some(3), 2));
+        assert!(starts_expanded(Some(3), 5));
+        assert!(!starts_expanded(Some(0), 5));
+        assert!(!starts_expanded(None, 5));
+    }
+
+    #[tokio::test]
+    async fn expired_timer_skips_the_question_without_waiting_for_input() {
+        let question = Question::free_text("q1", "Who are the primary users?");
+
+        let response = tokio::time::timeout(
+            Duration::from_secs(5),
+            answer_within(
+                Duration::from_millis(20),
+                QuestionTimeoutAction::Skip,
+                &question,
+                None,
+                std::future::pending(),
+            ),
+        )
+        .await
+        .expect("the abandoned input must not be waited for")
+        .unwrap();
+
+        assert_eq!(response, SKIP_COMMAND);
+    }
+
+    #[tokio::test]
+    async fn expired_timer_accepts_the_default_or_skips_without_one() {
+        let mut suggested = Question::free_text("q1", "Who are the primary users?");
+        suggested.default_answer = Some("Nurses".to_string());
+        let bare = Question::free_text("q2", "What is the budget?");
+        let limit = Duration::from_millis(20);
+        let accept = QuestionTimeoutAction::AcceptDefault;
+
+        let pending = || std::future::pending::<Result<String>>();
+        assert_eq!(
+            answer_within(limit, accept, &suggested, None, pending())
+                .await
+                .unwrap(),
+            "Nurses"
+        );
+        assert_eq!(
+            answer_within(limit, accept, &bare, Some("Known"), pending())
+                .await
+                .unwrap(),
+            "Known"
+        );
+        assert_eq!(
+            answer_within(limit, accept, &bare, None, pending())
+                .await
+                .unwrap(),
+            SKIP_COMMAND
+        );
+
+        let typed = answer_within(limit * 50, accept, &bare, None, async {
+            Ok("About 10k".to_string())
+        });
+        assert_eq!(typed.await.unwrap(), "About 10k");
+    }
+
+    #[test]
+    fn typed_timed_answer_picks_options_by_number_or_text() {
+        let question = Question::multiple_choice(
+            "q1",
+            "Where will it run?",
+            vec!["Web".to_string(), "Mobile".to_string()],
+        );
+        let choices = question.choices();
+        let default = timed_default(&question, choices.as_deref(), Some("Mobile"));
+        assert_eq!(default.as_deref(), Some("Mobile"));
+        let respond =
+            |line| timed_line_response(&question, choices.as_deref(), default.as_deref(), line);
+
+        assert_eq!(respond("2\n"), "Mobile");
+        assert_eq!(respond("web"), "Web");
+        assert_eq!(respond("\n"), "Mobile");
+        assert_eq!(respond(SKIP_COMMAND), SKIP_COMMAND);
+        assert_eq!(respond("7"), "7");
+
+        let rating = Question::rating_scale("q2", "How urgent is it?", 0, 5);
+        let choices = rating.choices();
+        assert_eq!(
+            timed_line_response(&rating, choices.as_deref(), None, "1"),
+            "1"
+        );
+    }
+
+    #[tokio::test]
+    async fn ci_mode_answers_from_the_answers_file_without_prompts() {
+        let dir = tempfile::tempdir().unwrap();
+        let answers = dir.path().join("answers.json");
+        std::fs::write(
+            &answers,
+            json!({ "Who are the users?": "Nurses" }).to_string(),
+        )
+        .unwrap();
+        let output = dir.path().join("interview.md");
+        let options = continue_args(&[
+            "--answers",
+            answers.to_str().unwrap(),
+            "--questions-only",
+            "--output",
+            output.to_str().unwrap(),
+        ]);
+        let (client, api) = LlmClient::scripted([
+            question_reply("Who are the users?"),
+            question_reply("What does it replace?"),
+        ]);
+        let session = Session::with_context(Context::new()).with_max_questions(2);
+
+        run_wizard_steps(
+            session,
+            client,
+            options,
+            ProgressMode::Lines,
+            &Interrupts::new(),
+        )
+        .await
+        .unwrap();
+
+        // The matching answer is given and the question without one is skipped
+        assert_eq!(api.received().len(), 2);
+        let interview = std::fs::read_to_string(&output).unwrap();
+        assert!(interview.contains("**Answer:** Nurses"), "{}", interview);
+        assert!(interview.contains("What does it replace?"), "{}", interview);
+
+        let error = run_wizard_steps(
+            Session::with_context(Context::new()),
+            LlmClient::scripted([]).0,
+            continue_args(&[]),
+            ProgressMode::Lines,
+            &Interrupts::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("--answers"), "{}", error);
+    }
+
+    #[test]
+    fn no_color_leaves_the_hint_and_reason_unstyled() {
+        assert!(colors_disabled(true, None));
+        assert!(colors_disabled(false, Some(std::ffi::OsStr::new("1"))));
+        assert!(!colors_disabled(false, Some(std::ffi::OsStr::new(""))));
+        assert!(!colors_disabled(false, None));
+
+        dialoguer::console::set_colors_enabled(true);
+        assert!(command_hint().contains('\u{1b}'));
+        dialoguer::console::set_colors_enabled(false);
+        assert!(command_hint().starts_with("Commands: "));
+        assert!(!command_hint().contains('\u{1b}'));
+        assert_eq!(reason_line("Sizes the team"), "Why: Sizes the team");
+    }
+}
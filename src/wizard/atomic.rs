@@ -0,0 +1,89 @@
+//! Atomic file writing module for the LLM-powered project definition wizard.
+//!
+//! This module provides the helper used by every save and export function so
+//! that an interrupted write never leaves a truncated file behind: content is
+//! written to a temporary file in the target's directory and then renamed over
+//! the target in one step.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::error::{Result, WizardError};
+
+/// Write the given contents to a file atomically
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
+    let target = path.as_ref().to_path_buf();
+    write_atomic_with(path, |file| {
+        file.write_all(contents.as_ref())
+            .map_err(WizardError::io("write", target))
+    })
+}
+
+/// Write a file atomically, producing its contents with the given writer function
+pub fn write_atomic_with<P, F>(path: P, write: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut File) -> Result<()>,
+{
+    let path = path.as_ref();
+    let temp_path = temp_path_for(path);
+
+    let result = (|| {
+        let mut file = File::create_new(&temp_path)
+            .map_err(WizardError::io("create temporary file", &temp_path))?;
+        write(&mut file)?;
+        file.sync_all().map_err(WizardError::io("flush", path))?;
+        fs::rename(&temp_path, path).map_err(WizardError::io("replace", path))
+    })();
+
+    // Never leave the temporary file behind when something went wrong
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Build a unique temporary path next to the target so the final rename stays on one filesystem
+fn temp_path_for(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "output".to_string());
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or(0);
+
+    path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        file_name,
+        std::process::id(),
+        nanos
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupted_write_leaves_the_original_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        write_atomic(&path, "{\"original\": true}").unwrap();
+
+        let result = write_atomic_with(&path, |file| {
+            file.write_all(b"{\"replacement\":").unwrap();
+            Err(WizardError::Parse(
+                "serialization failed halfway".to_string(),
+            ))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"original\": true}");
+        let files: Vec<_> = fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(files.len(), 1);
+    }
+}
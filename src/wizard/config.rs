@@ -3,17 +3,100 @@
 //! This module handles loading and managing configuration settings,
 //! including domain definitions.
 
-use anyhow::{Context as _, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
 
+use super::atomic::write_atomic;
+use super::error::{Result, WizardError};
+use super::language::find_language;
+use super::llm::{LlmConfig, TemperatureSchedule};
+use super::moderation::ModerationConfig;
+use super::output::{ConfidenceStyle, DuplicateSectionPolicy, MarkdownStyle};
+use super::session::QuestionTimeoutAction;
+
 /// Configuration for the wizard
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     /// Available domains
     pub domains: Vec<String>,
+    /// Translated domain lists, keyed by locale (`de`, `pt-BR`) or language name (`German`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub domains_by_locale: BTreeMap<String, Vec<String>>,
+    /// Model to use instead of the built-in default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// How confidence levels are marked in generated documents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence_style: Option<ConfidenceStyle>,
+    /// House style of generated documents (heading level, numbering, marker placement, timestamp)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markdown_style: Option<MarkdownStyle>,
+    /// What to do when the model repeats a section heading, merge if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_sections: Option<DuplicateSectionPolicy>,
+    /// Confidence (1-5) assumed for generated sections without a confidence marker, 3 if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_confidence: Option<u8>,
+    /// Maximum number of tokens to generate per request, 4096 if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u16>,
+    /// Output token limits of models, by model ID, added to the built-in table
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub model_output_limits: BTreeMap<String, u16>,
+    /// Sequences at which the model stops generating, sent with every request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    /// Topics never to ask about, added to those given with `--avoid`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub avoid_topics: Vec<String>,
+    /// Header to send a per-request idempotency key in (e.g. `Idempotency-Key`), none if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_header: Option<String>,
+    /// Times a request is sent again when no response arrived (default 0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_retries: Option<u32>,
+    /// Named sets of model settings, selected with `--profile` to override the settings above
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// Options of a multiple choice question shown before a show-more entry, all if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_options_display: Option<usize>,
+    /// Check each answer with a moderation service before it is sent, off if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<ModerationConfig>,
+    /// Wall-clock limit for an interview in seconds, used when `--timeout` is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_timeout: Option<u64>,
+    /// Time to answer each question in seconds, used when `--question-timeout` is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub question_timeout: Option<u64>,
+    /// What happens to a question that isn't answered in time (default skip)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub question_timeout_action: Option<QuestionTimeoutAction>,
+    /// Repetitive questions in a row before offering to end the interview (default 3, 0 = off)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exhaustion_threshold: Option<usize>,
+    /// Ask malformed questions in a simpler form instead of failing, off if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repair_questions: Option<bool>,
+    /// Message asking the model to correct a response that isn't the expected JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_retry_instruction: Option<String>,
+    /// Times the model is asked to correct a response that isn't the expected JSON (default 1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_retry_max: Option<u32>,
+    /// Automatic retries allowed across a whole session, unlimited if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_budget: Option<usize>,
+    /// Directory offered when saving a project definition, updated to the last one used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+    /// Path offered when saving a session, updated to the last one used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_session_path: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -206,6 +289,31 @@ impl Default for Config {
 
         Self {
             domains: default_domains.into_iter().map(String::from).collect(),
+            domains_by_locale: BTreeMap::new(),
+            model: None,
+            confidence_style: None,
+            markdown_style: None,
+            duplicate_sections: None,
+            default_confidence: None,
+            max_tokens: None,
+            model_output_limits: BTreeMap::new(),
+            stop_sequences: None,
+            avoid_topics: Vec::new(),
+            idempotency_header: None,
+            network_retries: None,
+            profiles: BTreeMap::new(),
+            moderation: None,
+            max_options_display: None,
+            session_timeout: None,
+            question_timeout: None,
+            question_timeout_action: None,
+            exhaustion_threshold: None,
+            repair_questions: None,
+            json_retry_instruction: None,
+            json_retry_max: None,
+            retry_budget: None,
+            output_dir: None,
+            last_session_path: None,
         }
     }
 }
@@ -213,24 +321,63 @@ impl Default for Config {
 impl Config {
     /// Load configuration from a file
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let file = File::open(&path)
-            .with_context(|| format!("Failed to open config file: {}", path.as_ref().display()))?;
+        let path = path.as_ref();
+        let file = File::open(path).map_err(WizardError::io("open config file", path))?;
         let reader = BufReader::new(file);
-        let config = serde_json::from_reader(reader)
-            .with_context(|| format!("Failed to parse config file: {}", path.as_ref().display()))?;
-        Ok(config)
+        serde_json::from_reader(reader)
+            .map_err(WizardError::json(format!("config file {}", path.display())))
     }
 
     /// Save configuration to a file
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let file = File::create(&path).with_context(|| {
-            format!("Failed to create config file: {}", path.as_ref().display())
-        })?;
-        serde_json::to_writer_pretty(file, self)
-            .with_context(|| format!("Failed to write config file: {}", path.as_ref().display()))?;
+        let json = serde_json::to_string_pretty(self).map_err(WizardError::serialize(format!(
+            "config file {}",
+            path.as_ref().display()
+        )))?;
+        write_atomic(&path, json)
+    }
+
+    /// Load configuration from the default path, falling back to the defaults
+    pub fn load_or_default() -> Self {
+        let default_path = Self::default_path();
+        if default_path.exists() {
+            Self::load_from_file(default_path).unwrap_or_default()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Save configuration to the default path, creating its directory if needed
+    pub fn save_to_default_path(&self) -> Result<PathBuf> {
+        let path = Self::default_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(WizardError::io("create config directory", dir))?;
+        }
+        self.save_to_file(&path)?;
+        Ok(path)
+    }
+
+    /// Update the configuration file at the default path, keeping its other settings
+    pub fn update_default(update: impl FnOnce(&mut Self)) -> Result<()> {
+        let mut config = Self::load_or_default();
+        update(&mut config);
+        config.save_to_default_path()?;
         Ok(())
     }
 
+    /// Get the domain list for a locale or language, falling back to the English `domains`
+    pub fn domains_for(&self, locale: Option<&str>) -> &[String] {
+        locale
+            .and_then(|locale| {
+                self.domains_by_locale
+                    .iter()
+                    .find(|(key, domains)| !domains.is_empty() && locale_matches(key, locale))
+            })
+            .map(|(_, domains)| domains.as_slice())
+            .unwrap_or(&self.domains)
+    }
+
     /// Get the default configuration file path
     pub fn default_path() -> PathBuf {
         dirs::config_dir()
@@ -239,3 +386,168 @@ impl Config {
             .join("config.json")
     }
 }
+
+/// Model settings of a named profile, each replacing the top-level setting only when given
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileConfig {
+    /// Model to use for chat completions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Sampling temperature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Maximum number of tokens to generate per request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u16>,
+    /// API key, for a profile using another account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Temperature schedule for question generation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_schedule: Option<TemperatureSchedule>,
+    /// Models to try, in order, when the model is unavailable or rate-limited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_models: Option<Vec<String>>,
+    /// Maximum size of a response body in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_response_bytes: Option<usize>,
+    /// Maximum size of the conversation context in the definition prompt, in characters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_definition_context_chars: Option<usize>,
+    /// Maximum size of a question prompt, in characters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_question_prompt_chars: Option<usize>,
+    /// Ask malformed questions in a simpler form instead of failing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repair_questions: Option<bool>,
+    /// Message asking the model to correct a response that isn't the expected JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_retry_instruction: Option<String>,
+    /// Times the model is asked to correct a response that isn't the expected JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_retry_max: Option<u32>,
+    /// Sequences at which the model stops generating
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    /// Topics never to ask about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avoid_topics: Option<Vec<String>>,
+    /// Header to send a per-request idempotency key in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_header: Option<String>,
+    /// Times a request is sent again when no response arrived
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_retries: Option<u32>,
+}
+
+impl ProfileConfig {
+    /// Replace the settings of a client configuration that the profile gives
+    pub fn apply_to(self, config: &mut LlmConfig) {
+        if let Some(model) = self.model {
+            config.model = model;
+        }
+        if let Some(temperature) = self.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            config.max_tokens = max_tokens;
+        }
+        if self.api_key.is_some() {
+            config.api_key = self.api_key;
+        }
+        if self.temperature_schedule.is_some() {
+            config.temperature_schedule = self.temperature_schedule;
+        }
+        if let Some(fallback_models) = self.fallback_models {
+            config.fallback_models = fallback_models;
+        }
+        if let Some(max_response_bytes) = self.max_response_bytes {
+            config.max_response_bytes = max_response_bytes;
+        }
+        if let Some(max_definition_context_chars) = self.max_definition_context_chars {
+            config.max_definition_context_chars = max_definition_context_chars;
+        }
+        if let Some(max_question_prompt_chars) = self.max_question_prompt_chars {
+            config.max_question_prompt_chars = max_question_prompt_chars;
+        }
+        if let Some(repair_questions) = self.repair_questions {
+            config.repair_questions = repair_questions;
+        }
+        if let Some(json_retry_instruction) = self.json_retry_instruction {
+            config.json_retry_instruction = json_retry_instruction;
+        }
+        if let Some(json_retry_max) = self.json_retry_max {
+            config.json_retry_max = json_retry_max;
+        }
+        if let Some(stop) = self.stop {
+            config.stop = Some(stop).filter(|stop| !stop.is_empty());
+        }
+        if let Some(avoid_topics) = self.avoid_topics {
+            config.avoid_topics = avoid_topics;
+        }
+        if self.idempotency_header.is_some() {
+            config.idempotency_header = self.idempotency_header;
+        }
+        if let Some(network_retries) = self.network_retries {
+            config.network_retries = network_retries;
+        }
+    }
+}
+
+/// Check whether a `domains_by_locale` key applies to a locale or language name
+///
+/// `de` matches `de_DE.UTF-8` and `de-AT`, and language names match in any of the
+/// forms `--language` accepts, so `German` matches `Deutsch` and `deu`.
+fn locale_matches(key: &str, locale: &str) -> bool {
+    let normalize = |value: &str| {
+        value
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .replace('_', "-")
+            .to_lowercase()
+    };
+    let key = normalize(key);
+    let locale = normalize(locale);
+    if key.is_empty() || locale.is_empty() {
+        return false;
+    }
+
+    let language = |value: &str| value.split('-').next().unwrap_or_default().to_string();
+    if key == locale || (!key.contains('-') && key == language(&locale)) {
+        return true;
+    }
+    matches!(
+        (find_language(&key), find_language(&locale)),
+        (Some(key), Some(locale)) if key == locale
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_selects_the_localized_domain_list() {
+        let german = vec!["Gesundheitswesen".to_string(), "Finanzen".to_string()];
+        let portuguese = vec!["Saúde".to_string(), "Finanças".to_string()];
+        let config = Config {
+            domains: vec!["Healthcare".to_string(), "Finance".to_string()],
+            domains_by_locale: BTreeMap::from([
+                ("de".to_string(), german.clone()),
+                ("Portuguese".to_string(), portuguese.clone()),
+                ("French".to_string(), Vec::new()),
+            ]),
+            ..Config::default()
+        };
+
+        assert_eq!(config.domains_for(Some("de_DE.UTF-8")), german);
+        assert_eq!(config.domains_for(Some("de-AT")), german);
+        assert_eq!(config.domains_for(Some("Português")), portuguese);
+        assert_eq!(config.domains_for(Some("por")), portuguese);
+        assert_eq!(config.domains_for(Some("French")), config.domains);
+        assert_eq!(config.domains_for(Some("ja")), config.domains);
+        assert_eq!(config.domains_for(None), config.domains);
+    }
+}
@@ -4,9 +4,64 @@
 //! and maintains the state of the wizard session.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::path::Path;
 
 use super::Question;
+use super::error::{Result, WizardError};
+use super::question::QuestionType;
+
+/// Typed value of an answer, normalized from the response per question type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AnswerValue {
+    /// Selected option of a multiple choice question
+    Choice(String),
+    /// Answer to a yes/no question
+    YesNo(bool),
+    /// Selected value of a rating scale question
+    Rating(u8),
+    /// Answer to a free text question
+    Text(String),
+}
+
+impl AnswerValue {
+    /// Normalize a response string into a typed value for the given question
+    pub fn from_response(question: &Question, response: &str) -> Option<Self> {
+        match question.question_type {
+            QuestionType::MultipleChoice => Some(Self::Choice(response.to_string())),
+            QuestionType::YesNo => match response.trim().to_lowercase().as_str() {
+                "yes" | "y" | "true" => Some(Self::YesNo(true)),
+                "no" | "n" | "false" => Some(Self::YesNo(false)),
+                _ => None,
+            },
+            QuestionType::RatingScale => response.trim().parse().ok().map(Self::Rating),
+            QuestionType::FreeText => Some(Self::Text(response.to_string())),
+        }
+    }
+
+    /// Normalize a response along with its typed value, writing yes/no answers as "Yes" or "No"
+    fn normalize(question: &Question, response: String) -> (String, Option<Self>) {
+        let value = Self::from_response(question, &response);
+        let response = match value {
+            Some(Self::YesNo(true)) => "Yes".to_string(),
+            Some(Self::YesNo(false)) => "No".to_string(),
+            _ => response,
+        };
+        (response, value)
+    }
+}
+
+/// Previous value of an answer that was edited
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    /// The response before the edit
+    pub previous_response: String,
+    /// Timestamp of the edit
+    pub edited_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Text written in place of a sensitive answer in logs and redacted session files
+pub const REDACTED: &str = "[REDACTED]";
 
 /// Represents a user's answer to a question
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,25 +70,466 @@ pub struct Answer {
     pub question: Question,
     /// The user's response
     pub response: String,
+    /// The response normalized to a typed value
+    #[serde(default)]
+    pub value: Option<AnswerValue>,
     /// Timestamp when the answer was provided
     pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// How confident the user said they were in the answer (1-5)
+    #[serde(default)]
+    pub self_confidence: Option<u8>,
+    /// Tags used to categorize the answer (e.g. "security", "ux")
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Previous responses, oldest first, recorded whenever the answer is edited
+    #[serde(default)]
+    pub revisions: Vec<Revision>,
+    /// Whether the user skipped the question instead of answering it
+    #[serde(default)]
+    pub skipped: bool,
 }
 
 impl Answer {
+    /// Get a copy with the response and earlier revisions replaced by `[REDACTED]` if the question is sensitive
+    pub fn redacted(&self) -> Self {
+        let mut answer = self.clone();
+        if answer.question.sensitive {
+            answer.response = REDACTED.to_string();
+            answer.value = None;
+            for revision in &mut answer.revisions {
+                revision.previous_response = REDACTED.to_string();
+            }
+        }
+        answer
+    }
+
     /// Create a new answer
     pub fn new(question: Question, response: impl Into<String>) -> Self {
+        let (response, value) = AnswerValue::normalize(&question, response.into());
+
         Self {
             question,
-            response: response.into(),
+            response,
+            value,
             timestamp: chrono::Utc::now(),
+            self_confidence: None,
+            tags: Vec::new(),
+            revisions: Vec::new(),
+            skipped: false,
+        }
+    }
+
+    /// Create an answer recording that the question was skipped
+    pub fn new_skipped(question: Question) -> Self {
+        Self {
+            value: None,
+            skipped: true,
+            ..Self::new(question, SKIPPED_RESPONSE)
         }
     }
+
+    /// Change the response, recording the previous one as a revision if it differs
+    pub fn edit(&mut self, response: impl Into<String>) -> bool {
+        let (response, value) = AnswerValue::normalize(&self.question, response.into());
+        if response == self.response {
+            return false;
+        }
+
+        let previous_response = std::mem::replace(&mut self.response, response);
+        self.revisions.push(Revision {
+            previous_response,
+            edited_at: chrono::Utc::now(),
+        });
+        self.value = value;
+        true
+    }
+
+    /// Add a tag to the answer, ignoring duplicates
+    pub fn add_tag(&mut self, tag: impl Into<String>) {
+        let tag = tag.into().trim().to_lowercase();
+        if !tag.is_empty() && !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+    }
+
+    /// Check whether the answer has a tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim().to_lowercase();
+        self.tags.contains(&tag)
+    }
+
+    /// Get the numeric value of a rating scale answer
+    pub fn rating(&self) -> Option<u8> {
+        match self.value {
+            Some(AnswerValue::Rating(rating)) => Some(rating),
+            _ => None,
+        }
+    }
+
+    /// Set the user's self-reported confidence in the answer
+    pub fn with_self_confidence(mut self, self_confidence: Option<u8>) -> Self {
+        self.self_confidence = self_confidence;
+        self
+    }
 }
 
-/// Enum representing different persona modes for the wizard
+/// Response recorded for a skipped question
+pub const SKIPPED_RESPONSE: &str = "(skipped)";
+
+/// Maximum number of characters of a single attachment included in the LLM context
+pub const MAX_ATTACHMENT_CHARS: usize = 8000;
+
+/// Context string sent to the LLM before anything is known about the project
+pub const EMPTY_CONTEXT_MESSAGE: &str = "No prior context; this is the first question.\n";
+
+/// Common words ignored when extracting covered topics from questions
+const TOPIC_STOPWORDS: &[&str] = &[
+    "about",
+    "also",
+    "anything",
+    "application",
+    "been",
+    "being",
+    "both",
+    "could",
+    "does",
+    "each",
+    "else",
+    "from",
+    "have",
+    "kind",
+    "like",
+    "many",
+    "more",
+    "most",
+    "much",
+    "need",
+    "needs",
+    "other",
+    "plan",
+    "planning",
+    "please",
+    "project",
+    "should",
+    "some",
+    "such",
+    "than",
+    "that",
+    "their",
+    "them",
+    "there",
+    "these",
+    "they",
+    "this",
+    "those",
+    "through",
+    "type",
+    "types",
+    "used",
+    "using",
+    "want",
+    "what",
+    "when",
+    "where",
+    "which",
+    "while",
+    "will",
+    "with",
+    "within",
+    "would",
+    "your",
+];
+
+/// What happens when an answer would take the history past its cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryCapPolicy {
+    /// Refuse to add more answers
+    #[default]
+    Refuse,
+    /// Fold the oldest answers into a condensed summary and drop them
+    Summarize,
+}
+
+/// Limit on the number of answers kept in the history
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct HistoryCap {
+    /// Maximum number of answers kept in the history
+    pub max_answers: usize,
+    /// What happens when the cap is reached
+    pub policy: HistoryCapPolicy,
+}
+
+/// Extra weight given to structured answers when trimming, as they are short but precise
+const STRUCTURED_ANSWER_SIGNAL: usize = 200;
+
+/// Estimate how much useful information an answer carries, for context trimming
+fn answer_signal(answer: &Answer) -> usize {
+    if answer.skipped {
+        return 0;
+    }
+
+    let length = answer.response.trim().chars().count();
+    match answer.question.question_type {
+        QuestionType::FreeText => length,
+        _ => length + STRUCTURED_ANSWER_SIGNAL,
+    }
+}
+
+/// Maximum number of characters of a response kept in the history summary
+const SUMMARY_RESPONSE_CHARS: usize = 120;
+
+/// Maximum number of running notes kept, the oldest being dropped first
+pub const MAX_NOTES: usize = 20;
+
+/// Maximum number of characters kept of a single running note
+const MAX_NOTE_CHARS: usize = 200;
+
+/// Share of keywords two questions must have in common to count as the same question
+const REPEAT_SIMILARITY: f32 = 0.6;
+
+/// Split a question into its distinct lowercased keywords, ignoring short and common words
+fn topic_words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .map(|word| word.trim_matches('-').to_lowercase())
+        .filter(|word| word.chars().count() >= 4 && !TOPIC_STOPWORDS.contains(&word.as_str()))
+    {
+        if !words.contains(&word) {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Get the share of keywords two question texts have in common (0.0-1.0)
+pub fn topic_similarity(text: &str, other: &str) -> f32 {
+    let words = topic_words(text);
+    let other = topic_words(other);
+    let shared = words.iter().filter(|word| other.contains(word)).count();
+    let combined = words.len() + other.len() - shared;
+    if combined == 0 {
+        return 0.0;
+    }
+    shared as f32 / combined as f32
+}
+
+/// Pick the more detailed of two optional texts, preferring the first on a tie
+fn richer(text: Option<String>, other: &Option<String>) -> Option<String> {
+    match (text, other) {
+        (Some(text), Some(other)) if other.trim().len() > text.trim().len() => Some(other.clone()),
+        (None, other) => other.clone(),
+        (text, _) => text,
+    }
+}
+
+/// Fields of the YAML front matter at the top of a Markdown reference document
+///
+/// Only simple front matter is understood: `key: value` lines and lists of
+/// strings, written either as `- item` lines or as `[a, b]`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrontMatter {
+    /// Title of the document or project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Domain the project belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// Who the project is for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+    /// Goals of the project
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub goals: Vec<String>,
+    /// Any other fields, ordered by key
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub other: BTreeMap<String, String>,
+}
+
+impl FrontMatter {
+    /// Split a document into its front matter and the rest, if it starts with a `---` block
+    pub fn split(content: &str) -> Option<(Self, &str)> {
+        let content = content.trim_start_matches('\u{feff}');
+        let yaml = content
+            .strip_prefix("---\n")
+            .or_else(|| content.strip_prefix("---\r\n"))?;
+
+        let mut offset = 0;
+        for line in yaml.split_inclusive('\n') {
+            if matches!(line.trim_end(), "---" | "...") {
+                return Some((Self::parse(&yaml[..offset]), &yaml[offset + line.len()..]));
+            }
+            offset += line.len();
+        }
+        None
+    }
+
+    /// Parse the YAML between the front matter delimiters
+    fn parse(yaml: &str) -> Self {
+        let mut front_matter = Self::default();
+        let mut list_key: Option<String> = None;
+
+        for line in yaml.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if let Some(item) = trimmed.strip_prefix('-') {
+                if let Some(key) = &list_key {
+                    front_matter.set(key, unquote(item));
+                }
+                continue;
+            }
+
+            let Some((key, value)) = trimmed.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+            if value.is_empty() {
+                list_key = Some(key);
+                continue;
+            }
+            list_key = None;
+            match value
+                .strip_prefix('[')
+                .and_then(|value| value.strip_suffix(']'))
+            {
+                Some(items) => {
+                    for item in items.split(',') {
+                        front_matter.set(&key, unquote(item));
+                    }
+                }
+                None => front_matter.set(&key, unquote(value)),
+            }
+        }
+
+        front_matter
+    }
+
+    /// Set a field, adding to it if it was already given
+    fn set(&mut self, key: &str, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        let field = match key {
+            "title" => &mut self.title,
+            "domain" => &mut self.domain,
+            "audience" => &mut self.audience,
+            "goals" | "goal" => return self.goals.push(value.to_string()),
+            _ => {
+                self.other
+                    .entry(key.to_string())
+                    .and_modify(|existing| existing.push_str(&format!(", {}", value)))
+                    .or_insert_with(|| value.to_string());
+                return;
+            }
+        };
+        match field {
+            Some(existing) => existing.push_str(&format!(", {}", value)),
+            None => *field = Some(value.to_string()),
+        }
+    }
+
+    /// Check whether no field was given
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Render the fields other than the domain as starting hints, if there are any
+    pub fn hints(&self) -> Option<String> {
+        let mut lines = Vec::new();
+        if let Some(title) = &self.title {
+            lines.push(format!("Title: {}", title));
+        }
+        if let Some(audience) = &self.audience {
+            lines.push(format!("Audience: {}", audience));
+        }
+        if !self.goals.is_empty() {
+            lines.push(format!("Goals: {}", self.goals.join("; ")));
+        }
+        for (key, value) in &self.other {
+            lines.push(format!("{}: {}", key, value));
+        }
+
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+}
+
+/// Remove the quotes around a YAML scalar
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value| value.strip_suffix(*quote))
+        })
+        .unwrap_or(value)
+}
+
+/// Reference document attached to the context
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    /// Name of the attachment (usually the file name)
+    pub name: String,
+    /// Text content of the attachment, without its front matter
+    pub content: String,
+    /// Front matter of a Markdown attachment, used to seed the context
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub front_matter: Option<FrontMatter>,
+}
+
+impl Attachment {
+    /// Create a new attachment
+    pub fn new(name: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            content: content.into(),
+            front_matter: None,
+        }
+    }
+
+    /// Read an attachment from a text or markdown file
+    ///
+    /// The YAML front matter of a Markdown file is kept apart from its content.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(WizardError::io("read", path))?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+
+        let markdown = path.extension().is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown")
+        });
+        if markdown && let Some((front_matter, body)) = FrontMatter::split(&content) {
+            return Ok(Self {
+                name,
+                content: body.trim_start().to_string(),
+                front_matter: Some(front_matter).filter(|front_matter| !front_matter.is_empty()),
+            });
+        }
+        Ok(Self::new(name, content))
+    }
+
+    /// Get the content truncated to at most `max_chars` characters
+    pub fn truncated_content(&self, max_chars: usize) -> String {
+        match self.content.char_indices().nth(max_chars) {
+            Some((end, _)) => format!("{}\n[... truncated ...]", &self.content[..end]),
+            None => self.content.clone(),
+        }
+    }
+}
+
+/// Enum representing different persona modes for the wizard
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum Persona {
     /// Default persona
+    #[default]
     Default,
     /// Product Manager persona
     ProductManager,
@@ -45,40 +541,87 @@ pub enum Persona {
     ComplianceOfficer,
 }
 
-impl Default for Persona {
-    fn default() -> Self {
-        Self::Default
+impl std::fmt::Display for Persona {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Persona::Default => write!(f, "Default"),
+            Persona::ProductManager => write!(f, "Product Manager"),
+            Persona::LlmArchitect => write!(f, "LLM Architect"),
+            Persona::UxDesigner => write!(f, "UX Designer"),
+            Persona::ComplianceOfficer => write!(f, "Compliance Officer"),
+        }
+    }
+}
+
+/// Project setup sent along with answers collected outside the wizard, e.g. by another frontend
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContextMeta {
+    /// Starting hints describing the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub starting_hints: Option<String>,
+    /// Domain of the project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// Name of the project, used as the document title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_name: Option<String>,
+    /// Persona the answers were collected for
+    #[serde(default)]
+    pub persona: Persona,
+    /// Additional metadata
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl ContextMeta {
+    /// Build the context of an interview that produced the given answers
+    pub fn into_context(self, answers: Vec<Answer>) -> Context {
+        Context {
+            starting_hints: self.starting_hints,
+            domain: self.domain,
+            project_name: self.project_name,
+            current_index: answers.len(),
+            history: answers,
+            persona: self.persona,
+            metadata: self.metadata,
+            ..Context::new()
+        }
     }
 }
 
 /// Context for the wizard session
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Context {
     /// User-provided starting hints
     pub starting_hints: Option<String>,
     /// Selected domain (if any)
     pub domain: Option<String>,
+    /// Concise name of the project, used for the document title and file names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_name: Option<String>,
     /// History of questions and answers
     pub history: Vec<Answer>,
     /// Current question index
     pub current_index: usize,
     /// Selected persona mode
     pub persona: Persona,
-    /// Additional metadata
-    pub metadata: HashMap<String, String>,
-}
-
-impl Default for Context {
-    fn default() -> Self {
-        Self {
-            starting_hints: None,
-            domain: None,
-            history: Vec::new(),
-            current_index: 0,
-            persona: Persona::default(),
-            metadata: HashMap::new(),
-        }
-    }
+    /// Additional metadata, ordered by key so output is stable between runs
+    pub metadata: BTreeMap<String, String>,
+    /// Reference documents provided by the user
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Limit on the number of answers kept in the history
+    #[serde(default)]
+    pub history_cap: Option<HistoryCap>,
+    /// Condensed record of answers dropped from the history by the cap
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Number of answers dropped from the history by the cap
+    #[serde(default)]
+    pub summarized_count: usize,
+    /// The persona's running notes on the project, one line added after each answer
+    #[serde(default)]
+    pub notes: Vec<String>,
 }
 
 impl Context {
@@ -88,6 +631,10 @@ impl Context {
     }
 
     /// Create a new context with starting hints
+    #[allow(
+        dead_code,
+        reason = "constructor for callers outside the interactive wizard"
+    )]
     pub fn with_hints(hints: impl Into<String>) -> Self {
         Self {
             starting_hints: Some(hints.into()),
@@ -96,6 +643,10 @@ impl Context {
     }
 
     /// Create a new context with a specific domain
+    #[allow(
+        dead_code,
+        reason = "constructor for callers outside the interactive wizard"
+    )]
     pub fn with_domain(domain: impl Into<String>) -> Self {
         Self {
             domain: Some(domain.into()),
@@ -104,16 +655,147 @@ impl Context {
     }
 
     /// Set the persona mode
+    #[allow(
+        dead_code,
+        reason = "builder for callers outside the interactive wizard"
+    )]
     pub fn with_persona(mut self, persona: Persona) -> Self {
         self.persona = persona;
         self
     }
 
+    /// Attach a reference document to the context
+    ///
+    /// Its front matter sets the domain if none was chosen, and adds the other
+    /// fields to the starting hints.
+    pub fn attach(&mut self, attachment: Attachment) {
+        if let Some(front_matter) = &attachment.front_matter {
+            if self.domain.is_none() {
+                self.domain = front_matter.domain.clone();
+            }
+            if let Some(hints) = front_matter.hints() {
+                let hints = format!("From {}:\n{}", attachment.name, hints);
+                self.starting_hints = Some(match self.starting_hints.take() {
+                    Some(existing) => format!("{}\n\n{}", existing, hints),
+                    None => hints,
+                });
+            }
+        }
+        self.attachments.push(attachment);
+    }
+
+    /// Read a text or markdown file and attach it to the context
+    pub fn attach_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let attachment = Attachment::from_file(path)?;
+        self.attach(attachment);
+        Ok(())
+    }
+
     /// Add an answer to the context
-    pub fn add_answer(&mut self, question: Question, response: impl Into<String>) {
-        let answer = Answer::new(question, response);
+    pub fn add_answer(&mut self, question: Question, response: impl Into<String>) -> Result<()> {
+        self.add_answer_with_confidence(question, response, None)
+    }
+
+    /// Add an answer along with the user's self-reported confidence in it
+    pub fn add_answer_with_confidence(
+        &mut self,
+        question: Question,
+        response: impl Into<String>,
+        self_confidence: Option<u8>,
+    ) -> Result<()> {
+        let answer = Answer::new(question, response).with_self_confidence(self_confidence);
+        self.push_answer(answer)
+    }
+
+    /// Record that a question was skipped; it still counts towards the question total
+    pub fn skip_question(&mut self, question: Question) -> Result<()> {
+        self.push_answer(Answer::new_skipped(question))
+    }
+
+    /// Append an answer to the history, applying the history cap
+    fn push_answer(&mut self, answer: Answer) -> Result<()> {
+        if let Some(cap) = self.history_cap
+            && self.history.len() >= cap.max_answers
+        {
+            match cap.policy {
+                HistoryCapPolicy::Refuse => {
+                    return Err(WizardError::State(format!(
+                        "The session history is capped at {} answers",
+                        cap.max_answers
+                    )));
+                }
+                HistoryCapPolicy::Summarize => {
+                    let excess = self.history.len() + 1 - cap.max_answers.max(1);
+                    self.summarize_oldest(excess);
+                }
+            }
+        }
+
         self.history.push(answer);
         self.current_index = self.history.len();
+        Ok(())
+    }
+
+    /// Fold the oldest answers into the condensed summary and drop them from the history
+    fn summarize_oldest(&mut self, count: usize) {
+        let count = count.min(self.history.len());
+        let mut summary = self.summary.take().unwrap_or_default();
+
+        for answer in self.history.drain(..count) {
+            let response = match answer.response.char_indices().nth(SUMMARY_RESPONSE_CHARS) {
+                Some((end, _)) => format!("{}...", &answer.response[..end]),
+                None => answer.response,
+            };
+            summary.push_str(&format!("- {} → {}\n", answer.question.text, response));
+        }
+
+        self.summary = Some(summary);
+        self.summarized_count += count;
+        self.current_index = self.history.len();
+    }
+
+    /// Get the total number of answers given, including those dropped by the history cap
+    pub fn answer_count(&self) -> usize {
+        self.summarized_count + self.history.len()
+    }
+
+    /// Get the number of skipped questions still in the history
+    pub fn skipped_count(&self) -> usize {
+        self.history.iter().filter(|answer| answer.skipped).count()
+    }
+
+    /// Edit the answer at a 0-based position in the history, returning whether it changed
+    pub fn edit_answer(&mut self, index: usize, response: impl Into<String>) -> Result<bool> {
+        match self.history.get_mut(index) {
+            Some(answer) => Ok(answer.edit(response)),
+            None => Err(WizardError::Invalid(format!(
+                "There is no answer number {}",
+                index + 1
+            ))),
+        }
+    }
+
+    /// Tag the most recent answer, returning false if there is none
+    pub fn tag_last_answer(&mut self, tag: impl Into<String>) -> bool {
+        match self.history.last_mut() {
+            Some(answer) => {
+                answer.add_tag(tag);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get all answers with a tag
+    #[allow(
+        dead_code,
+        reason = "tag filter for callers outside the interactive wizard"
+    )]
+    pub fn answers_with_tag(&self, tag: &str) -> Vec<&Answer> {
+        self.history
+            .iter()
+            .filter(|answer| answer.has_tag(tag))
+            .collect()
     }
 
     /// Go back to a previous question
@@ -127,6 +809,7 @@ impl Context {
     }
 
     /// Go forward to a next question (if we've gone back)
+    #[allow(dead_code, reason = "the wizard navigates through SessionManager")]
     pub fn go_forward(&mut self) -> Option<&Answer> {
         if self.current_index < self.history.len() - 1 {
             self.current_index += 1;
@@ -137,6 +820,7 @@ impl Context {
     }
 
     /// Get the current answer
+    #[allow(dead_code, reason = "the wizard navigates through SessionManager")]
     pub fn current_answer(&self) -> Option<&Answer> {
         if self.current_index < self.history.len() {
             self.history.get(self.current_index)
@@ -145,8 +829,24 @@ impl Context {
         }
     }
 
+    /// Check whether nothing the context string shows is known yet
+    pub fn is_empty(&self) -> bool {
+        self.starting_hints.is_none()
+            && self.domain.is_none()
+            && self.project_name.is_none()
+            && self.notes.is_empty()
+            && self.attachments.is_empty()
+            && self.summary.is_none()
+            && self.history.is_empty()
+    }
+
     /// Get all answers as a formatted string for LLM context
     pub fn get_context_string(&self) -> String {
+        // Say so explicitly rather than sending empty headers the model may read as withheld context
+        if self.is_empty() {
+            return EMPTY_CONTEXT_MESSAGE.to_string();
+        }
+
         let mut context = String::new();
 
         // Add starting hints if available
@@ -159,28 +859,662 @@ impl Context {
             context.push_str(&format!("Domain: {}\n\n", domain));
         }
 
+        // Add the project name if one was chosen
+        if let Some(name) = &self.project_name {
+            context.push_str(&format!("Project name: {}\n\n", name));
+        }
+
+        // Add reference material if available
+        if !self.attachments.is_empty() {
+            context.push_str("Reference Material:\n");
+            for attachment in &self.attachments {
+                context.push_str(&format!(
+                    "--- {} ---\n{}\n\n",
+                    attachment.name,
+                    attachment.truncated_content(MAX_ATTACHMENT_CHARS)
+                ));
+            }
+        }
+
+        // Add answers dropped from the history by the cap
+        if let Some(summary) = &self.summary {
+            context.push_str(&format!("Summary of earlier answers:\n{}\n", summary));
+        }
+
+        // Add the persona's understanding of the project so far
+        if !self.notes.is_empty() {
+            context.push_str("Your running notes on the project:\n");
+            for note in &self.notes {
+                context.push_str(&format!("- {}\n", note));
+            }
+            context.push('\n');
+        }
+
         // Add question-answer history
-        context.push_str("Previous questions and answers:\n");
+        if !self.history.is_empty() {
+            context.push_str("Previous questions and answers:\n");
+        }
         for (i, answer) in self.history.iter().enumerate() {
+            let number = self.summarized_count + i + 1;
             context.push_str(&format!(
-                "Q{}: {}\nA{}: {}\n\n",
-                i + 1,
-                answer.question.text,
-                i + 1,
-                answer.response
+                "Q{}: {}\nA{}: {}\n",
+                number, answer.question.text, number, answer.response
             ));
+
+            if !answer.tags.is_empty() {
+                context.push_str(&format!("(Tags: {})\n", answer.tags.join(", ")));
+            }
+
+            // Flag how sure the user was so uncertain answers can be weighted down
+            if let Some(confidence) = answer.self_confidence {
+                context.push_str(&format!(
+                    "(User's self-reported certainty: {}/5)\n",
+                    confidence
+                ));
+            }
+
+            context.push('\n');
         }
 
         context
     }
 
+    /// Get the context string, dropping the lowest-signal answers until it fits in `max_chars`
+    ///
+    /// Skipped questions and short free-text answers go first; structured answers and
+    /// long free-text answers are kept as long as possible. If the context is still too
+    /// long once every answer is dropped, it is cut off at the limit.
+    pub fn get_trimmed_context_string(&self, max_chars: usize) -> String {
+        let full = self.get_context_string();
+        if full.chars().count() <= max_chars {
+            return full;
+        }
+
+        let mut order: Vec<usize> = (0..self.history.len()).collect();
+        order.sort_by_key(|&index| answer_signal(&self.history[index]));
+
+        let mut trimmed = self.clone();
+        let mut dropped = vec![false; self.history.len()];
+        let mut context = full;
+        for (count, index) in order.into_iter().enumerate() {
+            dropped[index] = true;
+            trimmed.history = self
+                .history
+                .iter()
+                .zip(&dropped)
+                .filter(|(_, dropped)| !**dropped)
+                .map(|(answer, _)| answer.clone())
+                .collect();
+
+            context = format!(
+                "{}({} lower-signal answers omitted to fit the prompt)\n",
+                trimmed.get_context_string(),
+                count + 1
+            );
+            if context.chars().count() <= max_chars {
+                return context;
+            }
+        }
+
+        context.chars().take(max_chars).collect()
+    }
+
+    /// Get the average of all rating scale answers
+    #[allow(
+        dead_code,
+        reason = "aggregate for callers outside the interactive wizard"
+    )]
+    pub fn average_rating(&self) -> Option<f32> {
+        let ratings: Vec<u8> = self.history.iter().filter_map(Answer::rating).collect();
+
+        if ratings.is_empty() {
+            None
+        } else {
+            Some(ratings.iter().map(|&r| r as f32).sum::<f32>() / ratings.len() as f32)
+        }
+    }
+
+    /// Extract up to `max` keywords for topics already covered by answered questions
+    ///
+    /// Keywords are ranked by how many questions mention them, ties keeping the order
+    /// in which they first appeared.
+    pub fn covered_topics(&self, max: usize) -> Vec<String> {
+        let mut topics: Vec<(String, usize)> = Vec::new();
+
+        for answer in &self.history {
+            for word in topic_words(&answer.question.text) {
+                match topics.iter_mut().find(|(topic, _)| *topic == word) {
+                    Some((_, count)) => *count += 1,
+                    None => topics.push((word, 1)),
+                }
+            }
+        }
+
+        // Stable sort keeps first-seen order among equally common topics
+        topics.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        topics
+            .into_iter()
+            .take(max)
+            .map(|(topic, _)| topic)
+            .collect()
+    }
+
+    /// Check whether a question mostly repeats one already answered, or says too little to be new
+    ///
+    /// Questions are compared by their keywords: a question sharing at least 60% of
+    /// the combined keywords with an earlier question counts as a repeat, and a
+    /// question without any keywords counts as filler.
+    pub fn is_repetitive(&self, question: &Question) -> bool {
+        if topic_words(&question.text).is_empty() {
+            return true;
+        }
+
+        self.history.iter().any(|answer| {
+            topic_similarity(&question.text, &answer.question.text) >= REPEAT_SIMILARITY
+        })
+    }
+
+    /// Get the questions and answers as a Markdown document
+    pub fn to_qa_markdown(&self) -> String {
+        let mut markdown = String::from("# Interview Questions and Answers\n\n");
+
+        if let Some(hints) = &self.starting_hints {
+            markdown.push_str(&format!("*Starting hints: {}*\n\n", hints));
+        }
+        if let Some(domain) = &self.domain {
+            markdown.push_str(&format!("*Domain: {}*\n\n", domain));
+        }
+
+        for (i, answer) in self.history.iter().enumerate() {
+            markdown.push_str(&format!("## {}. {}\n\n", i + 1, answer.question.text));
+            markdown.push_str(&format!("*Source: {}*\n\n", answer.question.source));
+            if let Some(options) = &answer.question.options {
+                for option in options {
+                    markdown.push_str(&format!("- {}\n", option));
+                }
+                markdown.push('\n');
+            }
+            markdown.push_str(&format!("**Answer:** {}\n\n", answer.response));
+            if !answer.revisions.is_empty() {
+                markdown.push_str(&format!("*Edited {} time(s)*\n\n", answer.revisions.len()));
+            }
+        }
+
+        markdown
+    }
+
+    /// Format the collected information as a readable brief to share with colleagues
+    ///
+    /// Unlike the prompt context, this leaves out attachment contents and skipped questions.
+    pub fn to_brief_markdown(&self) -> String {
+        let mut brief = String::from("# Project Brief\n\n");
+
+        if let Some(hints) = &self.starting_hints {
+            brief.push_str(&format!("## Starting Point\n\n{}\n\n", hints.trim()));
+        }
+
+        let mut overview = Vec::new();
+        if let Some(domain) = &self.domain {
+            overview.push(format!("- **Domain:** {}", domain));
+        }
+        if !matches!(self.persona, Persona::Default) {
+            overview.push(format!("- **Perspective:** {}", self.persona));
+        }
+        for attachment in &self.attachments {
+            overview.push(format!("- **Reference document:** {}", attachment.name));
+        }
+        if !overview.is_empty() {
+            brief.push_str(&format!("## Overview\n\n{}\n\n", overview.join("\n")));
+        }
+
+        if let Some(summary) = &self.summary {
+            brief.push_str(&format!("## Earlier Answers\n\n{}\n\n", summary.trim()));
+        }
+
+        let answered: Vec<&Answer> = self.history.iter().filter(|a| !a.skipped).collect();
+        brief.push_str("## Answers\n\n");
+        if answered.is_empty() {
+            brief.push_str("No questions answered yet.\n\n");
+        }
+        for answer in answered {
+            brief.push_str(&format!("**{}**\n\n", answer.question.text));
+            brief.push_str(&format!("{}\n", answer.response.trim()));
+            if !answer.tags.is_empty() {
+                brief.push_str(&format!("\n*Tags: {}*\n", answer.tags.join(", ")));
+            }
+            brief.push('\n');
+        }
+
+        brief.trim_end().to_string() + "\n"
+    }
+
+    /// Check whether a question's condition is met by the answers so far
+    ///
+    /// Questions without a condition are always asked. A condition is met when the
+    /// question it refers to was answered (not skipped) with the expected answer,
+    /// ignoring case and surrounding whitespace.
+    pub fn condition_met(&self, question: &Question) -> bool {
+        let Some((question_id, expected)) = &question.depends_on else {
+            return true;
+        };
+
+        self.history.iter().any(|answer| {
+            answer.question.id == *question_id
+                && !answer.skipped
+                && answer.response.trim().eq_ignore_ascii_case(expected.trim())
+        })
+    }
+
+    /// Get the history with the responses to sensitive questions redacted
+    pub fn redacted_history(&self) -> Vec<Answer> {
+        self.history.iter().map(Answer::redacted).collect()
+    }
+
+    /// Get the responses to sensitive questions, including earlier revisions
+    pub fn sensitive_responses(&self) -> Vec<&str> {
+        self.history
+            .iter()
+            .filter(|answer| answer.question.sensitive && !answer.skipped)
+            .flat_map(|answer| {
+                std::iter::once(answer.response.as_str()).chain(
+                    answer
+                        .revisions
+                        .iter()
+                        .map(|revision| revision.previous_response.as_str()),
+                )
+            })
+            .collect()
+    }
+
+    /// Get the questions and answers as JSON
+    pub fn to_qa_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(&self.history).map_err(WizardError::serialize("answers"))
+    }
+
+    /// Create a fresh context with the setup of another one: hints, domain, persona and metadata
+    ///
+    /// Answers, attachments and the history cap are not carried over.
+    pub fn setup_from(other: &Context) -> Self {
+        Self {
+            starting_hints: other.starting_hints.clone(),
+            domain: other.domain.clone(),
+            persona: other.persona.clone(),
+            metadata: other.metadata.clone(),
+            ..Self::new()
+        }
+    }
+
+    /// Merge the answers and setup of another context into this one
+    ///
+    /// Answers to a question already in the history replace the earlier answer in place, the
+    /// others are appended in order. Metadata is combined, with the other context's values
+    /// winning, and the longer of the two hints or domains is kept. The history cap is not
+    /// applied to the merged history.
+    pub fn merge(&mut self, other: &Context) {
+        for answer in &other.history {
+            match self
+                .history
+                .iter_mut()
+                .find(|existing| existing.question.id == answer.question.id)
+            {
+                Some(existing) => *existing = answer.clone(),
+                None => self.history.push(answer.clone()),
+            }
+        }
+        self.current_index = self.history.len();
+
+        self.starting_hints = richer(self.starting_hints.take(), &other.starting_hints);
+        self.domain = richer(self.domain.take(), &other.domain);
+        if self.project_name.is_none() {
+            self.project_name = other.project_name.clone();
+        }
+        self.metadata.extend(other.metadata.clone());
+
+        for attachment in &other.attachments {
+            if !self.attachments.iter().any(|a| a.name == attachment.name) {
+                self.attachments.push(attachment.clone());
+            }
+        }
+        if let Some(summary) = &other.summary {
+            self.summary = Some(self.summary.take().unwrap_or_default() + summary);
+        }
+        self.summarized_count += other.summarized_count;
+        self.notes.extend(other.notes.iter().cloned());
+        if self.notes.len() > MAX_NOTES {
+            let excess = self.notes.len() - MAX_NOTES;
+            self.notes.drain(..excess);
+        }
+    }
+
+    /// Add a line to the running notes, keeping only the most recent notes
+    ///
+    /// Only the first non-empty line is kept, without list markers, cut off at 200 characters.
+    pub fn add_note(&mut self, note: &str) -> bool {
+        let Some(line) = note.lines().map(str::trim).find(|line| !line.is_empty()) else {
+            return false;
+        };
+        let line = line.trim_start_matches(['-', '*', '•']).trim();
+        if line.is_empty() {
+            return false;
+        }
+
+        self.notes.push(line.chars().take(MAX_NOTE_CHARS).collect());
+        if self.notes.len() > MAX_NOTES {
+            let excess = self.notes.len() - MAX_NOTES;
+            self.notes.drain(..excess);
+        }
+        true
+    }
+
     /// Add metadata to the context
     pub fn add_metadata(&mut self, key: impl Into<String>, value: impl Into<String>) {
         self.metadata.insert(key.into(), value.into());
     }
 
     /// Get metadata from the context
+    #[allow(
+        dead_code,
+        reason = "lookup for callers outside the interactive wizard"
+    )]
     pub fn get_metadata(&self, key: &str) -> Option<&String> {
         self.metadata.get(key)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_confidence_is_recorded_and_in_context_string() {
+        let mut context = Context::new();
+        context
+            .add_answer_with_confidence(
+                Question::free_text("q1", "Who are the users?"),
+                "Probably nurses",
+                Some(2),
+            )
+            .unwrap();
+
+        assert_eq!(context.history[0].self_confidence, Some(2));
+        assert!(
+            context
+                .get_context_string()
+                .contains("(User's self-reported certainty: 2/5)")
+        );
+    }
+
+    #[test]
+    fn rating_answer_records_number_and_string() {
+        let mut context = Context::new();
+        let question = Question::rating_scale("q1", "How mature is the idea?", 1, 5);
+        context.add_answer(question.clone(), "4").unwrap();
+        context.add_answer(question, "2").unwrap();
+
+        let answer = &context.history[0];
+        assert_eq!(answer.response, "4");
+        assert_eq!(answer.value, Some(AnswerValue::Rating(4)));
+        assert_eq!(answer.rating(), Some(4));
+        assert_eq!(context.average_rating(), Some(3.0));
+    }
+
+    #[test]
+    fn attachment_text_is_in_context_string_and_truncated() {
+        let mut context = Context::new();
+        context.attach(Attachment::new("brief.md", "Build a triage chatbot."));
+        context.attach(Attachment::new(
+            "prd.md",
+            "x".repeat(MAX_ATTACHMENT_CHARS + 100),
+        ));
+
+        let context_string = context.get_context_string();
+
+        assert!(context_string.contains("Reference Material:"));
+        assert!(context_string.contains("--- brief.md ---\nBuild a triage chatbot.\n"));
+        let kept = format!("{}\n[... truncated ...]", "x".repeat(MAX_ATTACHMENT_CHARS));
+        assert!(context_string.contains(&format!("--- prd.md ---\n{}\n", kept)));
+        assert!(!context_string.contains(&"x".repeat(MAX_ATTACHMENT_CHARS + 1)));
+    }
+
+    #[test]
+    fn empty_context_string_says_this_is_the_first_question() {
+        assert_eq!(
+            Context::new().get_context_string(),
+            "No prior context; this is the first question.\n"
+        );
+    }
+
+    #[test]
+    fn project_name_or_notes_make_the_context_non_empty() {
+        let mut named = Context::new();
+        named.project_name = Some("Triage Bot".to_string());
+        assert_eq!(named.get_context_string(), "Project name: Triage Bot\n\n");
+
+        let mut noted = Context::new();
+        noted.notes.push("Targets small clinics".to_string());
+        assert!(!noted.is_empty());
+        assert!(
+            noted
+                .get_context_string()
+                .contains("- Targets small clinics\n")
+        );
+    }
+
+    #[test]
+    fn tagged_answer_is_found_by_tag() {
+        let mut context = Context::new();
+        context
+            .add_answer(
+                Question::free_text("q1", "How is data stored?"),
+                "Encrypted",
+            )
+            .unwrap();
+        context.tag_last_answer(" Security ");
+        context
+            .add_answer(Question::free_text("q2", "Who are the users?"), "Nurses")
+            .unwrap();
+
+        let tagged = context.answers_with_tag("security");
+
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].question.id, "q1");
+        assert!(context.answers_with_tag("ux").is_empty());
+        assert!(context.get_context_string().contains("(Tags: security)"));
+    }
+
+    #[test]
+    fn each_edit_records_a_revision() {
+        let mut context = Context::new();
+        context
+            .add_answer(Question::free_text("q1", "Who are the users?"), "Nurses")
+            .unwrap();
+
+        assert!(context.edit_answer(0, "Nurses and doctors").unwrap());
+        assert!(context.edit_answer(0, "Clinical staff").unwrap());
+
+        let answer = &context.history[0];
+        assert_eq!(answer.response, "Clinical staff");
+        let previous: Vec<&str> = answer
+            .revisions
+            .iter()
+            .map(|revision| revision.previous_response.as_str())
+            .collect();
+        assert_eq!(previous, ["Nurses", "Nurses and doctors"]);
+    }
+
+    /// Context capped at two answers, with two answered
+    fn capped_context(policy: HistoryCapPolicy) -> Context {
+        let mut context = Context::new();
+        context.history_cap = Some(HistoryCap {
+            max_answers: 2,
+            policy,
+        });
+        for (id, text) in [("q1", "Who are the users?"), ("q2", "What is the goal?")] {
+            context
+                .add_answer(Question::free_text(id, text), "An answer")
+                .unwrap();
+        }
+        context
+    }
+
+    #[test]
+    fn refuse_cap_rejects_more_answers() {
+        let mut context = capped_context(HistoryCapPolicy::Refuse);
+
+        let result = context.add_answer(Question::free_text("q3", "Which platforms?"), "Web");
+
+        assert!(result.is_err());
+        assert_eq!(context.history.len(), 2);
+        assert_eq!(context.answer_count(), 2);
+    }
+
+    #[test]
+    fn summarize_cap_folds_the_oldest_answer_into_the_summary() {
+        let mut context = capped_context(HistoryCapPolicy::Summarize);
+
+        context
+            .add_answer(Question::free_text("q3", "Which platforms?"), "Web")
+            .unwrap();
+
+        assert_eq!(context.history.len(), 2);
+        assert_eq!(context.history[0].question.id, "q2");
+        assert_eq!(context.answer_count(), 3);
+        assert!(
+            context
+                .summary
+                .as_deref()
+                .unwrap()
+                .contains("Who are the users?")
+        );
+    }
+
+    #[test]
+    fn metadata_is_ordered_by_key() {
+        let mut first = Context::new();
+        let mut second = Context::new();
+        for (key, value) in [
+            ("region", "EU"),
+            ("compliance", "GDPR"),
+            ("audience", "Clinics"),
+        ] {
+            first.add_metadata(key, value);
+        }
+        for (key, value) in [
+            ("audience", "Clinics"),
+            ("region", "EU"),
+            ("compliance", "GDPR"),
+        ] {
+            second.add_metadata(key, value);
+        }
+
+        let keys: Vec<&str> = first.metadata.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["audience", "compliance", "region"]);
+        assert_eq!(
+            serde_json::to_string(&first.metadata).unwrap(),
+            r#"{"audience":"Clinics","compliance":"GDPR","region":"EU"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+    }
+
+    #[test]
+    fn setup_from_another_session_has_an_empty_history() {
+        let mut previous = Context::new();
+        previous.starting_hints = Some("A triage assistant".to_string());
+        previous.domain = Some("Healthcare".to_string());
+        previous.persona = Persona::ComplianceOfficer;
+        previous.add_metadata("compliance", "HIPAA");
+        previous
+            .add_answer(Question::free_text("q1", "Who are the users?"), "Nurses")
+            .unwrap();
+
+        let context = Context::setup_from(&previous);
+
+        assert_eq!(
+            context.starting_hints.as_deref(),
+            Some("A triage assistant")
+        );
+        assert_eq!(context.domain.as_deref(), Some("Healthcare"));
+        assert!(matches!(context.persona, Persona::ComplianceOfficer));
+        assert_eq!(
+            context.get_metadata("compliance").map(String::as_str),
+            Some("HIPAA")
+        );
+        assert!(context.history.is_empty());
+        assert_eq!(context.current_index, 0);
+    }
+
+    #[test]
+    fn brief_lists_setup_and_answers() {
+        let mut context = Context::new();
+        context.starting_hints = Some("A triage assistant for a rural clinic".to_string());
+        context.domain = Some("Healthcare".to_string());
+        context.persona = Persona::ComplianceOfficer;
+        context
+            .add_answer(Question::free_text("q1", "Who are the users?"), "Nurses")
+            .unwrap();
+        context.tag_last_answer("users");
+        context
+            .skip_question(Question::free_text("q2", "What is the budget?"))
+            .unwrap();
+
+        assert_eq!(
+            context.to_brief_markdown(),
+            "# Project Brief\n\n\
+             ## Starting Point\n\nA triage assistant for a rural clinic\n\n\
+             ## Overview\n\n- **Domain:** Healthcare\n- **Perspective:** Compliance Officer\n\n\
+             ## Answers\n\n**Who are the users?**\n\nNurses\n\n*Tags: users*\n"
+        );
+    }
+
+    #[test]
+    fn reference_front_matter_domain_populates_the_context_domain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("brief.md");
+        std::fs::write(
+            &path,
+            "---\ntitle: Triage Bot\ndomain: Healthcare\naudience: Night-shift nurses\ngoals:\n  - Shorter waits\n  - Fewer missed cases\nowner: \"Clinic IT\"\n---\n\nBuild a triage chatbot.\n",
+        )
+        .unwrap();
+        let mut context = Context::new();
+
+        context.attach_file(&path).unwrap();
+
+        assert_eq!(context.domain.as_deref(), Some("Healthcare"));
+        let attachment = &context.attachments[0];
+        assert_eq!(attachment.content, "Build a triage chatbot.\n");
+        let front_matter = attachment.front_matter.as_ref().unwrap();
+        assert_eq!(
+            front_matter.goals,
+            vec!["Shorter waits", "Fewer missed cases"]
+        );
+        assert_eq!(front_matter.other["owner"], "Clinic IT");
+        let hints = context.starting_hints.as_deref().unwrap();
+        assert!(hints.starts_with("From brief.md:\n"));
+        assert!(hints.contains("Triage Bot") && hints.contains("Night-shift nurses"));
+        assert!(!hints.contains("Healthcare"));
+    }
+
+    #[test]
+    fn chosen_domain_is_kept_over_the_front_matter() {
+        let (front_matter, body) =
+            FrontMatter::split("---\ndomain: Finance\ngoals: [Audit, Reporting]\n---\nBody")
+                .unwrap();
+        let mut context = Context::with_domain("Healthcare");
+
+        context.attach(Attachment {
+            name: "brief.md".to_string(),
+            content: body.to_string(),
+            front_matter: Some(front_matter),
+        });
+
+        assert_eq!(context.domain.as_deref(), Some("Healthcare"));
+        assert_eq!(
+            context.attachments[0].front_matter.as_ref().unwrap().goals,
+            vec!["Audit", "Reporting"]
+        );
+        assert!(FrontMatter::split("No front matter here").is_none());
+    }
+}
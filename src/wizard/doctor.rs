@@ -0,0 +1,323 @@
+//! Doctor module for the LLM-powered project definition wizard.
+//!
+//! This module diagnoses common setup problems, such as a missing API key or
+//! an unreadable configuration file. Each check reports whether it passed,
+//! with a hint on how to fix it if it did not.
+
+use std::fmt;
+use std::path::Path;
+
+use super::config::Config;
+use super::llm::{API_BASE_URL, LlmClient};
+use super::template::TemplateRepository;
+
+/// Environment variable holding the API key
+pub const API_KEY_VAR: &str = "OPENROUTER_API_KEY";
+
+/// Prefix of OpenRouter API keys
+const API_KEY_PREFIX: &str = "sk-or-";
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    /// Everything is in order
+    Pass,
+    /// Probably works, but looks unusual
+    Warn,
+    /// Will not work as configured
+    Fail,
+}
+
+impl CheckStatus {
+    /// Get the symbol shown in front of the check
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Pass => "✅",
+            Self::Warn => "⚠️ ",
+            Self::Fail => "❌",
+        }
+    }
+}
+
+/// Result of a single check, with a hint on how to fix it if it did not pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    /// What was checked
+    pub name: &'static str,
+    /// Whether the check passed
+    pub status: CheckStatus,
+    /// What was found
+    pub detail: String,
+    /// How to fix the problem, if there is one
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    /// Create a passing result
+    pub fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    /// Create a warning with a hint
+    pub fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    /// Create a failing result with a hint
+    pub fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.status.symbol(), self.name, self.detail)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n   → {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that an API key is set and looks like one
+pub fn check_api_key(api_key: Option<&str>) -> CheckResult {
+    const NAME: &str = "API key";
+
+    let Some(api_key) = api_key else {
+        return CheckResult::fail(
+            NAME,
+            format!("{} is not set", API_KEY_VAR),
+            format!(
+                "Set {} in your environment or in a .env file (see --env-file)",
+                API_KEY_VAR
+            ),
+        );
+    };
+
+    if api_key.trim().is_empty() {
+        return CheckResult::fail(
+            NAME,
+            format!("{} is empty", API_KEY_VAR),
+            "Copy your key from https://openrouter.ai/keys",
+        );
+    }
+    if api_key.trim() != api_key || api_key.contains(char::is_whitespace) {
+        return CheckResult::fail(
+            NAME,
+            "the key contains whitespace",
+            "Remove spaces and line breaks around the key",
+        );
+    }
+    if api_key.starts_with(['"', '\'']) || api_key.ends_with(['"', '\'']) {
+        return CheckResult::fail(
+            NAME,
+            "the key is wrapped in quotes",
+            "Remove the quotes around the key",
+        );
+    }
+    if !api_key.starts_with(API_KEY_PREFIX) {
+        return CheckResult::warn(
+            NAME,
+            format!("the key does not start with \"{}\"", API_KEY_PREFIX),
+            "Make sure it is an OpenRouter key, not one from another provider",
+        );
+    }
+
+    CheckResult::pass(NAME, format!("{} is set", API_KEY_VAR))
+}
+
+/// Check that the configuration file, if there is one, can be loaded
+pub fn check_config_file(path: &Path) -> CheckResult {
+    const NAME: &str = "Configuration file";
+
+    if !path.exists() {
+        return CheckResult::pass(
+            NAME,
+            format!("{} not found, using the defaults", path.display()),
+        );
+    }
+
+    match Config::load_from_file(path) {
+        Ok(_) => CheckResult::pass(NAME, format!("{} is valid", path.display())),
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("{:#}", e),
+            format!(
+                "Fix the file, or move it away to go back to the defaults ({})",
+                path.display()
+            ),
+        ),
+    }
+}
+
+/// Check that the templates load and their questions are well-formed
+pub fn check_templates(repo: &TemplateRepository) -> CheckResult {
+    const NAME: &str = "Templates";
+
+    let templates = repo.get_all_templates();
+    let mut problems = Vec::new();
+    for template in templates {
+        for question in &template.initial_questions {
+            if let Err(e) = question.validate() {
+                problems.push(format!("{}: {}", template.name, e));
+            }
+            if let Some((question_id, _)) = &question.depends_on
+                && !template
+                    .initial_questions
+                    .iter()
+                    .any(|other| other.id == *question_id)
+            {
+                problems.push(format!(
+                    "{}: question '{}' depends on unknown question '{}'",
+                    template.name, question.id, question_id
+                ));
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return CheckResult::fail(
+            NAME,
+            problems.join("; "),
+            "Fix the listed questions in the template",
+        );
+    }
+
+    CheckResult::pass(
+        NAME,
+        format!(
+            "{} template(s) and {} domain(s) loaded",
+            templates.len(),
+            repo.get_all_domains().len()
+        ),
+    )
+}
+
+/// Check that the API can be reached by listing the available models
+pub async fn check_api(llm_client: &LlmClient) -> CheckResult {
+    const NAME: &str = "API";
+
+    match llm_client.list_models().await {
+        Ok(models) => CheckResult::pass(
+            NAME,
+            format!(
+                "{} reachable, {} model(s) available",
+                API_BASE_URL,
+                models.len()
+            ),
+        ),
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("{} could not be reached: {}", API_BASE_URL, e),
+            "Check your network connection, proxy settings (HTTPS_PROXY) and firewall",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::llm::scripted::Reply;
+    use crate::wizard::question::Question;
+    use crate::wizard::template::Template;
+
+    #[test]
+    fn api_key_problems_are_told_apart() {
+        let status = |api_key| check_api_key(api_key).status;
+
+        assert_eq!(status(None), CheckStatus::Fail);
+        assert_eq!(status(Some("  ")), CheckStatus::Fail);
+        assert_eq!(status(Some("sk-or-v1-abc\n")), CheckStatus::Fail);
+        assert_eq!(status(Some("\"sk-or-v1-abc\"")), CheckStatus::Fail);
+        assert_eq!(status(Some("sk-proj-abc")), CheckStatus::Warn);
+        assert_eq!(status(Some("sk-or-v1-abc")), CheckStatus::Pass);
+        assert!(check_api_key(None).hint.unwrap().contains(API_KEY_VAR));
+    }
+
+    #[test]
+    fn config_file_is_optional_but_must_be_valid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+
+        let missing = check_config_file(&path);
+        std::fs::write(&path, serde_json::to_string(&Config::default()).unwrap()).unwrap();
+        let valid = check_config_file(&path);
+        std::fs::write(&path, "{ \"domains\": ").unwrap();
+        let invalid = check_config_file(&path);
+
+        assert_eq!(missing.status, CheckStatus::Pass);
+        assert!(missing.detail.contains("using the defaults"));
+        assert_eq!(valid.status, CheckStatus::Pass);
+        assert_eq!(invalid.status, CheckStatus::Fail);
+        assert!(invalid.hint.is_some());
+    }
+
+    #[test]
+    fn template_with_an_unknown_dependency_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, serde_json::to_string(&Config::default()).unwrap()).unwrap();
+        let mut repo = TemplateRepository::with_config(&path).unwrap();
+        let mut template = Template::new("Clinic Assistant", "", "Healthcare".into(), "");
+        template.add_question(Question::yes_no("patients", "Do you handle patient data?"));
+        repo.add_template(template.clone());
+
+        let valid = check_templates(&repo);
+        template.add_question(
+            Question::free_text("hipaa", "How do you meet HIPAA?").with_depends_on("phi", "yes"),
+        );
+        repo.add_template(template);
+        let broken = check_templates(&repo);
+
+        assert_eq!(valid.status, CheckStatus::Pass);
+        assert!(
+            valid.detail.starts_with("1 template(s)"),
+            "{}",
+            valid.detail
+        );
+        assert_eq!(broken.status, CheckStatus::Fail);
+        assert!(
+            broken.detail.contains("depends on unknown question 'phi'"),
+            "{}",
+            broken.detail
+        );
+    }
+
+    #[tokio::test]
+    async fn api_check_reports_the_models_or_the_failure() {
+        let models = serde_json::json!({
+            "data": [{ "id": "openai/gpt-4", "name": "GPT-4" }, { "id": "google/gemma-3-27b-it", "name": "Gemma 3" }]
+        });
+        let (llm_client, _) = LlmClient::scripted([
+            Reply::Raw(200, models.to_string()),
+            Reply::Raw(503, "Service Unavailable".to_string()),
+        ]);
+
+        let reachable = check_api(&llm_client).await;
+        let unreachable = check_api(&llm_client).await;
+
+        assert_eq!(reachable.status, CheckStatus::Pass);
+        assert!(
+            reachable.detail.ends_with("2 model(s) available"),
+            "{}",
+            reachable.detail
+        );
+        assert_eq!(unreachable.status, CheckStatus::Fail);
+        assert!(unreachable.detail.contains("503"), "{}", unreachable.detail);
+    }
+}
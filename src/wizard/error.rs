@@ -0,0 +1,153 @@
+//! Error module for the LLM-powered project definition wizard.
+//!
+//! This module defines the error returned by the library: the LLM client, the
+//! session manager, the output generator and the modules they build on. Each
+//! variant names a category of failure, so callers can match on it directly;
+//! the underlying network, JSON and file errors are kept as the source.
+
+use std::path::PathBuf;
+
+/// Result of a wizard operation
+pub type Result<T> = std::result::Result<T, WizardError>;
+
+/// Category of a wizard failure
+#[derive(Debug, thiserror::Error)]
+pub enum WizardError {
+    /// The LLM API could not be reached, or the connection failed during the response
+    #[error("Failed to reach the LLM API: {0}")]
+    Network(#[from] reqwest::Error),
+    /// The LLM API answered with an error
+    #[error("{0}")]
+    Api(String),
+    /// The model can't serve the request right now, but another may
+    #[error("Model '{model}' is unavailable: {reason}")]
+    ModelUnavailable {
+        /// The model that was unavailable
+        model: String,
+        /// The reason reported by the API
+        reason: String,
+    },
+    /// A response or file is not valid JSON, or doesn't have the expected structure
+    #[error("Failed to parse {what} as JSON: {source}")]
+    Json {
+        /// What was being parsed
+        what: String,
+        /// The JSON error
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A value could not be written as JSON
+    #[error("Failed to serialize {what} as JSON: {source}")]
+    Serialize {
+        /// What was being written
+        what: String,
+        /// The JSON error
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A response from the LLM could not be understood
+    #[error("{0}")]
+    Parse(String),
+    /// A file could not be read or written
+    #[error("Failed to {action} {}: {source}", path.display())]
+    Io {
+        /// What was being done, e.g. `read`
+        action: &'static str,
+        /// The file or directory
+        path: PathBuf,
+        /// The I/O error
+        #[source]
+        source: std::io::Error,
+    },
+    /// The operation is not possible in the session's current state
+    #[error("{0}")]
+    State(String),
+    /// The session file is open in another process
+    #[error("{0}")]
+    Locked(String),
+    /// The PDF document could not be rendered
+    #[cfg(feature = "pdf")]
+    #[error("Failed to render the PDF: {0}")]
+    Pdf(#[from] printpdf::Error),
+    /// A setting, template or answer given to the wizard is not valid
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl WizardError {
+    /// Build the error for JSON that could not be parsed, for use with `map_err`
+    pub fn json(what: impl Into<String>) -> impl FnOnce(serde_json::Error) -> Self {
+        let what = what.into();
+        move |source| Self::Json { what, source }
+    }
+
+    /// Build the error for a value that could not be written as JSON, for use with `map_err`
+    pub fn serialize(what: impl Into<String>) -> impl FnOnce(serde_json::Error) -> Self {
+        let what = what.into();
+        move |source| Self::Serialize { what, source }
+    }
+
+    /// Build the error for a failed file operation, for use with `map_err`
+    pub fn io(
+        action: &'static str,
+        path: impl Into<PathBuf>,
+    ) -> impl FnOnce(std::io::Error) -> Self {
+        let path = path.into();
+        move |source| Self::Io {
+            action,
+            path,
+            source,
+        }
+    }
+
+    /// Check whether the error is a response the model could be asked to correct
+    pub fn is_parse_error(&self) -> bool {
+        matches!(self, Self::Parse(_) | Self::Json { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::llm::scripted::Reply;
+    use crate::wizard::session::{Session, SessionManager};
+    use crate::wizard::{Context, LlmClient};
+
+    #[tokio::test]
+    async fn failures_have_distinct_variants() {
+        let (client, _) = LlmClient::scripted([
+            Reply::Raw(500, "Internal error".to_string()),
+            Reply::content("not json"),
+            Reply::content("still not json"),
+        ]);
+
+        let api = client.generate_question(&Context::new(), 0.0).await;
+        let parse = client.generate_question(&Context::new(), 0.0).await;
+        let mut session_manager = SessionManager::new(Session::new(), client);
+        let state = session_manager.answer_question("Nurses");
+
+        assert!(matches!(api, Err(WizardError::Api(_))), "{:?}", api);
+        assert!(
+            matches!(parse, Err(WizardError::Json { .. })),
+            "{:?}",
+            parse
+        );
+        assert!(matches!(state, Err(WizardError::State(_))), "{:?}", state);
+    }
+
+    #[test]
+    fn locked_session_has_its_own_variant() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        Session::new().save_to_file(&path).unwrap();
+        let _open = Session::open(&path).unwrap();
+
+        let locked = Session::open(&path);
+
+        assert!(
+            matches!(locked, Err(WizardError::Locked(_))),
+            "{:?}",
+            locked.map(|_| ())
+        );
+    }
+}
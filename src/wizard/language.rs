@@ -0,0 +1,51 @@
+//! Language module for the LLM-powered project definition wizard.
+//!
+//! This module provides a lightweight check that the generated project
+//! definition is written in the language that was requested.
+
+use whatlang::Lang;
+
+/// Outcome of checking the language of a generated document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageCheck {
+    /// The document is written in the requested language
+    Match,
+    /// The document appears to be written in another language
+    Mismatch {
+        /// English name of the detected language
+        detected: &'static str,
+    },
+    /// The requested language is unknown or the detection was not reliable
+    Inconclusive,
+}
+
+/// Look up a language by its English name, native name or three-letter code
+pub fn find_language(name: &str) -> Option<Lang> {
+    let name = name.trim();
+    Lang::all().iter().copied().find(|lang| {
+        lang.eng_name().eq_ignore_ascii_case(name)
+            || lang.name().to_lowercase() == name.to_lowercase()
+            || lang.code().eq_ignore_ascii_case(name)
+    })
+}
+
+/// Check whether the markdown document is written in the requested language
+pub fn check_language(markdown: &str, requested: &str) -> LanguageCheck {
+    let Some(requested) = find_language(requested) else {
+        return LanguageCheck::Inconclusive;
+    };
+
+    // Section titles are kept in English, so only look at the body text
+    let body: Vec<&str> = markdown
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect();
+
+    match whatlang::detect(&body.join("\n")) {
+        Some(info) if info.lang() == requested => LanguageCheck::Match,
+        Some(info) if info.is_reliable() => LanguageCheck::Mismatch {
+            detected: info.lang().eng_name(),
+        },
+        _ => LanguageCheck::Inconclusive,
+    }
+}
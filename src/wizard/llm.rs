@@ -3,20 +3,34 @@
 //! This module handles the communication with the LLM API for generating
 //! questions and project definitions.
 
-use anyhow::Result;
-use chrono::{DateTime, Local};
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::fs;
-use std::io::Write;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
-use super::{Context, Question};
+use super::error::Result;
+use super::progress::ProgressMode;
+use super::transcript::{Transcript, TranscriptEntry};
+use super::{Context, Question, WizardError};
 use crate::wizard::context::Persona;
 use crate::wizard::question::QuestionType;
 
+/// Maximum number of covered topics listed in the question prompt
+const MAX_COVERED_TOPICS: usize = 20;
+
+/// Number of times a question about an avoided topic is asked for again
+const MAX_AVOIDED_TOPIC_RETRIES: usize = 2;
+
+/// Base URL of the OpenRouter API
+pub const API_BASE_URL: &str = "https://openrouter.ai/api/v1";
+
 /// Configuration for the LLM client
+///
+/// Settings missing when it is read, e.g. from a profile, take their default values.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LlmConfig {
     /// The model to use for chat completions
     pub model: String,
@@ -26,6 +40,90 @@ pub struct LlmConfig {
     pub max_tokens: u16,
     /// The API key for the LLM service
     pub api_key: Option<String>,
+    /// Optional temperature schedule for question generation
+    #[serde(default)]
+    pub temperature_schedule: Option<TemperatureSchedule>,
+    /// Models to try, in order, when the primary model is unavailable or rate-limited
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+    /// Maximum size of a response body in bytes
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
+    /// Sampling seed sent with every request for reproducible output
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Whether to tell the model which topics were already covered so it doesn't re-ask
+    #[serde(default)]
+    pub avoid_covered_topics: bool,
+    /// Language to write the project definition in
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Voice of the project definition document, the default professional tone if not set
+    #[serde(default)]
+    pub doc_style: Option<DocStyle>,
+    /// Order in which questions cover the project, left to the model if not set
+    #[serde(default)]
+    pub flow: Option<InterviewFlow>,
+    /// Topics the user never wants to be asked about
+    #[serde(default)]
+    pub avoid_topics: Vec<String>,
+    /// Maximum size of the conversation context in the definition prompt, in characters
+    #[serde(default = "default_max_definition_context_chars")]
+    pub max_definition_context_chars: usize,
+    /// Maximum size of a question prompt, in characters, before the context in it is trimmed
+    #[serde(default = "default_max_question_prompt_chars")]
+    pub max_question_prompt_chars: usize,
+    /// Whether to repair malformed questions (e.g. ask a multiple choice without options as free text)
+    #[serde(default)]
+    pub repair_questions: bool,
+    /// Message sent after a response that isn't the expected JSON, asking the model to correct it
+    #[serde(default = "default_json_retry_instruction")]
+    pub json_retry_instruction: String,
+    /// Maximum number of times the model is asked to correct a response that isn't the expected JSON
+    #[serde(default = "default_json_retry_max")]
+    pub json_retry_max: u32,
+    /// Whether to leave the per-section confidence scores out of the project definition
+    #[serde(default)]
+    pub omit_confidence: bool,
+    /// Output token limits of models, taking precedence over the built-in table
+    #[serde(default)]
+    pub output_limits: BTreeMap<String, u16>,
+    /// Sequences at which the model stops generating, sent with every request if set
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+    /// Header carrying the idempotency key of each request, for providers that support one
+    #[serde(default)]
+    pub idempotency_header: Option<String>,
+    /// Times a request is sent again when no response arrived, e.g. after a dropped connection
+    #[serde(default)]
+    pub network_retries: u32,
+}
+
+/// Default message asking the model to correct a response that isn't the expected JSON
+fn default_json_retry_instruction() -> String {
+    "Your previous response could not be used because it was not the JSON object requested. \
+    Reply again with only that JSON object, with no explanation or markdown formatting."
+        .to_string()
+}
+
+/// Default number of times the model is asked to correct its JSON
+fn default_json_retry_max() -> u32 {
+    1
+}
+
+/// Default maximum size of the context in the definition prompt (roughly 15k tokens)
+fn default_max_definition_context_chars() -> usize {
+    60_000
+}
+
+/// Default maximum size of a question prompt (roughly 15k tokens)
+fn default_max_question_prompt_chars() -> usize {
+    60_000
+}
+
+/// Default maximum size of a response body (4 MiB)
+fn default_max_response_bytes() -> usize {
+    4 * 1024 * 1024
 }
 
 impl Default for LlmConfig {
@@ -35,10 +133,492 @@ impl Default for LlmConfig {
             temperature: 1.0,
             max_tokens: 4096,
             api_key: None,
+            temperature_schedule: None,
+            fallback_models: Vec::new(),
+            max_response_bytes: default_max_response_bytes(),
+            seed: None,
+            avoid_covered_topics: false,
+            language: None,
+            doc_style: None,
+            flow: None,
+            avoid_topics: Vec::new(),
+            max_definition_context_chars: default_max_definition_context_chars(),
+            max_question_prompt_chars: default_max_question_prompt_chars(),
+            repair_questions: false,
+            json_retry_instruction: default_json_retry_instruction(),
+            json_retry_max: default_json_retry_max(),
+            omit_confidence: false,
+            output_limits: BTreeMap::new(),
+            stop: None,
+            idempotency_header: None,
+            network_retries: 0,
+        }
+    }
+}
+
+impl LlmConfig {
+    /// Get the primary model followed by the fallback models
+    pub fn models(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.model).chain(self.fallback_models.iter())
+    }
+
+    /// Get the temperature to use for question generation at the given progress (0.0-1.0)
+    pub fn question_temperature(&self, progress: f32) -> f32 {
+        match &self.temperature_schedule {
+            Some(schedule) => schedule.temperature_at(progress),
+            None => self.temperature,
+        }
+    }
+
+    /// Get the maximum number of tokens a model can generate, if known
+    pub fn output_limit(&self, model: &str) -> Option<u16> {
+        // Variants such as `:free` share the limit of the base model
+        let base = model.split_once(':').map_or(model, |(base, _)| base);
+        [model, base].iter().find_map(|id| {
+            self.output_limits.get(*id).copied().or_else(|| {
+                MODEL_OUTPUT_LIMITS
+                    .iter()
+                    .find(|(known, _)| known == id)
+                    .map(|(_, limit)| *limit)
+            })
+        })
+    }
+
+    /// Get the `max_tokens` to request from a model, clamped to its output limit
+    pub fn max_tokens_for(&self, model: &str) -> u16 {
+        match self.output_limit(model) {
+            Some(limit) => self.max_tokens.min(limit),
+            None => self.max_tokens,
+        }
+    }
+
+    /// Get the settings that are recorded on a session
+    pub fn model_settings(&self) -> ModelSettings {
+        ModelSettings {
+            model: self.model.clone(),
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+        }
+    }
+}
+
+/// Known output token limits of models, for models whose limit may be below `max_tokens`
+const MODEL_OUTPUT_LIMITS: &[(&str, u16)] = &[
+    ("openai/gpt-3.5-turbo", 4096),
+    ("openai/gpt-4", 4096),
+    ("openai/gpt-4-turbo", 4096),
+    ("anthropic/claude-3-haiku", 4096),
+    ("anthropic/claude-3-opus", 4096),
+    ("anthropic/claude-3.5-sonnet", 8192),
+    ("google/gemma-3-27b-it", 8192),
+];
+
+/// Model settings recorded on a session so that continuing it keeps using the same model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelSettings {
+    /// The model used for chat completions
+    pub model: String,
+    /// The temperature parameter for the LLM
+    pub temperature: f32,
+    /// The maximum number of tokens to generate
+    pub max_tokens: u16,
+}
+
+/// Voice of the generated project definition document
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DocStyle {
+    /// Terse engineering specification
+    Concise,
+    /// Flowing business brief
+    Narrative,
+    /// Mostly bullet points
+    Bulleted,
+}
+
+impl DocStyle {
+    /// Get the tone instruction added to the definition prompt
+    pub fn instruction(&self) -> &'static str {
+        match self {
+            Self::Concise => {
+                "Write like a terse engineering specification: short, precise statements with no marketing language or filler."
+            }
+            Self::Narrative => {
+                "Write like a narrative business brief: connected prose paragraphs that explain the reasoning, using lists only where they help."
+            }
+            Self::Bulleted => {
+                "Write almost entirely in bullet points: one idea per bullet, nested bullets for detail, and as little prose as possible."
+            }
+        }
+    }
+}
+
+/// Order in which the interview covers the project
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum InterviewFlow {
+    /// Touch on every area of the definition before going into detail
+    Breadth,
+    /// Explore each topic in depth before moving on
+    Depth,
+}
+
+impl InterviewFlow {
+    /// Get the strategy instruction added to the question prompt
+    pub fn instruction(&self) -> String {
+        match self {
+            Self::Breadth => {
+                let areas: Vec<&str> = DEFINITION_SECTIONS
+                    .iter()
+                    .map(|section| section.title)
+                    .collect();
+                format!(
+                    "7. Interview breadth-first: cover every area of the project definition at a high level before going into detail on any of them. \
+                    Ask about the area the conversation has said least about so far. The areas are: {}.",
+                    areas.join("; ")
+                )
+            }
+            Self::Depth => "7. Interview depth-first: stay with the current topic and ask follow-up questions that go deeper into the latest answers, \
+                moving on to a new area only once the current one is understood in detail."
+                .to_string(),
+        }
+    }
+}
+
+/// Temperature that moves linearly from `start` to `end` as the interview progresses
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TemperatureSchedule {
+    /// Temperature for the first question
+    pub start: f32,
+    /// Temperature once the maximum number of questions is reached
+    pub end: f32,
+}
+
+/// Error for a temperature schedule that isn't two numbers in the form `start:end`
+fn invalid_schedule(value: &str) -> WizardError {
+    WizardError::Invalid(format!(
+        "Temperature schedule must be in the form start:end, got '{}'",
+        value
+    ))
+}
+
+impl TemperatureSchedule {
+    /// Parse a schedule in the form `start:end` (e.g. `1.2:0.4`)
+    pub fn parse(value: &str) -> Result<Self> {
+        let (start, end) = value
+            .split_once(':')
+            .ok_or_else(|| invalid_schedule(value))?;
+
+        Ok(Self {
+            start: start.trim().parse().map_err(|_| invalid_schedule(value))?,
+            end: end.trim().parse().map_err(|_| invalid_schedule(value))?,
+        })
+    }
+
+    /// Get the interpolated temperature at the given progress (0.0-1.0)
+    pub fn temperature_at(&self, progress: f32) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+        self.start + (self.end - self.start) * progress
+    }
+}
+
+/// Section of the project definition document
+#[derive(Debug, Clone, Copy)]
+pub struct DefinitionSection {
+    /// Title of the section
+    pub title: &'static str,
+    /// What the section should contain
+    pub guidance: &'static str,
+    /// Whether the section carries a confidence score
+    pub scored: bool,
+}
+
+/// Sections of the project definition document, in order
+pub const DEFINITION_SECTIONS: &[DefinitionSection] = &[
+    DefinitionSection {
+        title: "Project Name and Summary",
+        guidance: "*Content...*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: "Use Cases and Goals",
+        guidance: "*Content... (include specific user scenarios if possible)*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: "Target User Profile(s)",
+        guidance: "*Content...*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: "Required Inputs and Expected Outputs",
+        guidance: "*Content... (Detail what the user provides to the system and what the system returns)*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: "Functional Components/Modules",
+        guidance: "*Content...*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: "Prompt Engineering Strategy",
+        guidance: "*Content...*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: "Dataset Needs and Sources",
+        guidance: "*Content...*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: "Evaluation Metrics and Success Criteria",
+        guidance: "*Content... (How will we know the project is successful?)*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: "Scalability and Deployment",
+        guidance: "*Content... (Initial thoughts on technical architecture and scaling)*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: "Ethical and Bias Considerations",
+        guidance: "*Content...*",
+        scored: true,
+    },
+    DefinitionSection {
+        title: OPEN_QUESTIONS_SECTION,
+        guidance: "*Based on the context, list the key pieces of information that are still needed to complete this project definition.*",
+        scored: false,
+    },
+];
+
+/// Title of the unscored section listing what is still unknown about the project
+pub const OPEN_QUESTIONS_SECTION: &str = "Open Questions and Missing Information";
+
+/// Groups of sections requested separately in split generation, as ranges into `DEFINITION_SECTIONS`
+pub const DEFINITION_SECTION_GROUPS: &[std::ops::Range<usize>] = &[0..5, 5..11];
+
+/// Render the numbered document structure for a range of definition sections
+///
+/// Without `scored`, the confidence line of each section is left out.
+fn render_section_structure(range: std::ops::Range<usize>, scored: bool) -> String {
+    DEFINITION_SECTIONS[range.clone()]
+        .iter()
+        .zip(range)
+        .map(|(section, index)| {
+            let confidence = if !scored {
+                String::new()
+            } else if section.scored {
+                "- **Confidence: [1-5]/5** (Reason: ...)\n".to_string()
+            } else {
+                "- **Confidence: N/A**\n".to_string()
+            };
+            format!(
+                "## {}. {}\n{}- {}\n",
+                index + 1,
+                section.title,
+                confidence,
+                section.guidance
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Projected confidence for a section of the project definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectionConfidence {
+    /// Title of the section
+    pub section: String,
+    /// Projected confidence from 1 to 5
+    pub confidence: u8,
+}
+
+/// Re-evaluation of a written project definition
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DefinitionReview {
+    /// Confidence of each section, judged from its text
+    pub sections: Vec<SectionConfidence>,
+    /// Information the definition is still missing
+    #[serde(default)]
+    pub open_questions: Vec<String>,
+}
+
+/// Judgement of whether an answer says enough to build on
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnswerAssessment {
+    /// Whether the answer is specific enough to move on
+    pub sufficient: bool,
+    /// What the answer leaves unclear, if it isn't sufficient
+    #[serde(default)]
+    pub missing: Option<String>,
+}
+
+impl AnswerAssessment {
+    /// Get what needs clarifying, if the answer isn't sufficient
+    pub fn gap(self) -> Option<String> {
+        if self.sufficient {
+            return None;
+        }
+        Some(
+            self.missing
+                .map(|missing| missing.trim().to_string())
+                .filter(|missing| !missing.is_empty())
+                .unwrap_or_else(|| "the answer is too vague".to_string()),
+        )
+    }
+}
+
+/// Get a printable prefix of a raw response body for error messages
+fn body_prefix(bytes: &[u8]) -> String {
+    const PREFIX_LEN: usize = 200;
+    let prefix = String::from_utf8_lossy(&bytes[..bytes.len().min(PREFIX_LEN)]);
+    if bytes.len() > PREFIX_LEN {
+        format!("{}...", prefix)
+    } else {
+        prefix.to_string()
+    }
+}
+
+/// Decode a response body as UTF-8, including a prefix of the raw bytes in the error
+pub fn decode_body(bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes).map_err(|e| {
+        WizardError::Parse(format!(
+            "LLM API response is not valid UTF-8 ({}): {}",
+            e.utf8_error(),
+            body_prefix(e.as_bytes())
+        ))
+    })
+}
+
+/// Check whether an API error means the model is unavailable or rate-limited
+pub fn is_model_unavailable(status: reqwest::StatusCode, message: &str) -> bool {
+    let message = message.to_lowercase();
+    matches!(status.as_u16(), 404 | 429 | 502 | 503)
+        || message.contains("no endpoints")
+        || message.contains("rate limit")
+        || message.contains("rate-limit")
+}
+
+/// Model available from the provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// Model identifier to pass in requests
+    pub id: String,
+    /// Human-readable model name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Maximum context length in tokens
+    #[serde(default)]
+    pub context_length: Option<u64>,
+    /// Pricing per token
+    #[serde(default)]
+    pub pricing: Option<ModelPricing>,
+    /// Limits of the provider serving the model
+    #[serde(default)]
+    pub top_provider: Option<ModelProvider>,
+}
+
+impl ModelInfo {
+    /// Get the maximum number of tokens the model can generate, if reported
+    pub fn max_completion_tokens(&self) -> Option<u64> {
+        self.top_provider.as_ref()?.max_completion_tokens
+    }
+}
+
+/// Limits of the provider serving a model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelProvider {
+    /// Maximum number of tokens the model can generate
+    #[serde(default)]
+    pub max_completion_tokens: Option<u64>,
+}
+
+/// Pricing of a model, in USD per token as reported by the provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPricing {
+    /// Price per prompt token
+    pub prompt: String,
+    /// Price per completion token
+    pub completion: String,
+}
+
+impl ModelPricing {
+    /// Format a per-token price as USD per million tokens
+    fn per_million(price: &str) -> String {
+        match price.parse::<f64>() {
+            Ok(0.0) => "free".to_string(),
+            Ok(price) => format!("${:.2}", price * 1_000_000.0),
+            Err(_) => price.to_string(),
         }
     }
 }
 
+/// Parse a models-list response from the provider
+pub fn parse_models_response(body: &str) -> Result<Vec<ModelInfo>> {
+    #[derive(Deserialize)]
+    struct ModelsResponse {
+        data: Vec<ModelInfo>,
+    }
+
+    let response: ModelsResponse =
+        serde_json::from_str(body).map_err(WizardError::json("models list"))?;
+    Ok(response.data)
+}
+
+/// Format models as a table with pricing (per million tokens) and context length
+pub fn format_models_table(models: &[ModelInfo]) -> String {
+    let id_width = models
+        .iter()
+        .map(|model| model.id.len())
+        .max()
+        .unwrap_or(0)
+        .max("MODEL".len());
+
+    let mut table = format!(
+        "{:<id_width$}  {:>10}  {:>10}  {:>10}\n",
+        "MODEL",
+        "PROMPT/1M",
+        "OUTPUT/1M",
+        "CONTEXT",
+        id_width = id_width
+    );
+
+    for model in models {
+        let (prompt, completion) = match &model.pricing {
+            Some(pricing) => (
+                ModelPricing::per_million(&pricing.prompt),
+                ModelPricing::per_million(&pricing.completion),
+            ),
+            None => ("?".to_string(), "?".to_string()),
+        };
+        let context_length = model
+            .context_length
+            .map(|length| length.to_string())
+            .unwrap_or_else(|| "?".to_string());
+
+        table.push_str(&format!(
+            "{:<id_width$}  {:>10}  {:>10}  {:>10}\n",
+            model.id,
+            prompt,
+            completion,
+            context_length,
+            id_width = id_width
+        ));
+    }
+
+    table
+}
+
+/// Domain the LLM classified the project into
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainGuess {
+    /// The best-matching domain
+    pub domain: String,
+    /// Confidence in the classification from 0.0 to 1.0
+    pub confidence: f32,
+}
+
 /// Role for a chat message
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -62,6 +642,15 @@ pub struct ChatCompletionRequest {
     pub messages: Vec<ChatMessage>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u16>,
+    /// Sampling seed for reproducible output (honoured only by some providers)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Whether to receive the response as server-sent events while it is generated
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+    /// Sequences at which the model stops generating, e.g. to cut off prose after the JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
 }
 
 /// Response from chat completion
@@ -76,6 +665,79 @@ pub struct ChatCompletionChoice {
     pub message: ChatMessage,
 }
 
+/// Number of automatic retries left, shared by every clone of a client
+#[derive(Debug, Clone)]
+pub struct RetryBudget {
+    /// Retries still available
+    remaining: Arc<AtomicUsize>,
+}
+
+impl RetryBudget {
+    /// Create a budget allowing the given number of retries
+    pub fn new(limit: usize) -> Self {
+        Self {
+            remaining: Arc::new(AtomicUsize::new(limit)),
+        }
+    }
+
+    /// Use one retry, returning false if the budget is exhausted
+    pub fn try_consume(&self) -> bool {
+        self.remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |remaining| {
+                remaining.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Get the number of retries left
+    pub fn remaining(&self) -> usize {
+        self.remaining.load(Ordering::SeqCst)
+    }
+}
+
+/// Requests made by this process, to keep idempotency keys unique within a second
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Questions generated by this process, to keep question ids unique within a second
+static QUESTION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Create an id for a generated question
+///
+/// Answers are matched by question id, e.g. when merging sessions, so two questions
+/// generated in the same second (such as the questions of a batch) must not share one.
+fn new_question_id() -> String {
+    let now = chrono::Utc::now();
+    format!(
+        "q_{}{:09}_{}",
+        now.timestamp(),
+        now.timestamp_subsec_nanos(),
+        QUESTION_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Per-request state kept across the network retries of a single request to a model
+#[derive(Debug, Clone)]
+struct RequestContext {
+    /// Key identifying the request, so a provider can recognize a retry it already processed
+    idempotency_key: String,
+}
+
+impl RequestContext {
+    /// Create the context of a new request, with a fresh idempotency key
+    fn new() -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            idempotency_key: format!(
+                "projector-{}-{}{:09}-{}",
+                std::process::id(),
+                now.timestamp(),
+                now.timestamp_subsec_nanos(),
+                REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ),
+        }
+    }
+}
+
 /// Client for interacting with the LLM API
 #[derive(Clone)]
 pub struct LlmClient {
@@ -83,10 +745,23 @@ pub struct LlmClient {
     client: reqwest::Client,
     /// Configuration for the LLM
     config: LlmConfig,
+    /// Automatic retries allowed across the whole session, unlimited if not set
+    retry_budget: Option<RetryBudget>,
+    /// Log of every request and raw response, if one is being written
+    transcript: Option<Transcript>,
+    /// How retries, fallbacks and other warnings are reported
+    progress: ProgressMode,
+    /// Canned responses answering the requests instead of the API
+    #[cfg(test)]
+    script: Option<Arc<scripted::ScriptedApi>>,
 }
 
 impl LlmClient {
     /// Create a new LLM client with the default configuration
+    #[allow(
+        dead_code,
+        reason = "the wizard always builds the client from the loaded configuration"
+    )]
     pub fn new() -> Result<Self> {
         let config = LlmConfig::default();
         Self::with_config(config)
@@ -95,303 +770,1630 @@ impl LlmClient {
     /// Create a new LLM client with a custom configuration
     pub fn with_config(config: LlmConfig) -> Result<Self> {
         let client = reqwest::Client::new();
-        Ok(Self { client, config })
+        Ok(Self {
+            client,
+            config,
+            retry_budget: None,
+            transcript: None,
+            progress: ProgressMode::Interactive,
+            #[cfg(test)]
+            script: None,
+        })
     }
 
-    /// Generate a question based on the current context
-    pub async fn generate_question(&self, context: &Context) -> Result<Question> {
-        let prompt = self.create_question_prompt(context);
-        let response = self.send_chat_request(prompt).await?;
-
-        // Parse the response to extract the question
-        self.parse_question_response(&response)
+    /// Create a client whose requests are answered by the given replies, in order
+    #[cfg(test)]
+    pub(crate) fn scripted(
+        replies: impl IntoIterator<Item = scripted::Reply>,
+    ) -> (Self, Arc<scripted::ScriptedApi>) {
+        let script = Arc::new(scripted::ScriptedApi::new(replies));
+        let mut client = Self::with_config(LlmConfig::default()).expect("client");
+        client.script = Some(script.clone());
+        (client, script)
     }
 
-    /// Generate a project definition based on the context
-    pub async fn generate_project_definition(&self, context: &Context) -> Result<String> {
-        let prompt = self.create_project_definition_prompt(context);
-        let response = self.send_chat_request(prompt).await?;
+    /// Limit the automatic retries made across every request of the session
+    ///
+    /// The budget is shared with all clones of the client, so the question and
+    /// output generators draw from the same pool.
+    pub fn with_retry_budget(mut self, limit: Option<usize>) -> Self {
+        self.retry_budget = limit.map(RetryBudget::new);
+        self
+    }
 
-        Ok(response)
+    /// Report retries, fallbacks and other warnings in the given progress mode
+    pub fn with_progress(mut self, progress: ProgressMode) -> Self {
+        self.progress = progress;
+        self
     }
 
-    /// Create a prompt for generating a question
-    fn create_question_prompt(&self, context: &Context) -> Vec<ChatMessage> {
-        let system_prompt = match context.persona {
-            Persona::Default => {
-                "You are an intelligent project definition wizard that helps users define applications. \
-                Generate thoughtful, context-aware questions to understand the user's project requirements. \
-                Your questions should build upon previous answers and help create a comprehensive project definition."
-            }
-            Persona::ProductManager => {
-                "You are a Product Manager helping to define an application. \
-                Ask questions focused on user needs, market fit, success metrics, and product roadmap. \
-                Your goal is to ensure the project has clear objectives and delivers value to users."
-            }
-            Persona::LlmArchitect => {
-                "You are an software architect helping to define an application. \
-                Ask technical questions about model selection, prompt engineering, data requirements, and system architecture. \
-                Your goal is to ensure the project is technically feasible and optimally designed."
-            }
-            Persona::UxDesigner => {
-                "You are a UX Designer helping to define an application. \
-                Ask questions about user experience, interface design, user flows, and accessibility. \
-                Your goal is to ensure the project delivers an excellent user experience."
-            }
-            Persona::ComplianceOfficer => {
-                "You are a Compliance Officer helping to define an application. \
-                Ask questions about data privacy, ethical considerations, regulatory requirements, and risk mitigation. \
-                Your goal is to ensure the project complies with relevant regulations and ethical standards."
-            }
-        };
+    /// Record every request and raw response in a transcript
+    pub fn with_transcript(mut self, transcript: Option<Transcript>) -> Self {
+        self.transcript = transcript;
+        self
+    }
 
-        let persona_name = format!("{:?}", context.persona); // "ProductManager", "UxDesigner", etc.
-        let context_str = context.get_context_string();
+    /// Keep a sensitive text out of the transcript, if one is being written
+    pub fn redact_in_transcript(&self, text: &str) {
+        if let Some(transcript) = &self.transcript {
+            transcript.redact(text);
+        }
+    }
 
-        let user_prompt = format!(
-            r#"Your task is to generate the single best question to ask a user to help define their software project.
-                You are acting as a {persona_name}.
+    /// Get the number of automatic retries left, if a budget is set
+    pub fn retries_remaining(&self) -> Option<usize> {
+        self.retry_budget.as_ref().map(RetryBudget::remaining)
+    }
 
-                **Rules:**
-                1.  Your entire response MUST be a single, valid JSON object. Do not include any explanatory text, markdown formatting, or any characters before or after the JSON.
-                2.  The question must logically follow the provided context and aim to fill in missing information.
-                3.  If the context is empty or sparse, ask a broad, foundational question.
-                4.  If the context is detailed, ask a more specific question to clarify a point or explore a related area.
-                5.  Prefer structured questions (`MultipleChoice`, `YesNo`, `RatingScale`) when possible to guide the user, but use `FreeText` for open-ended topics.
-                6. Number of answer options should be in range of 4 to 10 
-                **Context of the conversation so far:**
-                ---
-                {context_str}
-                ---
+    /// Send a fixed sampling seed with every request
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.config.seed = seed;
+        self
+    }
 
-                **JSON Output Structure:**
-                {{
-                  "question_type": "MultipleChoice" | "YesNo" | "RatingScale" | "FreeText",
-                  "question_text": "The text of the question to the user.",
-                  "options": ["Option 1", "Option 2", ...] // (Required for MultipleChoice, otherwise omit)
-                  "scale": [min_number, max_number]     // (Required for RatingScale, otherwise omit)
-                  "help_text": "Optional clarifying text for the user." // (Optional, include if the question is complex)
-                }}
+    /// Use the given model instead of the configured one
+    pub fn with_model(mut self, model: Option<String>) -> Self {
+        if let Some(model) = model {
+            self.config.model = model;
+        }
+        self
+    }
 
-                **Example:**
-                If the user just said "I want to build a chatbot for my website", a good follow-up question would be:
+    /// Use the model settings recorded on a session
+    pub fn with_model_settings(mut self, settings: &ModelSettings) -> Self {
+        self.config.model = settings.model.clone();
+        self.config.temperature = settings.temperature;
+        self.config.max_tokens = settings.max_tokens;
+        self
+    }
 
-                ```json
-                {{
-                  "question_type": "MultipleChoice",
-                  "question_text": "What is the primary goal of your website chatbot?",
-                  "options": [
-                    "Answering customer support questions",
-                    "Generating sales leads",
-                    "Guiding users through the website",
-                    .
-                    .
-                    .
-                    "Something else ?"
-                  ],
-                  "help_text": "This will help us understand the core functionality and success metrics for the chatbot."
-                }}
-                Now, based on the provided context, generate the next question as a JSON object."#,
-            persona_name = persona_name,
-            context_str = context_str
-        );
+    /// Get the configuration in use
+    #[cfg(test)]
+    pub(crate) fn config(&self) -> &LlmConfig {
+        &self.config
+    }
 
-        vec![
-            ChatMessage {
-                role: Role::System,
-                content: system_prompt.to_string(),
-            },
-            ChatMessage {
-                role: Role::User,
-                content: user_prompt,
-            },
-        ]
+    /// Get the model settings in use
+    pub fn model_settings(&self) -> ModelSettings {
+        self.config.model_settings()
     }
 
-    /// Create a prompt for generating a project definition
-    fn create_project_definition_prompt(&self, context: &Context) -> Vec<ChatMessage> {
-        let system_prompt = "You are an intelligent project definition wizard that helps users define applications. \
-            Based on the user's answers to your questions, generate a comprehensive project definition document in Markdown format.";
+    /// Describe each configured model whose output limit is below `max_tokens`
+    ///
+    /// Requests to these models are sent with `max_tokens` reduced to the limit.
+    pub fn max_tokens_warnings(&self) -> Vec<String> {
+        self.config
+            .models()
+            .filter_map(|model| {
+                let limit = self.config.max_tokens_for(model);
+                (limit < self.config.max_tokens).then(|| {
+                    format!(
+                        "max_tokens {} is above the output limit of {} ({}); using {}",
+                        self.config.max_tokens, model, limit, limit
+                    )
+                })
+            })
+            .collect()
+    }
 
-        let context_str = context.get_context_string();
+    /// Set the voice of the project definition document
+    pub fn with_doc_style(mut self, doc_style: Option<DocStyle>) -> Self {
+        self.config.doc_style = doc_style;
+        self
+    }
 
-        let user_prompt = format!(
-            r#"Based on the conversation context provided below, generate a comprehensive Project Definition Document.
-            **Formatting Instructions:**
-            - The entire output must be a single Markdown document.
-            - Use `##` for main section titles.
-            - Use bullet points (`-`) for lists.
-            - Write in a clear, professional, and concise tone suitable for both technical and business stakeholders.
+    /// Set the order in which questions cover the project
+    pub fn with_flow(mut self, flow: Option<InterviewFlow>) -> Self {
+        self.config.flow = flow;
+        self
+    }
 
-            **Content Instructions:**
-            - You must include every section listed below.
-            - For each section, provide a **Confidence Score** from 1 (low confidence, very little info) to 5 (high confidence, detailed info).
-            - After each confidence score, you MUST provide a brief justification in parentheses. Example: `**Confidence: 2/5** (Reason: The user mentioned a target audience but did not provide specific demographics.)`
-            - If you have insufficient information for a section, state that clearly and explain what information is needed.
+    /// Never ask about the given topics, in addition to any already configured
+    pub fn with_avoided_topics(mut self, topics: Vec<String>) -> Self {
+        for topic in topics {
+            let topic = topic.trim().to_string();
+            if !topic.is_empty() && !self.config.avoid_topics.contains(&topic) {
+                self.config.avoid_topics.push(topic);
+            }
+        }
+        self
+    }
 
-            **Conversation Context:**
-            ---
-            {context_str}
-            ---
+    /// Ask for the project definition to be written in the given language
+    pub fn with_language(mut self, language: Option<String>) -> Self {
+        self.config.language = language;
+        self
+    }
 
-            **Project Definition Document Structure:**
+    /// List topics already covered in the question prompt so the model doesn't re-ask them
+    pub fn with_avoid_covered_topics(mut self, avoid_covered_topics: bool) -> Self {
+        self.config.avoid_covered_topics = avoid_covered_topics;
+        self
+    }
 
-            ## 1. Project Name and Summary
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content...*
+    /// Leave the per-section confidence scores and their justifications out of the definition
+    pub fn with_omit_confidence(mut self, omit_confidence: bool) -> Self {
+        self.config.omit_confidence = omit_confidence;
+        self
+    }
 
-            ## 2. Use Cases and Goals
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content... (include specific user scenarios if possible)*
+    /// List the models available from the provider
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        let response = self.get("models").await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(WizardError::Api(format!(
+                "Models endpoint returned {}",
+                status
+            )));
+        }
 
-            ## 3. Target User Profile(s)
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content...*
+        let body = self.read_body(response).await?;
+        parse_models_response(&body)
+    }
 
-            ## 4. Required Inputs and Expected Outputs
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content... (Detail what the user provides to the system and what the system returns)*
+    /// Generate a question based on the current context
+    ///
+    /// `progress` is how far through the interview we are (0.0-1.0) and drives
+    /// the temperature schedule, if one is configured.
+    pub async fn generate_question(&self, context: &Context, progress: f32) -> Result<Question> {
+        let prompt = self.create_question_prompt(context);
+        let temperature = self.config.question_temperature(progress);
+        self.request_question(prompt, temperature).await
+    }
+
+    /// Get the first avoided topic a question mentions, if any
+    pub fn avoided_topic(&self, question: &Question) -> Option<&str> {
+        self.config
+            .avoid_topics
+            .iter()
+            .find(|topic| question.mentions(topic))
+            .map(String::as_str)
+    }
+
+    /// Request a single question, asking again when it is about an avoided topic
+    ///
+    /// After `MAX_AVOIDED_TOPIC_RETRIES` new attempts the last question is kept, with
+    /// a warning, so the interview can go on (the user can still skip it).
+    async fn request_question(
+        &self,
+        mut prompt: Vec<ChatMessage>,
+        temperature: f32,
+    ) -> Result<Question> {
+        let mut retries = 0;
+        loop {
+            let question = self
+                .request_json(prompt.clone(), temperature, |response| {
+                    self.parse_question_response(response)
+                })
+                .await?;
+
+            let Some(topic) = self.avoided_topic(&question) else {
+                return Ok(question);
+            };
+            if retries == MAX_AVOIDED_TOPIC_RETRIES {
+                self.progress.warn(&format!(
+                    "Warning: The model kept asking about the avoided topic \"{}\". Skip the question if you don't want to answer it.",
+                    topic
+                ));
+                return Ok(question);
+            }
+
+            retries += 1;
+            prompt.push(ChatMessage {
+                role: Role::User,
+                content: format!(
+                    "Your question \"{}\" is about \"{}\", a topic the user asked never to be asked about. \
+                    Generate a different question about another topic, as a JSON object with the same structure.",
+                    question.text, topic
+                ),
+            });
+        }
+    }
+
+    /// Generate several candidate questions at once, for the user to choose which to answer
+    pub async fn generate_question_batch(
+        &self,
+        context: &Context,
+        progress: f32,
+        count: usize,
+    ) -> Result<Vec<Question>> {
+        let mut prompt = self.create_question_prompt(context);
+        if let Some(message) = prompt.last_mut() {
+            message.content.push_str(&format!(
+                "\n\nInstead of a single question, generate {count} different candidate questions, each about a different open topic, so the user can choose which to answer first. \
+                Your entire response MUST be a JSON array of {count} question objects, each with the structure above, and nothing else."
+            ));
+        }
+
+        let temperature = self.config.question_temperature(progress);
+        let mut questions = self
+            .request_json(prompt, temperature, |response| {
+                self.parse_question_batch_response(response)
+            })
+            .await?;
+
+        // Drop candidates about avoided topics, asking for a single question if none are left
+        questions.retain(|question| self.avoided_topic(question).is_none());
+        if questions.is_empty() {
+            return Ok(vec![self.generate_question(context, progress).await?]);
+        }
+
+        questions.truncate(count);
+        Ok(questions)
+    }
+
+    /// Generate a follow-up question that gathers more information about a specific area
+    pub async fn generate_followup_question(
+        &self,
+        context: &Context,
+        area: &str,
+    ) -> Result<Question> {
+        let mut prompt = self.create_question_prompt(context);
+        prompt.push(ChatMessage {
+            role: Role::User,
+            content: format!(
+                "The project definition is still weak in this area: \"{}\". \
+                The next question MUST gather the information needed to describe it with confidence.",
+                area
+            ),
+        });
+
+        // Follow-ups come at the end of the interview, so use the end of the schedule
+        let temperature = self.config.question_temperature(1.0);
+        self.request_question(prompt, temperature).await
+    }
+
+    /// Generate a clarifying question about the last answer, which left `missing` unclear
+    pub async fn generate_clarifying_question(
+        &self,
+        context: &Context,
+        missing: &str,
+    ) -> Result<Question> {
+        let mut prompt = self.create_question_prompt(context);
+        prompt.push(ChatMessage {
+            role: Role::User,
+            content: format!(
+                "The user's last answer was too vague: {}. \
+                The next question MUST ask the user to clarify that answer, and nothing else.",
+                missing
+            ),
+        });
+
+        // A clarifier stays close to the answer, so use the start of the schedule
+        let temperature = self.config.question_temperature(0.0);
+        self.request_question(prompt, temperature).await
+    }
+
+    /// Ask the LLM whether the last answer is specific enough to build on
+    pub async fn assess_answer(&self, context: &Context) -> Result<AnswerAssessment> {
+        let system_prompt = "You are an intelligent project definition wizard that helps users define applications. \
+            You judge whether the user's answers are specific enough to write a project definition from.";
+
+        let Some(answer) = context.history.last() else {
+            return Ok(AnswerAssessment {
+                sufficient: true,
+                missing: None,
+            });
+        };
+        let user_prompt = format!(
+            r#"Judge whether the user's answer to the question below is specific enough to build on.
+            An answer is insufficient when it is vague, evasive or leaves out what the question asked for.
+
+            **Rules:**
+            1.  Your entire response MUST be a single, valid JSON object. Do not include any explanatory text or markdown formatting.
+            2.  If the answer is insufficient, describe in one short phrase what it leaves unclear.
+
+            **Question:** {question}
+            **Answer:** {response}
+
+            **Conversation Context:**
+            ---
+            {context_str}
+            ---
+
+            **JSON Output Structure:**
+            {{ "sufficient": true | false, "missing": "What the answer leaves unclear" }}"#,
+            question = answer.question.text,
+            response = answer.response,
+            context_str = context.get_context_string()
+        );
+
+        let prompt = vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt.to_string(),
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ];
+        self.request_json(
+            prompt,
+            self.config.temperature,
+            Self::parse_assessment_response,
+        )
+        .await
+    }
+
+    /// Parse the LLM response to an answer assessment
+    pub fn parse_assessment_response(response: &str) -> Result<AnswerAssessment> {
+        let response = response.trim().replace("```json", "").replace("```", "");
+        serde_json::from_str(&response).map_err(WizardError::json("answer assessment"))
+    }
+
+    /// Ask the LLM to explain why a question matters and give an example answer
+    pub async fn explain_question(&self, context: &Context, question: &Question) -> Result<String> {
+        let system_prompt = "You are an intelligent project definition wizard that helps users define applications. \
+            You explain your questions to users who are unsure what is being asked.";
+
+        let mut user_prompt = format!(
+            r#"The user is unsure about this question:
+
+            "{question}"
 
-            ## 5. Functional Components/Modules
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content...*
+            In two or three short sentences, explain why this question matters for defining their project.
+            Then give one example answer that fits the project so far, starting with "Example answer:".
+            Reply in plain text, without markdown formatting.
+
+            **Context of the conversation so far:**
+            ---
+            {context_str}
+            ---"#,
+            question = question.text,
+            context_str = context.get_context_string()
+        );
+        if let Some(options) = question.choices() {
+            user_prompt.push_str(&format!(
+                "\n\nThe answer options are: {}",
+                options.join(", ")
+            ));
+        }
+
+        let messages = vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt.to_string(),
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ];
+
+        let response = self
+            .send_chat_request(messages, self.config.temperature)
+            .await?;
+        Ok(response.trim().to_string())
+    }
+
+    /// Ask the LLM for a short reply to the user's latest free-form chat message
+    pub async fn chat_reply(&self, context: &Context) -> Result<String> {
+        let system_prompt = format!(
+            "You are acting as a {} helping to define an application. \
+            After the structured interview, the user is clarifying points in a free-form conversation.",
+            context.persona
+        );
+
+        let user_prompt = format!(
+            r#"Reply to the user's latest message, the last answer in the context below.
+            Acknowledge what it adds to the project in one or two sentences, and if something is still unclear, ask one short follow-up question.
+            Reply in plain text, without markdown formatting, in at most three sentences.
+
+            **Context of the conversation so far:**
+            ---
+            {context_str}
+            ---"#,
+            context_str = context.get_context_string()
+        );
+
+        let messages = vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt,
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ];
+
+        let response = self
+            .send_chat_request(messages, self.config.temperature)
+            .await?;
+        Ok(response.trim().to_string())
+    }
+
+    /// Ask the LLM for a one-line note on what the latest answer means for the project
+    pub async fn generate_note(&self, context: &Context) -> Result<String> {
+        let system_prompt = format!(
+            "You are acting as a {} helping to define an application. \
+            You keep short running notes on your evolving understanding of the project.",
+            context.persona
+        );
+
+        let user_prompt = format!(
+            r#"Write one new line for your running notes, capturing what the latest answer tells you about the project:
+            a decision, a requirement, an open risk or a gap still to explore.
+            Don't repeat earlier notes. Reply with the note only, in at most 25 words, without markdown formatting.
+
+            **Context of the conversation so far:**
+            ---
+            {context_str}
+            ---"#,
+            context_str = context.get_context_string()
+        );
+
+        let messages = vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt,
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ];
+
+        let response = self
+            .send_chat_request(messages, self.config.temperature)
+            .await?;
+        Ok(response.trim().to_string())
+    }
+
+    /// Ask the LLM for a concise name for the project described so far
+    pub async fn generate_project_name(&self, context: &Context) -> Result<String> {
+        let system_prompt = "You are an intelligent project definition wizard that helps users define applications. \
+            You give projects short, memorable working names.";
+
+        let user_prompt = format!(
+            r#"Suggest a concise working name for the project described in the conversation context below,
+            in two to five words. Reply with the name only, without quotes, explanation or markdown formatting.
+
+            **Conversation Context:**
+            ---
+            {context_str}
+            ---"#,
+            context_str = context.get_context_string()
+        );
+
+        let messages = vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt.to_string(),
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ];
+
+        let response = self
+            .send_chat_request(messages, self.config.temperature)
+            .await?;
+        Ok(response.trim().to_string())
+    }
+
+    /// Ask the LLM how confident it would be in each definition section given the context
+    pub async fn probe_section_confidence(
+        &self,
+        context: &Context,
+    ) -> Result<Vec<SectionConfidence>> {
+        let prompt = self.create_confidence_probe_prompt(context);
+        self.request_json(
+            prompt,
+            self.config.temperature,
+            Self::parse_confidence_probe_response,
+        )
+        .await
+    }
+
+    /// Ask the LLM to re-evaluate the confidence and open questions of a written definition
+    pub async fn review_definition(&self, markdown: &str) -> Result<DefinitionReview> {
+        let prompt = self.create_review_prompt(markdown);
+        self.request_json(prompt, self.config.temperature, Self::parse_review_response)
+            .await
+    }
+
+    /// Ask the LLM which of the given domains the project belongs to
+    pub async fn classify_domain(
+        &self,
+        context: &Context,
+        domains: &[String],
+    ) -> Result<DomainGuess> {
+        let system_prompt = "You are an intelligent project definition wizard that helps users define applications. \
+            You classify projects into business domains.";
+
+        let user_prompt = format!(
+            r#"Classify the project described in the conversation context below into exactly one of the listed domains.
+
+            **Rules:**
+            1.  Your entire response MUST be a single, valid JSON object. Do not include any explanatory text or markdown formatting.
+            2.  The domain MUST be copied exactly from the list.
+            3.  The confidence is a number from 0.0 (pure guess) to 1.0 (certain).
+
+            **Conversation Context:**
+            ---
+            {context_str}
+            ---
+
+            **Domains:**
+            {domains}
+
+            **JSON Output Structure:**
+            {{ "domain": "Domain from the list", "confidence": 0.0 }}"#,
+            context_str = context.get_context_string(),
+            domains = domains.join(", ")
+        );
+
+        let prompt = vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt.to_string(),
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ];
+        self.request_json(prompt, self.config.temperature, Self::parse_domain_response)
+            .await
+    }
+
+    /// Parse the LLM response to a domain classification
+    pub fn parse_domain_response(response: &str) -> Result<DomainGuess> {
+        let response = response.trim().replace("```json", "").replace("```", "");
+        serde_json::from_str(&response).map_err(WizardError::json("domain classification"))
+    }
+
+    /// Generate a project definition based on the context
+    pub async fn generate_project_definition(&self, context: &Context) -> Result<String> {
+        self.generate_definition_sections(context, 0..DEFINITION_SECTIONS.len())
+            .await
+    }
+
+    /// Stream a range of sections of the project definition, passing the text to `on_text` as it arrives
+    pub async fn stream_definition_sections(
+        &self,
+        context: &Context,
+        sections: std::ops::Range<usize>,
+        on_text: impl FnMut(&str) -> Result<()>,
+    ) -> Result<String> {
+        let prompt = self.create_project_definition_prompt(context, sections);
+        self.stream_chat_request(prompt, self.config.temperature, on_text)
+            .await
+    }
+
+    /// Generate only a range of sections of the project definition
+    pub async fn generate_definition_sections(
+        &self,
+        context: &Context,
+        sections: std::ops::Range<usize>,
+    ) -> Result<String> {
+        let prompt = self.create_project_definition_prompt(context, sections);
+        let response = self
+            .send_chat_request(prompt, self.config.temperature)
+            .await?;
+
+        Ok(response)
+    }
+
+    /// Create a prompt for generating a question
+    ///
+    /// Long sessions can make the prompt too large for the provider, so when it would
+    /// exceed the configured limit, the lowest-signal answers are left out of it.
+    /// The prompt depends only on the client settings and the context, never on the clock.
+    pub(crate) fn create_question_prompt(&self, context: &Context) -> Vec<ChatMessage> {
+        let prompt = self.render_question_prompt(context, None);
+        let size: usize = prompt
+            .iter()
+            .map(|message| message.content.chars().count())
+            .sum();
+        let limit = self.config.max_question_prompt_chars;
+        if size <= limit {
+            return prompt;
+        }
+
+        self.progress.warn(&format!(
+            "Warning: The question prompt is {} characters, over the limit of {}. Leaving the lowest-signal answers out of it.",
+            size, limit
+        ));
+        let context_chars = context.get_context_string().chars().count();
+        self.render_question_prompt(context, Some(context_chars.saturating_sub(size - limit)))
+    }
+
+    /// Render the question prompt, trimming the context to `max_context_chars` if given
+    fn render_question_prompt(
+        &self,
+        context: &Context,
+        max_context_chars: Option<usize>,
+    ) -> Vec<ChatMessage> {
+        // Each persona gets its own system prompt and a few-shot example in its area of focus
+        let (system_prompt, example) = match context.persona {
+            Persona::Default => (
+                "You are an intelligent project definition wizard that helps users define applications. \
+                Generate thoughtful, context-aware questions to understand the user's project requirements. \
+                Your questions should build upon previous answers and help create a comprehensive project definition.",
+                r#"If the user just said "I want to build a chatbot for my website", a good follow-up question would be:
+
+                ```json
+                {
+                  "question_type": "MultipleChoice",
+                  "question_text": "What is the primary goal of your website chatbot?",
+                  "options": [
+                    "Answering customer support questions",
+                    "Generating sales leads",
+                    "Guiding users through the website",
+                    .
+                    .
+                    .
+                    "Something else ?"
+                  ],
+                  "help_text": "This will help us understand the core functionality and success metrics for the chatbot."
+                }"#,
+            ),
+            Persona::ProductManager => (
+                "You are a Product Manager helping to define an application. \
+                Ask questions focused on user needs, market fit, success metrics, and product roadmap. \
+                Your goal is to ensure the project has clear objectives and delivers value to users.",
+                r#"If the user just said "We want an assistant that drafts replies to customer emails", a good follow-up question would be:
+
+                ```json
+                {
+                  "question_type": "MultipleChoice",
+                  "question_text": "How will you measure whether the email assistant is successful?",
+                  "options": [
+                    "Reduction in average response time",
+                    "Share of drafts sent without edits",
+                    "Customer satisfaction scores",
+                    "Number of emails handled per agent",
+                    "Something else ?"
+                  ],
+                  "help_text": "A clear success metric helps prioritise features and decide when the product is ready to launch."
+                }"#,
+            ),
+            Persona::LlmArchitect => (
+                "You are an software architect helping to define an application. \
+                Ask technical questions about model selection, prompt engineering, data requirements, and system architecture. \
+                Your goal is to ensure the project is technically feasible and optimally designed.",
+                r#"If the user just said "The assistant should answer questions about our internal documentation", a good follow-up question would be:
+
+                ```json
+                {
+                  "question_type": "MultipleChoice",
+                  "question_text": "How should the assistant get access to your internal documentation?",
+                  "options": [
+                    "Retrieval over a vector index of the documents",
+                    "Fine-tuning a model on the documents",
+                    "Including the relevant documents directly in the prompt",
+                    "Calling an existing search API",
+                    "Not sure yet"
+                  ],
+                  "help_text": "This determines the data pipeline, the model's context requirements and how the documentation is kept up to date."
+                }"#,
+            ),
+            Persona::UxDesigner => (
+                "You are a UX Designer helping to define an application. \
+                Ask questions about user experience, interface design, user flows, and accessibility. \
+                Your goal is to ensure the project delivers an excellent user experience.",
+                r#"If the user just said "Users will book appointments through the assistant", a good follow-up question would be:
+
+                ```json
+                {
+                  "question_type": "MultipleChoice",
+                  "question_text": "What should happen when the assistant can't find a free slot at the time the user asked for?",
+                  "options": [
+                    "Suggest the nearest available slots",
+                    "Show a calendar to pick another time",
+                    "Offer to join a waiting list",
+                    "Hand the user over to a human",
+                    "Something else ?"
+                  ],
+                  "help_text": "Designing the unhappy path of the booking flow early avoids dead ends for users."
+                }"#,
+            ),
+            Persona::ComplianceOfficer => (
+                "You are a Compliance Officer helping to define an application. \
+                Ask questions about data privacy, ethical considerations, regulatory requirements, and risk mitigation. \
+                Your goal is to ensure the project complies with relevant regulations and ethical standards.",
+                r#"If the user just said "The assistant will help patients describe their symptoms before a visit", a good follow-up question would be:
+
+                ```json
+                {
+                  "question_type": "MultipleChoice",
+                  "question_text": "Which data protection regulations apply to the patient data the assistant will collect?",
+                  "options": [
+                    "HIPAA",
+                    "GDPR",
+                    "Both HIPAA and GDPR",
+                    "Other local health data regulations",
+                    "Not sure yet"
+                  ],
+                  "help_text": "Health data is highly sensitive; the applicable regulations decide how it must be stored, processed and retained."
+                }"#,
+            ),
+        };
+
+        let persona_name = format!("{:?}", context.persona); // "ProductManager", "UxDesigner", etc.
+        let mut context_str = match max_context_chars {
+            Some(max_chars) => context.get_trimmed_context_string(max_chars),
+            None => context.get_context_string(),
+        };
+
+        // Steer the model away from topics it has already asked about
+        if self.config.avoid_covered_topics {
+            let topics = context.covered_topics(MAX_COVERED_TOPICS);
+            if !topics.is_empty() {
+                context_str.push_str(&format!(
+                    "Topics already covered (do not re-ask about these): {}\n",
+                    topics.join(", ")
+                ));
+            }
+        }
+
+        // Steer the order in which the project is covered, if a flow was chosen
+        let flow_instruction = self
+            .config
+            .flow
+            .map(|flow| flow.instruction())
+            .unwrap_or_default();
+
+        // Topics the user never wants to be asked about
+        let avoid_instruction = if self.config.avoid_topics.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "NEVER ask about these topics, the user asked not to be asked about them: {}.",
+                self.config.avoid_topics.join("; ")
+            )
+        };
+
+        let user_prompt = format!(
+            r#"Your task is to generate the single best question to ask a user to help define their software project.
+                You are acting as a {persona_name}.
+
+                **Rules:**
+                1.  Your entire response MUST be a single, valid JSON object. Do not include any explanatory text, markdown formatting, or any characters before or after the JSON.
+                2.  The question must logically follow the provided context and aim to fill in missing information.
+                3.  If the context is empty or sparse, ask a broad, foundational question.
+                4.  If the context is detailed, ask a more specific question to clarify a point or explore a related area.
+                5.  Prefer structured questions (`MultipleChoice`, `YesNo`, `RatingScale`) when possible to guide the user, but use `FreeText` for open-ended topics.
+                6. Number of answer options should be in range of 4 to 10 
+                {flow_instruction}
+                {avoid_instruction}
+                **Context of the conversation so far:**
+                ---
+                {context_str}
+                ---
+
+                **JSON Output Structure:**
+                {{
+                  "question_type": "MultipleChoice" | "YesNo" | "RatingScale" | "FreeText",
+                  "question_text": "The text of the question to the user.",
+                  "options": ["Option 1", "Option 2", ...] // (Required for MultipleChoice, otherwise omit)
+                  "scale": [min_number, max_number]     // (Required for RatingScale, otherwise omit)
+                  "help_text": "Optional clarifying text for the user.", // (Optional, include if the question is complex)
+                  "reason": "Why this question matters for the project definition.", // (Optional, one short sentence)
+                  "sensitive": true // (Optional, include if the answer is likely to contain personal, health or financial data)
+                  "default_answer": "A suggested answer." // (Optional, FreeText only: include if the context suggests a sensible answer the user can accept as is)
+                }}
+
+                **Example:**
+                {example}
+                Now, based on the provided context, generate the next question as a JSON object."#,
+            persona_name = persona_name,
+            flow_instruction = flow_instruction,
+            avoid_instruction = avoid_instruction,
+            context_str = context_str,
+            example = example
+        );
+
+        vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt.to_string(),
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ]
+    }
+
+    /// Create a prompt for generating a range of sections of the project definition
+    ///
+    /// Like the question prompt, it depends only on the client settings and the context.
+    pub(crate) fn create_project_definition_prompt(
+        &self,
+        context: &Context,
+        sections: std::ops::Range<usize>,
+    ) -> Vec<ChatMessage> {
+        let system_prompt = "You are an intelligent project definition wizard that helps users define applications. \
+            Based on the user's answers to your questions, generate a comprehensive project definition document in Markdown format.";
+
+        // Long interviews are trimmed so the final, most expensive request doesn't overflow
+        let context_str =
+            context.get_trimmed_context_string(self.config.max_definition_context_chars);
+        let scored = !self.config.omit_confidence;
+        let structure = render_section_structure(sections, scored);
+
+        // Section titles and confidence markers stay in English so the document can be parsed
+        let language_instruction = match &self.config.language {
+            Some(language) if scored => format!(
+                "- Write the content in {}, but keep the section titles and the `**Confidence: n/5**` markers exactly as given, in English.",
+                language
+            ),
+            Some(language) => format!(
+                "- Write the content in {}, but keep the section titles exactly as given, in English.",
+                language
+            ),
+            None => String::new(),
+        };
+        let confidence_instruction = if scored {
+            "- For each section, provide a **Confidence Score** from 1 (low confidence, very little info) to 5 (high confidence, detailed info).
+            - After each confidence score, you MUST provide a brief justification in parentheses. Example: `**Confidence: 2/5** (Reason: The user mentioned a target audience but did not provide specific demographics.)`"
+        } else {
+            "- Do not add confidence scores or ratings to the sections."
+        };
+        let style_instruction = self
+            .config
+            .doc_style
+            .map(|style| format!("- {}", style.instruction()))
+            .unwrap_or_default();
+
+        let user_prompt = format!(
+            r#"Based on the conversation context provided below, generate a comprehensive Project Definition Document.
+            **Formatting Instructions:**
+            - The entire output must be a single Markdown document.
+            - Use `##` for main section titles.
+            - Use bullet points (`-`) for lists.
+            - Write in a clear, professional, and concise tone suitable for both technical and business stakeholders.
+            {style_instruction}
+            {language_instruction}
+
+            **Content Instructions:**
+            - You must include every section listed below, and only those sections.
+            {confidence_instruction}
+            - If you have insufficient information for a section, state that clearly and explain what information is needed.
+
+            **Conversation Context:**
+            ---
+            {context_str}
+            ---
+
+            **Project Definition Document Structure:**
+
+            {structure}
+            "#,
+            context_str = context_str,
+            structure = structure,
+            confidence_instruction = confidence_instruction,
+            style_instruction = style_instruction,
+            language_instruction = language_instruction
+        );
+
+        vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt.to_string(),
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ]
+    }
+
+    /// Create a prompt for estimating the confidence of each definition section
+    fn create_confidence_probe_prompt(&self, context: &Context) -> Vec<ChatMessage> {
+        let system_prompt = "You are an intelligent project definition wizard that helps users define applications. \
+            You assess how much is known about a project before its definition document is written.";
+
+        let context_str = context.get_context_string();
+        let sections = DEFINITION_SECTIONS
+            .iter()
+            .filter(|section| section.scored)
+            .map(|section| format!("- {}", section.title))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let user_prompt = format!(
+            r#"Based on the conversation context below, estimate the Confidence Score (1 = very little info, 5 = detailed info) you could give each section of the project definition document if you wrote it now.
+
+            **Rules:**
+            1.  Your entire response MUST be a single, valid JSON object. Do not include any explanatory text or markdown formatting.
+            2.  Include every section listed below, using the exact section titles.
+
+            **Conversation Context:**
+            ---
+            {context_str}
+            ---
+
+            **Sections:**
+            {sections}
+
+            **JSON Output Structure:**
+            {{
+              "sections": [
+                {{ "section": "Section title", "confidence": 1 }}
+              ]
+            }}"#,
+            context_str = context_str,
+            sections = sections
+        );
+
+        vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt.to_string(),
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ]
+    }
+
+    /// Create a prompt for re-evaluating a project definition that may have been edited by hand
+    fn create_review_prompt(&self, markdown: &str) -> Vec<ChatMessage> {
+        let system_prompt = "You are an intelligent project definition wizard that helps users define applications. \
+            You review project definition documents for how complete and specific they are.";
+
+        let user_prompt = format!(
+            r#"Review the project definition document below, which the user may have edited by hand. For each section, give a Confidence Score (1 = vague or missing information, 5 = detailed and specific), judged only from the text of the section. Then list the key pieces of information the document is still missing.
+
+            **Rules:**
+            1.  Your entire response MUST be a single, valid JSON object. Do not include any explanatory text or markdown formatting.
+            2.  Include every section of the document except "{open_questions_section}", using the exact section titles.
+            3.  Each open question is a single sentence; leave the list empty if nothing important is missing.
+
+            **Project Definition:**
+            ---
+            {markdown}
+            ---
+
+            **JSON Output Structure:**
+            {{
+              "sections": [
+                {{ "section": "Section title", "confidence": 1 }}
+              ],
+              "open_questions": ["Missing information"]
+            }}"#,
+            open_questions_section = OPEN_QUESTIONS_SECTION,
+            markdown = markdown
+        );
+
+        vec![
+            ChatMessage {
+                role: Role::System,
+                content: system_prompt.to_string(),
+            },
+            ChatMessage {
+                role: Role::User,
+                content: user_prompt,
+            },
+        ]
+    }
+
+    /// Parse the LLM response to a definition review
+    pub fn parse_review_response(response: &str) -> Result<DefinitionReview> {
+        let response = response.trim().replace("```json", "").replace("```", "");
+        serde_json::from_str(&response).map_err(WizardError::json("definition review"))
+    }
+
+    /// Parse the LLM response to a confidence probe
+    pub fn parse_confidence_probe_response(response: &str) -> Result<Vec<SectionConfidence>> {
+        #[derive(Deserialize)]
+        struct ProbeResponse {
+            sections: Vec<SectionConfidence>,
+        }
+
+        let response = response.trim().replace("```json", "").replace("```", "");
+        let parsed: ProbeResponse =
+            serde_json::from_str(&response).map_err(WizardError::json("confidence probe"))?;
+
+        Ok(parsed.sections)
+    }
+
+    /// Send a chat request whose response must be JSON, asking the model to correct a response that can't be parsed
+    ///
+    /// Each correction counts against the retry budget, if one is set.
+    async fn request_json<T>(
+        &self,
+        mut messages: Vec<ChatMessage>,
+        temperature: f32,
+        parse: impl Fn(&str) -> Result<T>,
+    ) -> Result<T> {
+        let mut corrections = 0;
+        loop {
+            let response = self
+                .send_chat_request(messages.clone(), temperature)
+                .await?;
+            let error = match parse(&response) {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+
+            if !error.is_parse_error() || corrections >= self.config.json_retry_max {
+                return Err(error);
+            }
+            if let Some(budget) = &self.retry_budget
+                && !budget.try_consume()
+            {
+                self.progress
+                    .warn("Retry budget exhausted; not asking the model to correct its response");
+                return Err(error);
+            }
+
+            self.progress.warn(&format!(
+                "Warning: {}. Asking the model to correct its response.",
+                error
+            ));
+            messages.push(ChatMessage {
+                role: Role::Assistant,
+                content: response,
+            });
+            messages.push(ChatMessage {
+                role: Role::User,
+                content: format!(
+                    "{}\n\nThe problem was: {}",
+                    self.config.json_retry_instruction, error
+                ),
+            });
+            corrections += 1;
+        }
+    }
+
+    /// Send a chat request to the LLM API, falling back to other models if needed
+    async fn send_chat_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        temperature: f32,
+    ) -> Result<String> {
+        let mut last_error = None;
+
+        for (attempt, model) in self.config.models().enumerate() {
+            // Every model after the first is a retry drawn from the session budget
+            if attempt > 0
+                && let Some(budget) = &self.retry_budget
+                && !budget.try_consume()
+            {
+                self.progress
+                    .warn("Retry budget exhausted; not trying further fallback models");
+                break;
+            }
+
+            match self
+                .send_chat_request_to_model(model, &messages, temperature)
+                .await
+            {
+                Ok(content) => {
+                    if model != &self.config.model {
+                        self.progress
+                            .warn(&format!("Response generated by fallback model: {}", model));
+                    }
+                    return Ok(content);
+                }
+                Err(e @ WizardError::ModelUnavailable { .. }) => {
+                    self.progress.warn(&e.to_string());
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| WizardError::Api("No model configured".to_string())))
+    }
+
+    /// Send a chat request to a specific model
+    async fn send_chat_request_to_model(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        temperature: f32,
+    ) -> Result<String> {
+        let request = ChatCompletionRequest {
+            model: model.to_string(),
+            messages: messages.to_vec(),
+            temperature: Some(temperature),
+            max_tokens: Some(self.config.max_tokens_for(model)),
+            seed: self.config.seed,
+            stream: false,
+            stop: self.config.stop.clone(),
+        };
+
+        // Send request to OpenRouter API
+        let result = async {
+            let response = self
+                .post_chat_request(&request, &RequestContext::new())
+                .await?;
+
+            let status = response.status();
+            let body = self.read_body(response).await?;
+            Ok::<_, WizardError>((status, body))
+        }
+        .await;
+        self.record_call(model, temperature, messages, &result);
+
+        let (status, body) = result?;
+        Self::parse_chat_response(model, status, &body)
+    }
+
+    /// Stream a chat request to the LLM API, passing the text to `on_text` as it arrives
+    ///
+    /// Falls back to other models like `send_chat_request`, but only while no text has
+    /// been received. Returns the complete text.
+    async fn stream_chat_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        temperature: f32,
+        mut on_text: impl FnMut(&str) -> Result<()>,
+    ) -> Result<String> {
+        let mut last_error = None;
+
+        for (attempt, model) in self.config.models().enumerate() {
+            // Every model after the first is a retry drawn from the session budget
+            if attempt > 0
+                && let Some(budget) = &self.retry_budget
+                && !budget.try_consume()
+            {
+                self.progress
+                    .warn("Retry budget exhausted; not trying further fallback models");
+                break;
+            }
+
+            match self
+                .stream_chat_request_to_model(model, &messages, temperature, &mut on_text)
+                .await
+            {
+                Ok(content) => {
+                    if model != &self.config.model {
+                        self.progress
+                            .warn(&format!("Response generated by fallback model: {}", model));
+                    }
+                    return Ok(content);
+                }
+                Err(e @ WizardError::ModelUnavailable { .. }) => {
+                    self.progress.warn(&e.to_string());
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| WizardError::Api("No model configured".to_string())))
+    }
+
+    /// Stream a chat request to a specific model
+    async fn stream_chat_request_to_model(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        temperature: f32,
+        on_text: &mut impl FnMut(&str) -> Result<()>,
+    ) -> Result<String> {
+        let request = ChatCompletionRequest {
+            model: model.to_string(),
+            messages: messages.to_vec(),
+            temperature: Some(temperature),
+            max_tokens: Some(self.config.max_tokens_for(model)),
+            seed: self.config.seed,
+            stream: true,
+            stop: self.config.stop.clone(),
+        };
+
+        let mut text = String::new();
+        let result = async {
+            let mut response = self
+                .post_chat_request(&request, &RequestContext::new())
+                .await?;
+
+            // Errors before the stream starts come back as a regular response body
+            let status = response.status();
+            if !status.is_success() {
+                let body = self.read_body(response).await?;
+                return Ok((status, body));
+            }
+
+            // Server-sent events are split into lines, which may span several chunks
+            let mut pending = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                pending.extend_from_slice(&chunk);
+                while let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    if let Some(delta) = Self::parse_stream_line(model, &line)? {
+                        if text.len() + delta.len() > self.config.max_response_bytes {
+                            return Err(WizardError::Api(format!(
+                                "LLM API response exceeded the size limit of {} bytes",
+                                self.config.max_response_bytes
+                            )));
+                        }
+                        text.push_str(&delta);
+                        on_text(&delta)?;
+                    }
+                }
+            }
+
+            Ok((status, text.clone()))
+        }
+        .await;
+
+        // Once text has been passed on, another model can't take over
+        let result = result.map_err(|e| {
+            if !text.is_empty() && matches!(e, WizardError::ModelUnavailable { .. }) {
+                WizardError::Api(e.to_string())
+            } else {
+                e
+            }
+        });
+        self.record_call(model, temperature, messages, &result);
+
+        let (status, body) = result?;
+        if !status.is_success() {
+            return Self::parse_chat_response(model, status, &body);
+        }
+        if text.trim().is_empty() {
+            return Err(WizardError::Parse(
+                "No response content from LLM".to_string(),
+            ));
+        }
+        Ok(text)
+    }
+
+    /// Parse one line of a streamed response, returning the text it adds, if any
+    ///
+    /// Blank lines, comments (such as OpenRouter's keep-alive messages) and the
+    /// final `data: [DONE]` add nothing; an `error` object fails the stream.
+    pub fn parse_stream_line(model: &str, line: &str) -> Result<Option<String>> {
+        let Some(data) = line.trim().strip_prefix("data:") else {
+            return Ok(None);
+        };
+        let data = data.trim();
+        if data.is_empty() || data == "[DONE]" {
+            return Ok(None);
+        }
+
+        let parsed: Value = serde_json::from_str(data).map_err(|e| {
+            WizardError::Parse(format!(
+                "Failed to parse streamed LLM API response: {}: {}",
+                e,
+                body_prefix(data.as_bytes())
+            ))
+        })?;
+
+        // Errors in mid-stream arrive as an event with an `error` object
+        if let Some(error) = parsed.get("error") {
+            let message = error["message"].as_str().unwrap_or("Unknown error");
+            if is_model_unavailable(reqwest::StatusCode::OK, message) {
+                return Err(WizardError::ModelUnavailable {
+                    model: model.to_string(),
+                    reason: message.to_string(),
+                });
+            }
+            return Err(WizardError::Api(format!("LLM API error: {}", message)));
+        }
+
+        Ok(parsed["choices"][0]["delta"]["content"]
+            .as_str()
+            .filter(|content| !content.is_empty())
+            .map(str::to_string))
+    }
+
+    /// Post a chat completion request to the API
+    ///
+    /// If no response arrives, the request is sent again up to `network_retries` times with
+    /// the same idempotency key, so a provider that already processed it can return the
+    /// first result instead of charging twice. Each retry counts against the retry budget.
+    async fn post_chat_request(
+        &self,
+        request: &ChatCompletionRequest,
+        context: &RequestContext,
+    ) -> Result<reqwest::Response> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        if let Some(api_key) = &self.config.api_key {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", api_key)).map_err(|_| {
+                    WizardError::Invalid("The API key contains invalid characters".to_string())
+                })?,
+            );
+        }
+        if let Some(header) = &self.config.idempotency_header {
+            let name = HeaderName::from_bytes(header.as_bytes()).map_err(|_| {
+                WizardError::Invalid(format!("Invalid idempotency header name: {}", header))
+            })?;
+            let key = HeaderValue::from_str(&context.idempotency_key)
+                .map_err(|_| WizardError::Invalid("Invalid idempotency key".to_string()))?;
+            headers.insert(name, key);
+        }
 
-            ## 6. Prompt Engineering Strategy
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content...*
+        let mut retries = 0;
+        loop {
+            let error = match self.send(&headers, request).await {
+                Ok(response) => return Ok(response),
+                Err(e) => e,
+            };
 
-            ## 7. Dataset Needs and Sources
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content...*
+            if retries >= self.config.network_retries {
+                return Err(error.into());
+            }
+            if let Some(budget) = &self.retry_budget
+                && !budget.try_consume()
+            {
+                self.progress
+                    .warn("Retry budget exhausted; not sending the request again");
+                return Err(error.into());
+            }
 
-            ## 8. Evaluation Metrics and Success Criteria
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content... (How will we know the project is successful?)*
+            self.progress
+                .warn(&format!("Warning: {}. Sending the request again.", error));
+            retries += 1;
+        }
+    }
 
-            ## 9. Scalability and Deployment
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content... (Initial thoughts on technical architecture and scaling)*
+    /// Send a GET request to an API endpoint
+    async fn get(&self, endpoint: &str) -> reqwest::Result<reqwest::Response> {
+        #[cfg(test)]
+        if let Some(script) = &self.script {
+            return script.reply_to_get().await;
+        }
 
-            ## 10. Ethical and Bias Considerations
-            - **Confidence: [1-5]/5** (Reason: ...)
-            - *Content...*
+        let mut request = self.client.get(format!("{}/{}", API_BASE_URL, endpoint));
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        request.send().await
+    }
 
-            ## 11. Open Questions and Missing Information
-            - **Confidence: N/A**
-            - *Based on the context, list the key pieces of information that are still needed to complete this project definition.*
-            "#,
-            context_str = context_str
-        );
+    /// Send a chat completion request once
+    async fn send(
+        &self,
+        headers: &HeaderMap,
+        request: &ChatCompletionRequest,
+    ) -> reqwest::Result<reqwest::Response> {
+        #[cfg(test)]
+        if let Some(script) = &self.script {
+            return script.reply(headers, request).await;
+        }
 
-        vec![
-            ChatMessage {
-                role: Role::System,
-                content: system_prompt.to_string(),
-            },
-            ChatMessage {
-                role: Role::User,
-                content: user_prompt,
-            },
-        ]
+        self.client
+            .post(format!("{}/chat/completions", API_BASE_URL))
+            .headers(headers.clone())
+            .json(request)
+            .send()
+            .await
     }
 
-    /// Send a chat request to the LLM API
-    async fn send_chat_request(&self, messages: Vec<ChatMessage>) -> Result<String> {
-        let request = ChatCompletionRequest {
-            model: self.config.model.clone(),
+    /// Record a call in the transcript, if one is being written; failing to do so is not fatal
+    fn record_call(
+        &self,
+        model: &str,
+        temperature: f32,
+        messages: &[ChatMessage],
+        result: &Result<(reqwest::StatusCode, String)>,
+    ) {
+        let Some(transcript) = &self.transcript else {
+            return;
+        };
+
+        let mut entry = TranscriptEntry {
+            model,
+            temperature,
             messages,
-            temperature: Some(self.config.temperature),
-            max_tokens: Some(self.config.max_tokens),
+            status: None,
+            response: None,
+            error: None,
         };
+        match result {
+            Ok((status, body)) => {
+                entry.status = Some(status.as_u16());
+                entry.response = Some(body);
+            }
+            Err(e) => entry.error = Some(e.to_string()),
+        }
 
-        // Create headers
-        let mut headers = HeaderMap::new();
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        if let Err(e) = transcript.record(&entry) {
+            self.progress.warn(&format!(
+                "Warning: could not write to the transcript: {}",
+                e
+            ));
+        }
+    }
 
-        if let Some(api_key) = &self.config.api_key {
-            headers.insert(
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {}", api_key))?,
-            );
+    /// Read a response body, failing clearly if it exceeds the configured size cap
+    async fn read_body(&self, mut response: reqwest::Response) -> Result<String> {
+        let limit = self.config.max_response_bytes;
+
+        if let Some(length) = response.content_length()
+            && length as usize > limit
+        {
+            return Err(WizardError::Api(format!(
+                "LLM API response is too large ({} bytes, limit is {} bytes)",
+                length, limit
+            )));
         }
 
-        // Send request to OpenRouter API
-        let response = self
-            .client
-            .post("https://openrouter.ai/api/v1/chat/completions")
-            .headers(headers)
-            .json(&request)
-            .send()
-            .await?
-            .json::<ChatCompletionResponse>()
-            .await?;
+        // Read in chunks so an oversized body is never fully buffered
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if bytes.len() + chunk.len() > limit {
+                return Err(WizardError::Api(format!(
+                    "LLM API response exceeded the size limit of {} bytes",
+                    limit
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        decode_body(bytes)
+    }
+
+    /// Parse a chat completion response body, classifying API errors
+    pub fn parse_chat_response(
+        model: &str,
+        status: reqwest::StatusCode,
+        body: &str,
+    ) -> Result<String> {
+        let parsed: Value = match serde_json::from_str(body) {
+            Ok(parsed) => parsed,
+            // Gateways answer errors with HTML or plain text; the status says what went wrong
+            Err(_) if !status.is_success() => Value::Null,
+            Err(e) => {
+                return Err(WizardError::Parse(format!(
+                    "Failed to parse LLM API response ({}): {}: {}",
+                    status,
+                    e,
+                    body_prefix(body.as_bytes())
+                )));
+            }
+        };
+
+        // OpenRouter reports errors in an `error` object, sometimes with a 200 status
+        if let Some(error) = parsed.get("error") {
+            let message = error["message"]
+                .as_str()
+                .unwrap_or("Unknown error")
+                .to_string();
+            let status = error["code"]
+                .as_u64()
+                .and_then(|code| reqwest::StatusCode::from_u16(code as u16).ok())
+                .unwrap_or(status);
 
+            if is_model_unavailable(status, &message) {
+                return Err(WizardError::ModelUnavailable {
+                    model: model.to_string(),
+                    reason: message,
+                });
+            }
+
+            return Err(WizardError::Api(format!(
+                "LLM API error ({}): {}",
+                status, message
+            )));
+        }
+
+        if !status.is_success() {
+            if is_model_unavailable(status, body) {
+                return Err(WizardError::ModelUnavailable {
+                    model: model.to_string(),
+                    reason: status.to_string(),
+                });
+            }
+
+            return Err(WizardError::Api(format!("LLM API error: {}", status)));
+        }
+
+        let response: ChatCompletionResponse =
+            serde_json::from_value(parsed).map_err(WizardError::json("LLM API response"))?;
         if let Some(choice) = response.choices.first() {
             return Ok(choice.message.content.clone());
         }
 
-        anyhow::bail!("No response content from LLM")
+        Err(WizardError::Parse(
+            "No response content from LLM".to_string(),
+        ))
     }
 
     /// Parse the LLM response to extract a question
     fn parse_question_response(&self, response: &str) -> Result<Question> {
         let response = response.trim().replace("```json", "").replace("```", "");
+
         // Try to parse the response as JSON
-        let local_now: DateTime<Local> = Local::now();
-        let formatted_local: String = local_now.format("%Y_%m_%d_%H_%M_%S").to_string();
+        let parsed: Value =
+            serde_json::from_str(response.as_str()).map_err(WizardError::json("LLM response"))?;
 
-        fs::File::create_new(format!("response_{}.json", formatted_local))
-            .unwrap()
-            .write_all(response.as_bytes())
-            .unwrap();
+        self.parse_question_value(&parsed, new_question_id())
+    }
+
+    /// Parse the LLM response to extract a batch of questions
+    ///
+    /// A single question object is accepted as a batch of one.
+    fn parse_question_batch_response(&self, response: &str) -> Result<Vec<Question>> {
+        let response = response.trim().replace("```json", "").replace("```", "");
+
+        let parsed: Value =
+            serde_json::from_str(response.as_str()).map_err(WizardError::json("LLM response"))?;
+
+        let questions = match &parsed {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| self.parse_question_value(item, new_question_id()))
+                .collect::<Result<Vec<_>>>()?,
+            _ => vec![self.parse_question_value(&parsed, new_question_id())?],
+        };
 
-        let parsed: Value = serde_json::from_str(response.as_str())
-            .map_err(|e| anyhow::anyhow!("Failed to parse LLM response as JSON: {}", e))?;
+        if questions.is_empty() {
+            return Err(WizardError::Parse(
+                "No questions in the LLM response".to_string(),
+            ));
+        }
+        Ok(questions)
+    }
 
+    /// Build a question from one parsed question object
+    fn parse_question_value(&self, parsed: &Value, id: String) -> Result<Question> {
         // Extract the question type
         let question_type = match parsed["question_type"].as_str() {
             Some("MultipleChoice") => QuestionType::MultipleChoice,
             Some("YesNo") => QuestionType::YesNo,
             Some("RatingScale") => QuestionType::RatingScale,
             Some("FreeText") => QuestionType::FreeText,
-            _ => anyhow::bail!("Invalid question type in LLM response"),
+            _ => {
+                return Err(WizardError::Parse(
+                    "Invalid question type in LLM response".to_string(),
+                ));
+            }
         };
 
         // Extract the question text
         let question_text = parsed["question_text"]
             .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Missing question_text in LLM response"))?
+            .ok_or_else(|| WizardError::Parse("Missing question_text in LLM response".to_string()))?
             .to_string();
 
-        // Generate a unique ID for the question
-        let id = format!("q_{}", chrono::Utc::now().timestamp());
-
         // Create the question based on the type
         let mut question = match question_type {
             QuestionType::MultipleChoice => {
                 let options = parsed["options"]
                     .as_array()
-                    .ok_or_else(|| anyhow::anyhow!("Missing options for MultipleChoice question"))?
-                    .iter()
-                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                    .collect::<Vec<_>>();
+                    .map(|options| {
+                        options
+                            .iter()
+                            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
 
-                Question::multiple_choice(id, question_text, options)
+                if !options.is_empty() {
+                    Question::multiple_choice(id, question_text, options)
+                } else if self.config.repair_questions {
+                    // Keep the question rather than failing the session over a malformed response
+                    self.progress.warn("Warning: The model sent a multiple choice question without options. Asking it as free text.");
+                    Question::free_text(id, question_text)
+                } else {
+                    return Err(WizardError::Parse(
+                        "Missing options for MultipleChoice question".to_string(),
+                    ));
+                }
             }
             QuestionType::YesNo => Question::yes_no(id, question_text),
             QuestionType::RatingScale => {
-                let scale = parsed["scale"]
-                    .as_array()
-                    .ok_or_else(|| anyhow::anyhow!("Missing scale for RatingScale question"))?;
+                let scale = parsed["scale"].as_array().ok_or_else(|| {
+                    WizardError::Parse("Missing scale for RatingScale question".to_string())
+                })?;
 
                 let min = scale[0]
                     .as_u64()
-                    .ok_or_else(|| anyhow::anyhow!("Invalid min value in scale"))?
+                    .ok_or_else(|| WizardError::Parse("Invalid min value in scale".to_string()))?
                     as u8;
 
                 let max = scale[1]
                     .as_u64()
-                    .ok_or_else(|| anyhow::anyhow!("Invalid max value in scale"))?
+                    .ok_or_else(|| WizardError::Parse("Invalid max value in scale".to_string()))?
                     as u8;
 
                 Question::rating_scale(id, question_text, min, max)
@@ -404,6 +2406,773 @@ impl LlmClient {
             question = question.with_help_text(help_text);
         }
 
+        // Tell the user why the question is being asked
+        if let Some(reason) = parsed["reason"].as_str()
+            && !reason.trim().is_empty()
+        {
+            question = question.with_reason(reason.trim());
+        }
+
+        // Keep answers that may contain personal data out of logs
+        if parsed["sensitive"].as_bool() == Some(true) {
+            question = question.with_sensitive(true);
+        }
+
+        // Offer the suggested answer as the default of free text questions
+        if matches!(question.question_type, QuestionType::FreeText)
+            && let Some(default_answer) = parsed["default_answer"].as_str()
+            && !default_answer.trim().is_empty()
+        {
+            question = question.with_default_answer(default_answer.trim());
+        }
+
         Ok(question)
     }
 }
+
+/// Canned API responses standing in for the LLM service in tests
+#[cfg(test)]
+pub(crate) mod scripted {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// One response of the scripted API
+    pub(crate) enum Reply {
+        /// A completion with the given message content
+        Content(String),
+        /// A response with the given status code and raw body
+        Raw(u16, String),
+        /// A request that fails before any response arrives
+        NetworkError,
+        /// Another reply, sent after a delay
+        Delayed(Duration, Box<Reply>),
+    }
+
+    impl Reply {
+        /// A completion with the given message content
+        pub(crate) fn content(content: impl Into<String>) -> Self {
+            Self::Content(content.into())
+        }
+
+        /// The same reply, sent after a delay
+        pub(crate) fn after(self, delay: Duration) -> Self {
+            Self::Delayed(delay, Box::new(self))
+        }
+    }
+
+    /// Request received by the scripted API
+    #[derive(Debug, Clone)]
+    pub(crate) struct Received {
+        /// The request headers
+        pub headers: HeaderMap,
+        /// The serialized request
+        pub body: Value,
+    }
+
+    impl Received {
+        /// Get the value of a request header, if it was sent
+        pub(crate) fn header(&self, name: &str) -> Option<&str> {
+            self.headers.get(name)?.to_str().ok()
+        }
+
+        /// Get the model the request was sent to
+        pub(crate) fn model(&self) -> &str {
+            self.body["model"].as_str().unwrap_or_default()
+        }
+
+        /// Get the text of all messages of the request
+        pub(crate) fn prompt(&self) -> String {
+            self.body["messages"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|message| message["content"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+
+    /// API answering each request with the next scripted reply
+    pub(crate) struct ScriptedApi {
+        /// Replies not sent yet
+        replies: Mutex<VecDeque<Reply>>,
+        /// Requests received so far
+        received: Mutex<Vec<Received>>,
+    }
+
+    impl ScriptedApi {
+        /// Create an API sending the given replies in order
+        pub(crate) fn new(replies: impl IntoIterator<Item = Reply>) -> Self {
+            Self {
+                replies: Mutex::new(replies.into_iter().collect()),
+                received: Mutex::new(Vec::new()),
+            }
+        }
+
+        /// Get the requests received so far
+        pub(crate) fn received(&self) -> Vec<Received> {
+            self.received.lock().unwrap().clone()
+        }
+
+        /// Record a request and send the next reply
+        pub(super) async fn reply(
+            &self,
+            headers: &HeaderMap,
+            request: &ChatCompletionRequest,
+        ) -> reqwest::Result<reqwest::Response> {
+            self.received.lock().unwrap().push(Received {
+                headers: headers.clone(),
+                body: serde_json::to_value(request).unwrap(),
+            });
+            self.send_next().await
+        }
+
+        /// Send the next reply to a GET request, which is not recorded
+        pub(super) async fn reply_to_get(&self) -> reqwest::Result<reqwest::Response> {
+            self.send_next().await
+        }
+
+        /// Send the next reply, once its delay is over
+        async fn send_next(&self) -> reqwest::Result<reqwest::Response> {
+            let mut reply = self
+                .replies
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("no scripted reply left");
+
+            while let Reply::Delayed(delay, next) = reply {
+                tokio::time::sleep(delay).await;
+                reply = *next;
+            }
+
+            let (status, body) = match reply {
+                Reply::Content(content) => {
+                    let body = serde_json::json!({
+                        "choices": [{ "message": { "role": "assistant", "content": content } }]
+                    });
+                    (200, body.to_string())
+                }
+                Reply::Raw(status, body) => (status, body),
+                // A request without a host fails like an unreachable server would
+                Reply::NetworkError => return reqwest::Client::new().get("http://").send().await,
+                Reply::Delayed(..) => unreachable!("delays are waited out above"),
+            };
+
+            let response = http::Response::builder().status(status).body(body).unwrap();
+            Ok(response.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scripted::Reply;
+    use super::*;
+
+    #[test]
+    fn temperature_schedule_decreases_with_progress() {
+        let config = LlmConfig {
+            temperature_schedule: Some(TemperatureSchedule::parse("1.2:0.4").unwrap()),
+            ..LlmConfig::default()
+        };
+
+        let temperatures: Vec<f32> = [0.0, 0.25, 0.5, 0.75, 1.0]
+            .into_iter()
+            .map(|progress| config.question_temperature(progress))
+            .collect();
+
+        assert!(temperatures.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!((temperatures[0] - 1.2).abs() < 1e-6);
+        assert!((temperatures[4] - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn temperature_is_constant_without_schedule() {
+        let config = LlmConfig::default();
+
+        assert_eq!(config.question_temperature(0.0), config.temperature);
+        assert_eq!(config.question_temperature(1.0), config.temperature);
+    }
+
+    /// Messages of a request whose content doesn't matter to the test
+    fn user_message(content: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage {
+            role: Role::User,
+            content: content.to_string(),
+        }]
+    }
+
+    /// Join the text of all messages of a prompt
+    fn prompt_text(messages: Vec<ChatMessage>) -> String {
+        messages
+            .into_iter()
+            .map(|message| message.content)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[tokio::test]
+    async fn unavailable_primary_model_falls_back() {
+        let (mut client, script) = LlmClient::scripted([
+            Reply::Raw(
+                404,
+                r#"{"error":{"message":"No endpoints found for primary/model"}}"#.to_string(),
+            ),
+            Reply::content("Hello from the fallback"),
+        ]);
+        client.config.model = "primary/model".to_string();
+        client.config.fallback_models = vec!["fallback/model".to_string()];
+
+        let response = client
+            .send_chat_request(user_message("Hello"), 0.7)
+            .await
+            .unwrap();
+
+        assert_eq!(response, "Hello from the fallback");
+        let models: Vec<String> = script
+            .received()
+            .iter()
+            .map(|request| request.model().to_string())
+            .collect();
+        assert_eq!(models, ["primary/model", "fallback/model"]);
+    }
+
+    #[test]
+    fn models_response_formats_as_table() {
+        let body = r#"{"data": [
+            {"id": "openai/gpt-4o", "context_length": 128000,
+             "pricing": {"prompt": "0.0000025", "completion": "0.00001"}},
+            {"id": "meta/llama:free", "context_length": 8192,
+             "pricing": {"prompt": "0", "completion": "0"}},
+            {"id": "unknown/model"}
+        ]}"#;
+
+        let models = parse_models_response(body).unwrap();
+
+        assert_eq!(
+            format_models_table(&models),
+            "MODEL             PROMPT/1M   OUTPUT/1M     CONTEXT\n\
+             openai/gpt-4o         $2.50      $10.00      128000\n\
+             meta/llama:free        free        free        8192\n\
+             unknown/model             ?           ?           ?\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_response_fails_with_the_size_cap() {
+        let (mut client, _) = LlmClient::scripted([Reply::content("x".repeat(4096))]);
+        client.config.max_response_bytes = 1024;
+
+        let error = client
+            .send_chat_request(user_message("Hello"), 0.7)
+            .await
+            .unwrap_err();
+
+        assert!(
+            error.to_string().contains("limit is 1024 bytes")
+                || error.to_string().contains("size limit of 1024 bytes"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn seed_is_sent_only_when_set() {
+        let (client, seeded) = LlmClient::scripted([Reply::content("Hi")]);
+        let client = client.with_seed(Some(42));
+        client
+            .send_chat_request(user_message("Hello"), 0.7)
+            .await
+            .unwrap();
+        let (client, unseeded) = LlmClient::scripted([Reply::content("Hi")]);
+        client
+            .send_chat_request(user_message("Hello"), 0.7)
+            .await
+            .unwrap();
+
+        assert_eq!(seeded.received()[0].body["seed"], 42);
+        assert!(unseeded.received()[0].body.get("seed").is_none());
+    }
+
+    #[test]
+    fn covered_topics_are_listed_in_the_question_prompt() {
+        let mut context = Context::new();
+        context
+            .add_answer(
+                Question::free_text("q1", "Which payment providers must be supported?"),
+                "Stripe",
+            )
+            .unwrap();
+        let (client, _) = LlmClient::scripted([]);
+
+        let prompt = |client: &LlmClient| prompt_text(client.create_question_prompt(&context));
+
+        let without = prompt(&client);
+        let with = prompt(&client.with_avoid_covered_topics(true));
+        assert!(!without.contains("Topics already covered"));
+        let covered = with
+            .lines()
+            .find(|line| line.starts_with("Topics already covered"))
+            .unwrap();
+        assert!(covered.contains("payment"), "{}", covered);
+        assert!(covered.contains("providers"), "{}", covered);
+    }
+
+    #[test]
+    fn compliance_persona_prompt_has_its_own_example() {
+        let (client, _) = LlmClient::scripted([]);
+        let prompt_for = |persona: Persona| {
+            let mut context = Context::new();
+            context.persona = persona;
+            prompt_text(client.create_question_prompt(&context))
+        };
+        let example = "Which data protection regulations apply to the patient data";
+
+        assert!(prompt_for(Persona::ComplianceOfficer).contains(example));
+        assert!(!prompt_for(Persona::UxDesigner).contains(example));
+    }
+
+    #[tokio::test]
+    async fn retry_budget_caps_attempts_across_questions() {
+        let question = || {
+            Reply::content(
+                serde_json::json!({ "question_type": "FreeText", "question_text": "Who?" })
+                    .to_string(),
+            )
+        };
+        let (client, api) = LlmClient::scripted([
+            Reply::content("not json"),
+            question(),
+            Reply::content("not json"),
+            question(),
+            Reply::content("not json"),
+        ]);
+        let client = client.with_retry_budget(Some(2));
+        let context = Context::new();
+
+        assert!(client.generate_question(&context, 0.0).await.is_ok());
+        assert!(client.generate_question(&context, 0.5).await.is_ok());
+        assert_eq!(client.retries_remaining(), Some(0));
+        assert!(client.generate_question(&context, 1.0).await.is_err());
+
+        assert_eq!(api.received().len(), 5);
+    }
+
+    #[test]
+    fn over_budget_context_is_trimmed_in_the_definition_prompt() {
+        let mut context = Context::new();
+        let detail =
+            "Nurses on night shifts who triage walk-in patients and need quick answers. ".repeat(4);
+        context
+            .add_answer(
+                Question::free_text("q1", "Who are the users?"),
+                detail.as_str(),
+            )
+            .unwrap();
+        for i in 2..=12 {
+            context
+                .add_answer(
+                    Question::free_text(format!("q{}", i), format!("Filler question {}?", i)),
+                    "ok",
+                )
+                .unwrap();
+        }
+        let (mut client, _) = LlmClient::scripted([]);
+        client.config.max_definition_context_chars = 600;
+        assert!(context.get_context_string().chars().count() > 600);
+
+        let prompt = prompt_text(
+            client.create_project_definition_prompt(&context, 0..DEFINITION_SECTIONS.len()),
+        );
+
+        let trimmed = context.get_trimmed_context_string(600);
+        assert!(trimmed.chars().count() <= 600);
+        assert!(prompt.contains(&trimmed));
+        assert!(prompt.contains("lower-signal answers omitted to fit the prompt"));
+        assert!(prompt.contains(detail.trim_end()));
+        assert!(!prompt.contains("Filler question 2?"));
+    }
+
+    #[test]
+    fn doc_style_instruction_reaches_the_definition_prompt() {
+        let (client, _) = LlmClient::scripted([]);
+        let context = Context::new();
+        let sections = 0..DEFINITION_SECTIONS.len();
+
+        let default =
+            prompt_text(client.create_project_definition_prompt(&context, sections.clone()));
+        let client = client.with_doc_style(Some(DocStyle::Bulleted));
+        let bulleted = prompt_text(client.create_project_definition_prompt(&context, sections));
+
+        assert!(bulleted.contains(DocStyle::Bulleted.instruction()));
+        for style in [DocStyle::Concise, DocStyle::Narrative, DocStyle::Bulleted] {
+            assert!(!default.contains(style.instruction()));
+        }
+    }
+
+    #[test]
+    fn multiple_choice_without_options_is_repaired_to_free_text() {
+        let response = serde_json::json!({
+            "question_type": "MultipleChoice",
+            "question_text": "Which platforms must be supported?"
+        })
+        .to_string();
+        let (mut client, _) = LlmClient::scripted([]);
+
+        assert!(client.parse_question_response(&response).is_err());
+        client.config.repair_questions = true;
+        let question = client.parse_question_response(&response).unwrap();
+
+        assert!(matches!(question.question_type, QuestionType::FreeText));
+        assert_eq!(question.text, "Which platforms must be supported?");
+        assert!(question.options.is_none());
+    }
+
+    #[tokio::test]
+    async fn configured_retry_instruction_is_sent_after_a_parse_failure() {
+        let (mut client, api) = LlmClient::scripted([
+            Reply::content("Sure! Here is a question: who are the users?"),
+            Reply::content(
+                serde_json::json!({ "question_type": "FreeText", "question_text": "Who are the users?" })
+                    .to_string(),
+            ),
+        ]);
+        client.config.json_retry_instruction = "Answer with the JSON object only.".to_string();
+        client.config.json_retry_max = 2;
+
+        let question = client
+            .generate_question(&Context::new(), 0.0)
+            .await
+            .unwrap();
+
+        assert_eq!(question.text, "Who are the users?");
+        let retry = &api.received()[1].body["messages"];
+        let correction = retry.as_array().unwrap().last().unwrap()["content"]
+            .as_str()
+            .unwrap();
+        assert!(
+            correction.starts_with("Answer with the JSON object only.\n\nThe problem was: "),
+            "{}",
+            correction
+        );
+        assert_eq!(retry.as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn no_confidence_prompt_leaves_out_the_confidence_instructions() {
+        let (client, _) = LlmClient::scripted([]);
+        let context = Context::new();
+        let sections = 0..DEFINITION_SECTIONS.len();
+
+        let scored =
+            prompt_text(client.create_project_definition_prompt(&context, sections.clone()));
+        let client = client.with_omit_confidence(true);
+        let unscored = prompt_text(client.create_project_definition_prompt(&context, sections));
+
+        assert!(scored.contains("Confidence Score"));
+        assert!(scored.contains("**Confidence: [1-5]/5**"));
+        assert!(!unscored.contains("Confidence Score"));
+        assert!(!unscored.contains("**Confidence:"));
+        assert!(!unscored.contains("justification"));
+        assert!(unscored.contains("Do not add confidence scores"));
+    }
+
+    #[tokio::test]
+    async fn over_limit_max_tokens_is_clamped_for_a_known_model() {
+        let (mut client, api) = LlmClient::scripted([Reply::content("Hi"), Reply::content("Hi")]);
+        client.config.max_tokens = 16000;
+        client.config.model = "openai/gpt-4:free".to_string();
+
+        client
+            .send_chat_request(user_message("Hello"), 0.0)
+            .await
+            .unwrap();
+        client.config.model = "some/unknown-model".to_string();
+        client
+            .send_chat_request(user_message("Hello"), 0.0)
+            .await
+            .unwrap();
+
+        let received = api.received();
+        assert_eq!(received[0].body["max_tokens"], 4096);
+        assert_eq!(received[1].body["max_tokens"], 16000);
+        client.config.model = "anthropic/claude-3.5-sonnet".to_string();
+        assert_eq!(
+            client.max_tokens_warnings(),
+            [
+                "max_tokens 16000 is above the output limit of anthropic/claude-3.5-sonnet (8192); using 8192"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn over_limit_question_prompt_is_trimmed_before_sending() {
+        let mut context = Context::new();
+        let detail =
+            "Nurses on night shifts who triage walk-in patients and need quick answers. ".repeat(4);
+        context
+            .add_answer(
+                Question::free_text("q1", "Who are the users?"),
+                detail.as_str(),
+            )
+            .unwrap();
+        for i in 2..=40 {
+            context
+                .add_answer(
+                    Question::free_text(format!("q{}", i), format!("Filler question {}?", i)),
+                    "ok",
+                )
+                .unwrap();
+        }
+        let (mut client, api) = LlmClient::scripted([
+            Reply::content(
+                serde_json::json!({ "question_type": "FreeText", "question_text": "What is the goal?" })
+                    .to_string(),
+            ),
+        ]);
+        let untrimmed = prompt_text(client.create_question_prompt(&context))
+            .chars()
+            .count();
+        client.config.max_question_prompt_chars = untrimmed - 400;
+
+        client.generate_question(&context, 0.0).await.unwrap();
+
+        // The system and user messages are joined by a line break
+        let sent = api.received()[0].prompt();
+        assert!(sent.chars().count() <= client.config.max_question_prompt_chars + 1);
+        assert!(sent.contains(detail.trim_end()));
+        assert!(!sent.contains("Filler question 2?"));
+        assert!(sent.contains("Filler question 40?"));
+    }
+
+    #[tokio::test]
+    async fn stop_sequences_are_sent_only_when_configured() {
+        let (client, unset) = LlmClient::scripted([Reply::content("Hi")]);
+        client
+            .send_chat_request(user_message("Hello"), 0.0)
+            .await
+            .unwrap();
+        let (mut client, set) = LlmClient::scripted([Reply::content("Hi")]);
+        client.config.stop = Some(vec!["\n\n".to_string(), "```".to_string()]);
+        client
+            .send_chat_request(user_message("Hello"), 0.0)
+            .await
+            .unwrap();
+
+        assert!(unset.received()[0].body.get("stop").is_none());
+        assert_eq!(
+            set.received()[0].body["stop"],
+            serde_json::json!(["\n\n", "```"])
+        );
+    }
+
+    #[test]
+    fn selected_flow_instruction_reaches_the_question_prompt() {
+        let (client, _) = LlmClient::scripted([]);
+        let context = Context::new();
+
+        let unset = prompt_text(client.create_question_prompt(&context));
+        let client = client.with_flow(Some(InterviewFlow::Breadth));
+        let breadth = prompt_text(client.create_question_prompt(&context));
+        let client = client.with_flow(Some(InterviewFlow::Depth));
+        let depth = prompt_text(client.create_question_prompt(&context));
+
+        assert!(breadth.contains(&InterviewFlow::Breadth.instruction()));
+        assert!(breadth.contains(DEFINITION_SECTIONS[0].title));
+        assert!(!breadth.contains(&InterviewFlow::Depth.instruction()));
+        assert!(depth.contains(&InterviewFlow::Depth.instruction()));
+        assert!(!unset.contains("breadth-first") && !unset.contains("depth-first"));
+    }
+
+    #[tokio::test]
+    async fn question_about_an_avoided_topic_is_regenerated() {
+        let question = |text: &str| {
+            Reply::content(
+                serde_json::json!({ "question_type": "FreeText", "question_text": text })
+                    .to_string(),
+            )
+        };
+        let (client, api) = LlmClient::scripted([
+            question("What is your BUDGET for the first release?"),
+            question("Who are the users?"),
+        ]);
+        let client = client.with_avoided_topics(vec!["budget".to_string()]);
+
+        let question = client
+            .generate_question(&Context::new(), 0.0)
+            .await
+            .unwrap();
+
+        assert_eq!(question.text, "Who are the users?");
+        let received = api.received();
+        assert_eq!(received.len(), 2);
+        assert!(received[0].prompt().contains(
+            "NEVER ask about these topics, the user asked not to be asked about them: budget."
+        ));
+    }
+
+    #[tokio::test]
+    async fn generated_questions_get_distinct_ids() {
+        let question = serde_json::json!({ "question_type": "FreeText", "question_text": "Who are the users?" });
+        let (client, _) = LlmClient::scripted([
+            Reply::content(question.to_string()),
+            Reply::content(question.to_string()),
+            Reply::content(serde_json::json!([question, question]).to_string()),
+        ]);
+        let context = Context::new();
+
+        let mut ids = vec![
+            client.generate_question(&context, 0.0).await.unwrap().id,
+            client.generate_question(&context, 0.0).await.unwrap().id,
+        ];
+        ids.extend(
+            client
+                .generate_question_batch(&context, 0.0, 2)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|question| question.id),
+        );
+
+        assert!(ids.iter().all(|id| id.starts_with("q_")), "{:?}", ids);
+        let mut distinct = ids.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 4, "{:?}", ids);
+    }
+
+    #[tokio::test]
+    async fn question_reason_is_parsed_and_documented_in_the_prompt() {
+        let question = |reason: &str| {
+            Reply::content(
+                serde_json::json!({
+                    "question_type": "FreeText",
+                    "question_text": "Who are the users?",
+                    "reason": reason
+                })
+                .to_string(),
+            )
+        };
+        let (client, api) = LlmClient::scripted([
+            question(" The target users shape every other section. "),
+            question("   "),
+        ]);
+
+        let with_reason = client
+            .generate_question(&Context::new(), 0.0)
+            .await
+            .unwrap();
+        let blank_reason = client
+            .generate_question(&Context::new(), 0.0)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            with_reason.reason.as_deref(),
+            Some("The target users shape every other section.")
+        );
+        assert_eq!(blank_reason.reason, None);
+        assert!(api.received()[0].prompt().contains("\"reason\":"));
+    }
+
+    #[tokio::test]
+    async fn network_retry_reuses_the_idempotency_key() {
+        let (mut client, api) = LlmClient::scripted([
+            Reply::NetworkError,
+            Reply::content("Hi"),
+            Reply::content("Hi again"),
+        ]);
+        client.config.idempotency_header = Some("Idempotency-Key".to_string());
+        client.config.network_retries = 1;
+
+        let reply = client
+            .send_chat_request(user_message("Hello"), 0.0)
+            .await
+            .unwrap();
+        client
+            .send_chat_request(user_message("Hello"), 0.0)
+            .await
+            .unwrap();
+
+        assert_eq!(reply, "Hi");
+        let received = api.received();
+        let keys: Vec<&str> = received
+            .iter()
+            .map(|received| received.header("Idempotency-Key").unwrap())
+            .collect();
+        assert!(keys[0].starts_with("projector-"), "{:?}", keys);
+        assert_eq!(keys[0], keys[1]);
+        assert_ne!(keys[1], keys[2]);
+    }
+
+    /// Context with fixed hints, domain and answers, so prompts built from it never change
+    fn snapshot_context(persona: Persona) -> Context {
+        let mut context = Context::with_domain("Healthcare").with_persona(persona);
+        context.starting_hints = Some("A triage assistant for a rural clinic.".to_string());
+        context
+            .add_answer(
+                Question::free_text("q1", "Who are the primary users?"),
+                "Nurses working night shifts",
+            )
+            .unwrap();
+        context
+            .add_answer(
+                Question::multiple_choice(
+                    "q2",
+                    "Where will it run?",
+                    vec!["Web".to_string(), "Mobile".to_string()],
+                ),
+                "Mobile",
+            )
+            .unwrap();
+        context
+    }
+
+    /// Render a prompt with the role of each message, as reviewed in the snapshots
+    fn snapshot_text(messages: Vec<ChatMessage>) -> String {
+        messages
+            .into_iter()
+            .map(|message| format!("--- {:?} ---\n{}", message.role, message.content))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// All personas, in the order of the snapshots
+    const PERSONAS: [Persona; 5] = [
+        Persona::Default,
+        Persona::ProductManager,
+        Persona::LlmArchitect,
+        Persona::UxDesigner,
+        Persona::ComplianceOfficer,
+    ];
+
+    #[test]
+    fn question_prompt_snapshots_for_each_persona() {
+        let (client, _) = LlmClient::scripted([]);
+
+        for persona in PERSONAS {
+            let name = format!("question_prompt_{:?}", persona);
+            let prompt = client.create_question_prompt(&snapshot_context(persona));
+
+            insta::assert_snapshot!(name, snapshot_text(prompt));
+        }
+    }
+
+    #[test]
+    fn definition_prompt_snapshots_for_each_persona() {
+        let (client, _) = LlmClient::scripted([]);
+
+        for persona in PERSONAS {
+            let name = format!("definition_prompt_{:?}", persona);
+            let prompt = client.create_project_definition_prompt(
+                &snapshot_context(persona),
+                0..DEFINITION_SECTIONS.len(),
+            );
+
+            insta::assert_snapshot!(name, snapshot_text(prompt));
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Metrics module for the LLM-powered project definition wizard.
+//!
+//! This module computes statistics about an interview, such as the mix of
+//! question types asked, for the optional metrics appendix and JSON export.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::Context;
+use super::question::QuestionType;
+use super::session::Session;
+
+/// Key used for sessions without a domain
+const NO_DOMAIN: &str = "(none)";
+
+/// Number of questions asked of each type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct QuestionTypeCounts {
+    /// Multiple choice questions
+    pub multiple_choice: usize,
+    /// Yes/No questions
+    pub yes_no: usize,
+    /// Rating scale questions
+    pub rating_scale: usize,
+    /// Free text questions
+    pub free_text: usize,
+}
+
+impl QuestionTypeCounts {
+    /// Count one more question of the given type
+    pub fn add(&mut self, question_type: &QuestionType) {
+        match question_type {
+            QuestionType::MultipleChoice => self.multiple_choice += 1,
+            QuestionType::YesNo => self.yes_no += 1,
+            QuestionType::RatingScale => self.rating_scale += 1,
+            QuestionType::FreeText => self.free_text += 1,
+        }
+    }
+
+    /// Get the total number of questions counted
+    pub fn total(&self) -> usize {
+        self.multiple_choice + self.yes_no + self.rating_scale + self.free_text
+    }
+
+    /// Get the counts with a display label for each type, in a fixed order
+    pub fn labeled(&self) -> [(&'static str, usize); 4] {
+        [
+            ("Multiple choice", self.multiple_choice),
+            ("Yes/No", self.yes_no),
+            ("Rating scale", self.rating_scale),
+            ("Free text", self.free_text),
+        ]
+    }
+}
+
+/// Statistics about the questions asked in a session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SessionMetrics {
+    /// Number of questions in the history
+    pub questions: usize,
+    /// Number of questions that were skipped
+    pub skipped: usize,
+    /// Number of answers that were edited afterwards
+    pub edited: usize,
+    /// Number of questions of each type
+    pub question_types: QuestionTypeCounts,
+}
+
+impl SessionMetrics {
+    /// Compute the metrics from the session history
+    pub fn from_context(context: &Context) -> Self {
+        let mut metrics = Self::default();
+
+        for answer in &context.history {
+            metrics.questions += 1;
+            if answer.skipped {
+                metrics.skipped += 1;
+            }
+            if !answer.revisions.is_empty() {
+                metrics.edited += 1;
+            }
+            metrics.question_types.add(&answer.question.question_type);
+        }
+
+        metrics
+    }
+
+    /// Render the metrics as a Markdown appendix
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("## Appendix: Session Metrics\n\n");
+        markdown.push_str(&format!(
+            "- Questions asked: {} ({} skipped, {} edited)\n\n",
+            self.questions, self.skipped, self.edited
+        ));
+
+        markdown.push_str("| Question type | Count | Share |\n");
+        markdown.push_str("|---|---|---|\n");
+        let total = self.question_types.total();
+        for (label, count) in self.question_types.labeled() {
+            let share = (count * 100 + total / 2).checked_div(total).unwrap_or(0);
+            markdown.push_str(&format!("| {} | {} | {}% |\n", label, count, share));
+        }
+
+        markdown
+    }
+}
+
+/// Aggregate statistics over a set of saved sessions
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SessionStats {
+    /// Number of sessions
+    pub sessions: usize,
+    /// Number of sessions in each domain
+    pub domains: BTreeMap<String, usize>,
+    /// Number of sessions using each persona
+    pub personas: BTreeMap<String, usize>,
+    /// Average number of questions asked per session
+    pub average_questions: f32,
+    /// Number of sessions with a stored project definition
+    pub definitions: usize,
+    /// Average section confidence (1-5) over the stored definitions with confidence scores, if any
+    pub average_confidence: Option<f32>,
+    /// Number of questions of each type, across all sessions
+    pub question_types: QuestionTypeCounts,
+}
+
+impl SessionStats {
+    /// Compute the statistics for the given sessions
+    pub fn from_sessions<'a>(sessions: impl IntoIterator<Item = &'a Session>) -> Self {
+        let mut stats = Self::default();
+        let mut questions = 0;
+        let mut confidence_sum = 0.0;
+        let mut scored_definitions = 0;
+
+        for session in sessions {
+            let context = &session.context;
+            stats.sessions += 1;
+
+            let domain = context.domain.as_deref().unwrap_or(NO_DOMAIN);
+            *stats.domains.entry(domain.to_string()).or_default() += 1;
+            *stats
+                .personas
+                .entry(context.persona.to_string())
+                .or_default() += 1;
+
+            let metrics = SessionMetrics::from_context(context);
+            questions += metrics.questions;
+            let types = metrics.question_types;
+            stats.question_types.multiple_choice += types.multiple_choice;
+            stats.question_types.yes_no += types.yes_no;
+            stats.question_types.rating_scale += types.rating_scale;
+            stats.question_types.free_text += types.free_text;
+
+            // Each definition counts once, however many sections it has
+            if let Some(definition) = &session.definition
+                && !definition.sections.is_empty()
+            {
+                stats.definitions += 1;
+
+                // Definitions generated without confidence scores don't count towards the average
+                if let Some(average) = definition.average_confidence() {
+                    confidence_sum += average;
+                    scored_definitions += 1;
+                }
+            }
+        }
+
+        if stats.sessions > 0 {
+            stats.average_questions = questions as f32 / stats.sessions as f32;
+        }
+        if scored_definitions > 0 {
+            stats.average_confidence = Some(confidence_sum / scored_definitions as f32);
+        }
+
+        stats
+    }
+
+    /// Get the question types with their counts, most common first
+    pub fn question_types_by_frequency(&self) -> Vec<(&'static str, usize)> {
+        let mut types = self.question_types.labeled().to_vec();
+        types.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        types
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::Question;
+    use crate::wizard::context::Persona;
+    use crate::wizard::output::{ConfidenceLevel, ProjectDefinition};
+    use crate::wizard::session::load_sessions_in_dir;
+
+    #[test]
+    fn question_type_distribution_matches_the_history() {
+        let mut context = Context::new();
+        let questions = [
+            Question::free_text("q1", "Who are the users?"),
+            Question::free_text("q2", "What is the goal?"),
+            Question::yes_no("q3", "Is there a deadline?"),
+            Question::rating_scale("q4", "How urgent is it?", 1, 5),
+        ];
+        for question in questions {
+            context.add_answer(question, "An answer").unwrap();
+        }
+        context.edit_answer(0, "Nurses").unwrap();
+
+        let metrics = SessionMetrics::from_context(&context);
+
+        assert_eq!(
+            metrics.question_types,
+            QuestionTypeCounts {
+                multiple_choice: 0,
+                yes_no: 1,
+                rating_scale: 1,
+                free_text: 2,
+            }
+        );
+        assert_eq!(metrics.edited, 1);
+        assert!(metrics.to_markdown().contains("| Free text | 2 | 50% |\n"));
+        let json = serde_json::to_value(&metrics).unwrap();
+        assert_eq!(json["question_types"]["free_text"], 2);
+    }
+
+    #[test]
+    fn stats_aggregate_a_directory_of_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let sessions = [
+            ("a.json", Some("Healthcare"), Persona::ComplianceOfficer, 2),
+            ("b.json", Some("Healthcare"), Persona::Default, 1),
+            ("c.json", None, Persona::Default, 3),
+        ];
+        for (file, domain, persona, answers) in sessions {
+            let mut session = Session::new();
+            session.context.domain = domain.map(str::to_string);
+            session.context.persona = persona;
+            for i in 0..answers {
+                let question = Question::free_text(format!("q{}", i), "Who are the users?");
+                session.context.add_answer(question, "Nurses").unwrap();
+            }
+            session.save_to_file(dir.path().join(file)).unwrap();
+        }
+        let mut session = Session::load_from_file(dir.path().join("a.json")).unwrap();
+        session
+            .context
+            .add_answer(Question::yes_no("q9", "Is there a deadline?"), "No")
+            .unwrap();
+        let mut definition = ProjectDefinition::new("Triage Bot");
+        definition.add_section("Target Users", "Nurses.", ConfidenceLevel::Low);
+        definition.add_section("Use Cases", "Symptom triage.", ConfidenceLevel::High);
+        session.definition = Some(definition);
+        session.save_to_file(dir.path().join("a.json")).unwrap();
+        std::fs::write(dir.path().join("broken.json"), "{ not a session").unwrap();
+
+        let loaded = load_sessions_in_dir(dir.path()).unwrap();
+        let stats = SessionStats::from_sessions(loaded.sessions.iter().map(|(_, session)| session));
+
+        assert_eq!(loaded.skipped.len(), 1);
+        assert_eq!(stats.sessions, 3);
+        assert_eq!(
+            stats.domains,
+            BTreeMap::from([("(none)".to_string(), 1), ("Healthcare".to_string(), 2)])
+        );
+        assert_eq!(
+            stats.personas,
+            BTreeMap::from([
+                ("Compliance Officer".to_string(), 1),
+                ("Default".to_string(), 2)
+            ])
+        );
+        assert_eq!(stats.average_questions, 7.0 / 3.0);
+        assert_eq!(stats.definitions, 1);
+        assert_eq!(stats.average_confidence, Some(3.0));
+        assert_eq!(stats.question_types_by_frequency()[0], ("Free text", 6));
+        assert_eq!(stats.question_types_by_frequency()[1], ("Yes/No", 1));
+    }
+}
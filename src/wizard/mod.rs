@@ -3,16 +3,30 @@
 //! This module contains the core functionality for the wizard,
 //! including session management, question generation, and output formatting.
 
+pub mod atomic;
 pub mod config;
 pub mod context;
+pub mod doctor;
+pub mod error;
+pub mod language;
 pub mod llm;
+pub mod metrics;
+pub mod moderation;
 pub mod output;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+pub mod portfolio;
+pub mod prefill;
+pub mod progress;
 pub mod question;
+pub mod report;
 pub mod session;
 pub mod template;
+pub mod transcript;
 
 pub use config::Config;
 pub use context::Context;
+pub use error::WizardError;
 pub use llm::LlmClient;
 pub use output::OutputGenerator;
 pub use question::Question;
@@ -0,0 +1,326 @@
+//! Moderation module for the LLM-powered project definition wizard.
+//!
+//! This module checks the user's answers before they are sent to the model,
+//! for deployments (e.g. medical or legal) where some content must not leave
+//! the machine. A flagged answer is either sent with a warning or refused,
+//! depending on the configured policy.
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::WizardError;
+use super::error::Result;
+
+/// Endpoint of the OpenAI moderation API
+pub const OPENAI_MODERATION_URL: &str = "https://api.openai.com/v1/moderations";
+
+/// Environment variable holding the OpenAI API key used for moderation
+pub const OPENAI_API_KEY_VAR: &str = "OPENAI_API_KEY";
+
+/// Moderation model used when none is configured
+const DEFAULT_OPENAI_MODERATION_MODEL: &str = "omni-moderation-latest";
+
+/// Result of checking a text
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModerationVerdict {
+    /// Whether the text contains disallowed content
+    pub flagged: bool,
+    /// Categories the text was flagged for, e.g. `self-harm`
+    pub categories: Vec<String>,
+}
+
+/// Service that checks answers for disallowed content
+pub trait Moderator: Send + Sync {
+    /// Check a text; by default nothing is flagged
+    fn check<'a>(&'a self, _text: &'a str) -> BoxFuture<'a, Result<ModerationVerdict>> {
+        Box::pin(async { Ok(ModerationVerdict::default()) })
+    }
+}
+
+/// Moderator that lets every answer through
+pub struct NoModeration;
+
+impl Moderator for NoModeration {}
+
+/// Moderator backed by the OpenAI moderation endpoint
+pub struct OpenAiModerator {
+    /// The HTTP client
+    client: reqwest::Client,
+    /// OpenAI API key
+    api_key: String,
+    /// Moderation model
+    model: String,
+}
+
+impl OpenAiModerator {
+    /// Create a moderator using the given API key and model, or the latest moderation model
+    pub fn new(api_key: impl Into<String>, model: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key: api_key.into(),
+            model: model.unwrap_or_else(|| DEFAULT_OPENAI_MODERATION_MODEL.to_string()),
+        }
+    }
+
+    /// Parse the response of the moderation endpoint
+    pub fn parse_response(body: &str) -> Result<ModerationVerdict> {
+        let parsed: Value =
+            serde_json::from_str(body).map_err(WizardError::json("moderation response"))?;
+        let result = parsed["results"]
+            .get(0)
+            .ok_or_else(|| WizardError::Parse("Moderation response has no results".to_string()))?;
+
+        let categories = result["categories"]
+            .as_object()
+            .map(|categories| {
+                categories
+                    .iter()
+                    .filter(|(_, flagged)| flagged.as_bool() == Some(true))
+                    .map(|(category, _)| category.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(ModerationVerdict {
+            flagged: result["flagged"].as_bool().unwrap_or(false),
+            categories,
+        })
+    }
+}
+
+impl Moderator for OpenAiModerator {
+    fn check<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<ModerationVerdict>> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .post(OPENAI_MODERATION_URL)
+                .bearer_auth(&self.api_key)
+                .json(&serde_json::json!({ "model": self.model, "input": text }))
+                .send()
+                .await?;
+
+            let status = response.status();
+            let body = response.text().await?;
+            if !status.is_success() {
+                return Err(WizardError::Api(format!(
+                    "Moderation API error ({}): {}",
+                    status, body
+                )));
+            }
+            Self::parse_response(&body)
+        })
+    }
+}
+
+/// What happens to an answer the moderator flags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationPolicy {
+    /// Send the answer anyway, with a warning
+    #[default]
+    Warn,
+    /// Ask for another answer; answers that can't be checked are refused too
+    Refuse,
+}
+
+/// Moderation service to use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationProvider {
+    /// The OpenAI moderation endpoint, with the key from `OPENAI_API_KEY`
+    #[default]
+    OpenAi,
+}
+
+/// Moderation settings of the configuration file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModerationConfig {
+    /// Service that checks the answers
+    #[serde(default)]
+    pub provider: ModerationProvider,
+    /// What happens to flagged answers
+    #[serde(default)]
+    pub policy: ModerationPolicy,
+    /// Moderation model, the provider's latest if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// Outcome of reviewing an answer under the moderation policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModerationOutcome {
+    /// The answer can be used
+    Allowed,
+    /// The answer can be used, but the user should be told why it was flagged
+    Warned(String),
+    /// The answer must not be used, for the given reason
+    Refused(String),
+}
+
+/// Moderator together with the policy applied to what it flags
+pub struct Moderation {
+    /// Service that checks the answers
+    moderator: Box<dyn Moderator>,
+    /// What happens to flagged answers
+    policy: ModerationPolicy,
+}
+
+impl Default for Moderation {
+    fn default() -> Self {
+        Self::new(NoModeration, ModerationPolicy::default())
+    }
+}
+
+impl Moderation {
+    /// Create a moderation with the given moderator and policy
+    pub fn new(moderator: impl Moderator + 'static, policy: ModerationPolicy) -> Self {
+        Self {
+            moderator: Box::new(moderator),
+            policy,
+        }
+    }
+
+    /// Create the moderation described in the configuration file
+    pub fn from_config(config: &ModerationConfig) -> Result<Self> {
+        match config.provider {
+            ModerationProvider::OpenAi => {
+                let api_key = std::env::var(OPENAI_API_KEY_VAR).map_err(|_| {
+                    WizardError::Invalid(format!(
+                        "Moderation is enabled but {} is not set",
+                        OPENAI_API_KEY_VAR
+                    ))
+                })?;
+                Ok(Self::new(
+                    OpenAiModerator::new(api_key, config.model.clone()),
+                    config.policy,
+                ))
+            }
+        }
+    }
+
+    /// Check an answer and decide what happens to it under the policy
+    pub async fn review(&self, text: &str) -> ModerationOutcome {
+        let verdict = match self.moderator.check(text).await {
+            Ok(verdict) => verdict,
+            Err(e) => {
+                let reason = format!("it could not be checked ({})", e);
+                return match self.policy {
+                    ModerationPolicy::Warn => ModerationOutcome::Warned(reason),
+                    ModerationPolicy::Refuse => ModerationOutcome::Refused(reason),
+                };
+            }
+        };
+        if !verdict.flagged {
+            return ModerationOutcome::Allowed;
+        }
+
+        let reason = if verdict.categories.is_empty() {
+            "it was flagged by the moderation service".to_string()
+        } else {
+            format!("it was flagged for {}", verdict.categories.join(", "))
+        };
+        match self.policy {
+            ModerationPolicy::Warn => ModerationOutcome::Warned(reason),
+            ModerationPolicy::Refuse => ModerationOutcome::Refused(reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::LlmClient;
+    use crate::wizard::session::{Session, SessionManager};
+
+    /// Moderator flagging every text that mentions the given word
+    struct StubModerator(&'static str);
+
+    impl Moderator for StubModerator {
+        fn check<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<ModerationVerdict>> {
+            let flagged = text.contains(self.0);
+            Box::pin(async move {
+                Ok(ModerationVerdict {
+                    flagged,
+                    categories: if flagged {
+                        vec!["self-harm".to_string()]
+                    } else {
+                        Vec::new()
+                    },
+                })
+            })
+        }
+    }
+
+    /// Moderator whose service can't be reached
+    struct UnreachableModerator;
+
+    impl Moderator for UnreachableModerator {
+        fn check<'a>(&'a self, _text: &'a str) -> BoxFuture<'a, Result<ModerationVerdict>> {
+            Box::pin(async { Err(WizardError::Api("connection refused".to_string())) })
+        }
+    }
+
+    /// Session manager checking answers with the stub moderator under the given policy
+    fn moderated_session(policy: ModerationPolicy) -> SessionManager {
+        let (client, _) = LlmClient::scripted([]);
+        SessionManager::new(Session::new(), client)
+            .with_moderation(Moderation::new(StubModerator("overdose"), policy))
+    }
+
+    #[tokio::test]
+    async fn flagged_answer_is_warned_about_or_refused_per_policy() {
+        let crafted = "Patients ask how to overdose on their medication";
+        let reason = "it was flagged for self-harm".to_string();
+
+        let warn = moderated_session(ModerationPolicy::Warn);
+        assert_eq!(
+            warn.moderate_answer(crafted).await,
+            ModerationOutcome::Warned(reason.clone())
+        );
+        assert_eq!(
+            warn.moderate_answer("Nurses in rural clinics").await,
+            ModerationOutcome::Allowed
+        );
+
+        let refuse = moderated_session(ModerationPolicy::Refuse);
+        assert_eq!(
+            refuse.moderate_answer(crafted).await,
+            ModerationOutcome::Refused(reason)
+        );
+        assert_eq!(
+            refuse.moderate_answer("Nurses in rural clinics").await,
+            ModerationOutcome::Allowed
+        );
+    }
+
+    #[tokio::test]
+    async fn unchecked_answer_is_refused_only_under_the_refuse_policy() {
+        let warn = Moderation::new(UnreachableModerator, ModerationPolicy::Warn);
+        let refuse = Moderation::new(UnreachableModerator, ModerationPolicy::Refuse);
+
+        assert!(matches!(
+            warn.review("An answer").await,
+            ModerationOutcome::Warned(reason) if reason.contains("connection refused")
+        ));
+        assert!(matches!(
+            refuse.review("An answer").await,
+            ModerationOutcome::Refused(reason) if reason.contains("could not be checked")
+        ));
+        assert_eq!(
+            Moderation::default().review("overdose").await,
+            ModerationOutcome::Allowed
+        );
+    }
+
+    #[test]
+    fn moderation_response_lists_the_flagged_categories() {
+        let body = r#"{"results": [{"flagged": true, "categories": {"self-harm": true, "violence": false}}]}"#;
+
+        let verdict = OpenAiModerator::parse_response(body).unwrap();
+
+        assert!(verdict.flagged);
+        assert_eq!(verdict.categories, vec!["self-harm".to_string()]);
+        assert!(OpenAiModerator::parse_response(r#"{"results": []}"#).is_err());
+    }
+}
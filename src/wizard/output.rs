@@ -3,26 +3,44 @@
 //! This module handles the generation of the final project definition document
 //! in Markdown format.
 
-use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::Path;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use super::{Context, LlmClient};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use super::atomic::write_atomic;
+use super::context::{Answer, ContextMeta};
+use super::error::Result;
+use super::llm::{
+    DEFINITION_SECTION_GROUPS, DEFINITION_SECTIONS, DefinitionReview, OPEN_QUESTIONS_SECTION,
+};
+use super::{Context, LlmClient, WizardError};
+
+/// Name of a project definition whose project name could not be found
+pub const DEFAULT_PROJECT_NAME: &str = "LLM Project Definition";
+
+/// Maximum number of characters kept of a project name
+const MAX_PROJECT_NAME_CHARS: usize = 60;
 
 /// Confidence level for sections of the project definition
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum ConfidenceLevel {
     /// Very low confidence (1/5)
     VeryLow = 1,
     /// Low confidence (2/5)
     Low = 2,
-    /// Medium confidence (3/5)
+    /// Medium confidence (3/5), also assumed for sections without a marker unless configured
+    #[default]
     Medium = 3,
     /// High confidence (4/5)
     High = 4,
     /// Very high confidence (5/5)
     VeryHigh = 5,
+    /// Not scored, when the definition was generated without confidence scores
+    NotApplicable = 0,
 }
 
 impl ConfidenceLevel {
@@ -38,7 +56,12 @@ impl ConfidenceLevel {
         }
     }
 
-    /// Get the emoji representation of the confidence level
+    /// Check whether the level is an actual score rather than `NotApplicable`
+    pub fn is_scored(&self) -> bool {
+        !matches!(self, Self::NotApplicable)
+    }
+
+    /// Get the emoji representation of the confidence level (empty if not scored)
     pub fn emoji(&self) -> &'static str {
         match self {
             Self::VeryLow => "⚠️",
@@ -46,8 +69,117 @@ impl ConfidenceLevel {
             Self::Medium => "🔶",
             Self::High => "✅",
             Self::VeryHigh => "⭐",
+            Self::NotApplicable => "",
         }
     }
+
+    /// Get the marker for the confidence level in the given style, if any
+    pub fn marker(&self, style: ConfidenceStyle) -> Option<String> {
+        if !self.is_scored() {
+            return None;
+        }
+
+        match style {
+            ConfidenceStyle::Emoji => Some(self.emoji().to_string()),
+            ConfidenceStyle::Ascii => Some(format!("[{}/5]", *self as u8)),
+            ConfidenceStyle::None => None,
+        }
+    }
+}
+
+/// How confidence levels are marked in section titles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfidenceStyle {
+    /// Emoji markers (e.g. ✅)
+    #[default]
+    Emoji,
+    /// Plain text markers (e.g. `[4/5]`) for terminals without emoji support
+    Ascii,
+    /// No markers
+    None,
+}
+
+/// Where the confidence marker goes in a section heading
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkerPlacement {
+    /// After the title, as in `## Summary ✅`
+    #[default]
+    After,
+    /// Before the title, as in `## ✅ Summary`
+    Before,
+}
+
+/// House style for rendering a project definition as Markdown
+///
+/// The default style gives the usual output: `##` section headings with the titles
+/// as generated, the confidence marker after the title and a timestamp line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarkdownStyle {
+    /// Heading level of the sections, from 1 (`#`) to 6; the document title always uses `#`
+    pub heading_level: u8,
+    /// Whether to number the sections 1, 2, 3..., replacing any number in the title
+    pub numbered: bool,
+    /// How confidence levels are marked (set with `confidence_style`, not part of this style)
+    #[serde(skip)]
+    pub confidence: ConfidenceStyle,
+    /// Where the confidence marker goes in the heading
+    pub marker_placement: MarkerPlacement,
+    /// Whether to include the "Generated on" timestamp
+    pub timestamp: bool,
+}
+
+impl Default for MarkdownStyle {
+    fn default() -> Self {
+        Self {
+            heading_level: 2,
+            numbered: false,
+            confidence: ConfidenceStyle::default(),
+            marker_placement: MarkerPlacement::default(),
+            timestamp: true,
+        }
+    }
+}
+
+impl MarkdownStyle {
+    /// Render a section heading in this style
+    fn heading(&self, index: usize, title: &str, confidence: ConfidenceLevel) -> String {
+        let hashes = "#".repeat(self.heading_level.clamp(1, 6) as usize);
+        let title = if self.numbered {
+            format!("{}. {}", index + 1, unnumbered_title(title))
+        } else {
+            title.to_string()
+        };
+
+        match (confidence.marker(self.confidence), self.marker_placement) {
+            (Some(marker), MarkerPlacement::After) => format!("{} {} {}", hashes, title, marker),
+            (Some(marker), MarkerPlacement::Before) => format!("{} {} {}", hashes, marker, title),
+            (None, _) => format!("{} {}", hashes, title),
+        }
+    }
+}
+
+/// What to do with a section whose title the model repeated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateSectionPolicy {
+    /// Append the repeated content to the first section and keep the higher confidence
+    #[default]
+    Merge,
+    /// Replace the first section with the later one
+    KeepLast,
+}
+
+/// File format of the exported project definition
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Markdown document
+    #[default]
+    Markdown,
+    /// Plain text without Markdown formatting, for systems that mangle Markdown
+    Text,
 }
 
 /// Section of the project definition document
@@ -82,6 +214,34 @@ impl ProjectDefinition {
         }
     }
 
+    /// Build a basic project definition straight from the answers, without the LLM
+    pub fn from_answers(name: impl Into<String>, context: &Context) -> Self {
+        let mut definition = Self::new(name);
+
+        let mut summary = String::new();
+        if let Some(domain) = &context.domain {
+            summary.push_str(&format!("- **Domain:** {}\n", domain));
+        }
+        if let Some(hints) = &context.starting_hints {
+            summary.push_str(&format!("- **Starting point:** {}\n", hints));
+        }
+        summary.push_str("\nAssembled offline from the answers below, without the LLM.");
+        definition.add_section("Project Name and Summary", summary, ConfidenceLevel::Low);
+
+        let answers: Vec<String> = context
+            .history
+            .iter()
+            .map(|answer| format!("- **{}**\n  {}", answer.question.text, answer.response))
+            .collect();
+        definition.add_section(
+            "Requirements Gathered",
+            answers.join("\n"),
+            ConfidenceLevel::Medium,
+        );
+
+        definition
+    }
+
     /// Add a section to the project definition
     pub fn add_section(
         &mut self,
@@ -96,50 +256,434 @@ impl ProjectDefinition {
         });
     }
 
+    /// Combine sections with the same title, as models sometimes repeat a heading
+    ///
+    /// Titles are compared ignoring case and any leading section number, so
+    /// `4. Required Inputs` and `Required Inputs` are the same section. The combined
+    /// section stays where the title first appeared. Returns the number of
+    /// duplicates removed.
+    pub fn merge_duplicate_sections(&mut self, policy: DuplicateSectionPolicy) -> usize {
+        let mut sections: Vec<ProjectSection> = Vec::with_capacity(self.sections.len());
+        let mut duplicates = 0;
+
+        for section in std::mem::take(&mut self.sections) {
+            let key = section_key(&section.title);
+            let Some(first) = sections
+                .iter_mut()
+                .find(|first| section_key(&first.title) == key)
+            else {
+                sections.push(section);
+                continue;
+            };
+
+            duplicates += 1;
+            match policy {
+                DuplicateSectionPolicy::Merge => {
+                    first.content = format!(
+                        "{}\n\n{}",
+                        first.content.trim_end(),
+                        section.content.trim_start_matches('\n')
+                    );
+                    if section.confidence as u8 > first.confidence as u8 {
+                        first.confidence = section.confidence;
+                    }
+                }
+                DuplicateSectionPolicy::KeepLast => *first = section,
+            }
+        }
+
+        self.sections = sections;
+        duplicates
+    }
+
+    /// Apply a review: update the confidence of the reviewed sections and replace the open questions
+    ///
+    /// Sections are matched by title, ignoring case and section numbers; scores outside
+    /// 1-5 and sections the review doesn't mention are left alone. Returns the number of
+    /// sections whose confidence changed.
+    pub fn apply_review(&mut self, review: &DefinitionReview) -> usize {
+        let mut changed = 0;
+        for estimate in &review.sections {
+            let Some(confidence) = ConfidenceLevel::from_value(estimate.confidence) else {
+                continue;
+            };
+            let key = section_key(&estimate.section);
+            if let Some(section) = self
+                .sections
+                .iter_mut()
+                .find(|section| section_key(&section.title) == key)
+                && section.confidence as u8 != confidence as u8
+            {
+                section.confidence = confidence;
+                changed += 1;
+            }
+        }
+
+        let open_questions = if review.open_questions.is_empty() {
+            "- None".to_string()
+        } else {
+            review
+                .open_questions
+                .iter()
+                .map(|question| format!("- {}", question.trim()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let key = section_key(OPEN_QUESTIONS_SECTION);
+        match self
+            .sections
+            .iter_mut()
+            .find(|section| section_key(&section.title) == key)
+        {
+            Some(section) => section.content = open_questions,
+            None => self.add_section(
+                OPEN_QUESTIONS_SECTION,
+                open_questions,
+                ConfidenceLevel::NotApplicable,
+            ),
+        }
+
+        changed
+    }
+
+    /// Get the average section confidence (1-5), if any section has a confidence score
+    pub fn average_confidence(&self) -> Option<f32> {
+        let scores: Vec<u32> = self
+            .sections
+            .iter()
+            .filter(|section| section.confidence.is_scored())
+            .map(|section| section.confidence as u32)
+            .collect();
+        if scores.is_empty() {
+            return None;
+        }
+        Some(scores.iter().sum::<u32>() as f32 / scores.len() as f32)
+    }
+
+    /// Get the titles of the `count` lowest-confidence sections, weakest first
+    ///
+    /// Sections without a confidence score are left out.
+    pub fn weakest_sections(&self, count: usize) -> Vec<String> {
+        let mut sections: Vec<&ProjectSection> = self
+            .sections
+            .iter()
+            .filter(|section| section.confidence.is_scored())
+            .collect();
+        sections.sort_by_key(|section| section.confidence as u8);
+
+        sections
+            .into_iter()
+            .take(count)
+            .map(|section| section.title.clone())
+            .collect()
+    }
+
     /// Convert the project definition to a Markdown string
+    #[allow(
+        dead_code,
+        reason = "the wizard renders with the configured confidence style"
+    )]
     pub fn to_markdown(&self) -> String {
+        self.to_markdown_with_style(ConfidenceStyle::default())
+    }
+
+    /// Convert the project definition to a Markdown string using a confidence marker style
+    pub fn to_markdown_with_style(&self, style: ConfidenceStyle) -> String {
+        self.to_styled_markdown(&MarkdownStyle {
+            confidence: style,
+            ..MarkdownStyle::default()
+        })
+    }
+
+    /// Convert the project definition to a Markdown string in a house style
+    pub fn to_styled_markdown(&self, style: &MarkdownStyle) -> String {
         let mut markdown = String::new();
 
         // Add title
         markdown.push_str(&format!("# {}\n\n", self.name));
 
         // Add timestamp
-        markdown.push_str(&format!(
-            "*Generated on: {}*\n\n",
-            self.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
-        ));
-
-        // Add sections
-        for section in &self.sections {
+        if style.timestamp {
             markdown.push_str(&format!(
-                "## {} {}\n\n",
-                section.title,
-                section.confidence.emoji()
+                "*Generated on: {}*\n\n",
+                self.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
             ));
+        }
+
+        // Add sections
+        for (index, section) in self.sections.iter().enumerate() {
+            markdown.push_str(&style.heading(index, &section.title, section.confidence));
+            markdown.push_str("\n\n");
             markdown.push_str(&format!("{}\n\n", section.content));
         }
 
         markdown
     }
 
+    /// Convert the project definition to plain text
+    #[allow(
+        dead_code,
+        reason = "the wizard renders with the configured confidence style"
+    )]
+    pub fn to_plain_text(&self) -> String {
+        self.to_plain_text_with_style(ConfidenceStyle::default())
+    }
+
+    /// Convert the project definition to plain text using a confidence marker style
+    pub fn to_plain_text_with_style(&self, style: ConfidenceStyle) -> String {
+        markdown_to_plain_text(&self.to_markdown_with_style(style))
+    }
+
     /// Save the project definition to a file
+    #[allow(
+        dead_code,
+        reason = "the wizard saves through SessionManager, which picks the output format"
+    )]
     pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
         let markdown = self.to_markdown();
-        fs::write(path, markdown)?;
+        write_atomic(path, markdown)?;
         Ok(())
     }
+
+    /// Save the project definition as a PDF document
+    #[cfg(feature = "pdf")]
+    pub fn to_pdf(&self, path: impl AsRef<Path>) -> Result<()> {
+        super::pdf::render(self, path.as_ref())
+    }
+
+    /// Find the project name in a `Name:` line of the first section, if the model wrote one
+    fn name_from_first_section(&self) -> Option<String> {
+        let section = self.sections.first()?;
+        section.content.lines().find_map(|line| {
+            let line = line.trim().trim_start_matches(['-', '*', ' ']);
+            let (label, name) = line.split_once(':')?;
+            if label.to_lowercase().contains("name") {
+                clean_project_name(name)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Clean up a project name suggested by the model or found in the definition
+///
+/// Keeps the first non-empty line, without heading, emphasis or quote marks or a
+/// `Project name:` label, cut to a reasonable length. Returns `None` if nothing is left.
+pub fn clean_project_name(raw: &str) -> Option<String> {
+    let is_markup =
+        |c: char| c.is_whitespace() || matches!(c, '#' | '*' | '_' | '`' | '"' | '\'' | '.');
+    let mut name = raw
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?
+        .trim_matches(is_markup);
+    if let Some((label, rest)) = name.split_once(':')
+        && label.to_lowercase().contains("name")
+    {
+        name = rest.trim_matches(is_markup);
+    }
+
+    let name: String = name.chars().take(MAX_PROJECT_NAME_CHARS).collect();
+    let name = name.trim_end();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Turn a project name into a file name stem, such as `task_tracker` for "Task Tracker"
+///
+/// Returns `None` if the name has no letters or digits to build a stem from.
+pub fn project_file_stem(name: &str) -> Option<String> {
+    let mut stem = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            stem.extend(c.to_lowercase());
+        } else if !stem.is_empty() && !stem.ends_with('_') {
+            stem.push('_');
+        }
+    }
+
+    let stem = stem.trim_end_matches('_');
+    if stem.is_empty() {
+        None
+    } else {
+        Some(stem.to_string())
+    }
+}
+
+/// Get a section title without any leading number, such as the `4.` in `4. Required Inputs`
+fn unnumbered_title(title: &str) -> &str {
+    title
+        .trim()
+        .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .trim()
+}
+
+/// Normalize a section title for comparison, dropping case and any leading number
+fn section_key(title: &str) -> String {
+    unnumbered_title(title).to_lowercase()
+}
+
+/// Convert a Markdown document to plain text, keeping its structure
+///
+/// Headings become uppercased titles, list items keep a dash (or their number)
+/// indented by nesting level, and emphasis, code and link markup is dropped,
+/// with link targets kept in parentheses.
+pub fn markdown_to_plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    // Text of the heading being read, uppercased once it ends
+    let mut heading: Option<String> = None;
+    // Next number of each open list, `None` for bullet lists
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    // Targets of the links being read
+    let mut links: Vec<String> = Vec::new();
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => heading = Some(String::new()),
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(title) = heading.take() {
+                    text.push_str(&title.trim().to_uppercase());
+                    text.push_str("\n\n");
+                }
+            }
+            Event::Start(Tag::List(start)) => lists.push(start),
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    text.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(number)) => {
+                        text.push_str(&format!("{}. ", number));
+                        *number += 1;
+                    }
+                    _ => text.push_str("- "),
+                }
+            }
+            Event::End(TagEnd::Item) if !text.ends_with('\n') => text.push('\n'),
+            Event::End(TagEnd::Paragraph) => {
+                text.push_str(if lists.is_empty() { "\n\n" } else { "\n" });
+            }
+            Event::End(TagEnd::CodeBlock) => text.push('\n'),
+            Event::Start(Tag::Link { dest_url, .. }) => links.push(dest_url.to_string()),
+            Event::End(TagEnd::Link) => {
+                if let Some(url) = links.pop()
+                    && heading.is_none()
+                    && !text.ends_with(&url)
+                {
+                    text.push_str(&format!(" ({})", url));
+                }
+            }
+            Event::Text(content) | Event::Code(content) => match heading.as_mut() {
+                Some(title) => title.push_str(&content),
+                None => text.push_str(&content),
+            },
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Rule => text.push_str("----------\n\n"),
+            _ => {}
+        }
+    }
+
+    format!("{}\n", text.trim_end())
+}
+
+/// Get the sidecar path used to keep the raw LLM response for an output file
+///
+/// The suffix is appended to the whole file name, so `definition.md` is kept as
+/// `definition.md.raw.md` and outputs differing only in extension don't share it.
+pub fn raw_output_path(output_path: impl AsRef<Path>) -> PathBuf {
+    let mut path = output_path.as_ref().as_os_str().to_owned();
+    path.push(".raw.md");
+    PathBuf::from(path)
 }
 
 /// Generator for project definition documents
 pub struct OutputGenerator {
     /// The LLM client used for generating project definitions
     llm_client: LlmClient,
+    /// Where to save the raw LLM response before parsing it
+    raw_output_path: Option<PathBuf>,
+    /// Whether to request groups of sections separately to stay under `max_tokens`
+    split_generation: bool,
+    /// Maximum number of concurrent per-section requests, if sections are generated in parallel
+    parallel_sections: Option<usize>,
+    /// Confidence assumed for sections the model didn't mark with a score
+    default_confidence: ConfidenceLevel,
+    /// Whether the definition is generated without confidence scores
+    omit_confidence: bool,
+    /// What to do with sections whose title the model repeated
+    duplicate_sections: DuplicateSectionPolicy,
+    /// Whether to stream the definition, writing it through to the raw output path
+    stream: bool,
 }
 
 impl OutputGenerator {
     /// Create a new output generator
     pub fn new(llm_client: LlmClient) -> Self {
-        Self { llm_client }
+        Self {
+            llm_client,
+            raw_output_path: None,
+            split_generation: false,
+            parallel_sections: None,
+            default_confidence: ConfidenceLevel::default(),
+            omit_confidence: false,
+            duplicate_sections: DuplicateSectionPolicy::default(),
+            stream: false,
+        }
+    }
+
+    /// Stream the definition, writing it to the raw output path as it arrives
+    ///
+    /// Not used with parallel sections, which arrive out of order.
+    pub fn with_stream(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    /// Set what happens to sections whose title the model repeated
+    pub fn with_duplicate_sections(mut self, policy: DuplicateSectionPolicy) -> Self {
+        self.duplicate_sections = policy;
+        self
+    }
+
+    /// Treat every section as unscored, for definitions generated without confidence scores
+    pub fn with_omit_confidence(mut self, omit_confidence: bool) -> Self {
+        self.omit_confidence = omit_confidence;
+        self
+    }
+
+    /// Set the confidence assumed for sections without a confidence marker
+    pub fn with_default_confidence(mut self, confidence: ConfidenceLevel) -> Self {
+        self.default_confidence = confidence;
+        self
+    }
+
+    /// Request every section separately, running at most `limit` requests at a time
+    pub fn with_parallel_sections(mut self, limit: Option<usize>) -> Self {
+        self.parallel_sections = limit.map(|limit| limit.max(1));
+        self
+    }
+
+    /// Request groups of sections separately and merge them into one document
+    pub fn with_split_generation(mut self, split_generation: bool) -> Self {
+        self.split_generation = split_generation;
+        self
+    }
+
+    /// Save the raw LLM response to a file before parsing it
+    pub fn with_raw_output_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.raw_output_path = Some(path.into());
+        self
     }
 
     /// Generate a project definition from the context
@@ -148,31 +692,150 @@ impl OutputGenerator {
         context: &Context,
     ) -> Result<ProjectDefinition> {
         // Use the LLM to generate the project definition
-        let markdown = self.llm_client.generate_project_definition(context).await?;
+        let markdown = if self.stream && self.parallel_sections.is_none() {
+            self.stream_project_definition(context).await?
+        } else if let Some(limit) = self.parallel_sections {
+            // `buffered` keeps the results in section order however the requests finish
+            let parts: Vec<String> = stream::iter(0..DEFINITION_SECTIONS.len())
+                .map(|index| {
+                    self.llm_client
+                        .generate_definition_sections(context, index..index + 1)
+                })
+                .buffered(limit)
+                .try_collect()
+                .await?;
+            parts.join("\n\n")
+        } else if self.split_generation {
+            let mut parts = Vec::new();
+            for group in DEFINITION_SECTION_GROUPS {
+                parts.push(
+                    self.llm_client
+                        .generate_definition_sections(context, group.clone())
+                        .await?,
+                );
+            }
+            parts.join("\n\n")
+        } else {
+            self.llm_client.generate_project_definition(context).await?
+        };
+
+        // Keep the model's work on disk so a parse failure doesn't lose it
+        if let Some(path) = &self.raw_output_path {
+            write_atomic(path, &markdown)?;
+        }
 
         // Parse the markdown to extract sections and confidence levels
         self.parse_markdown_definition(&markdown)
     }
 
+    /// Generate a project definition from answers collected elsewhere, without a session
+    ///
+    /// The answers are in the format exported by `--questions-only`. The project
+    /// name from `meta` titles the document; otherwise the model's title is kept.
+    pub async fn generate_from_answers(
+        &self,
+        answers: Vec<Answer>,
+        meta: ContextMeta,
+    ) -> Result<ProjectDefinition> {
+        if answers.iter().all(|answer| answer.skipped) {
+            return Err(WizardError::Invalid(
+                "No answers to generate a project definition from".to_string(),
+            ));
+        }
+
+        let context = meta.into_context(answers);
+        let mut definition = self.generate_project_definition(&context).await?;
+        if let Some(name) = context.project_name {
+            definition.name = name;
+        }
+        Ok(definition)
+    }
+
+    /// Stream the project definition, in groups of sections with split generation
+    ///
+    /// The text is written through to the raw output path as it arrives, so an
+    /// interrupted stream leaves a partial but readable document behind. The
+    /// complete response replaces it in one step once the stream has finished.
+    async fn stream_project_definition(&self, context: &Context) -> Result<String> {
+        let groups: Vec<std::ops::Range<usize>> = if self.split_generation {
+            DEFINITION_SECTION_GROUPS.to_vec()
+        } else {
+            std::iter::once(0..DEFINITION_SECTIONS.len()).collect()
+        };
+
+        let mut partial = match &self.raw_output_path {
+            Some(path) => Some((
+                File::create(path).map_err(WizardError::io("create", path))?,
+                path,
+            )),
+            None => None,
+        };
+        let mut write_through = |text: &str| -> Result<()> {
+            if let Some((file, path)) = partial.as_mut() {
+                file.write_all(text.as_bytes())
+                    .and_then(|_| file.flush())
+                    .map_err(WizardError::io("write", path.as_path()))?;
+            }
+            Ok(())
+        };
+
+        let mut parts = Vec::new();
+        for group in groups {
+            if !parts.is_empty() {
+                write_through("\n\n")?;
+            }
+            parts.push(
+                self.llm_client
+                    .stream_definition_sections(context, group, &mut write_through)
+                    .await?,
+            );
+        }
+        Ok(parts.join("\n\n"))
+    }
+
+    /// Re-evaluate a project definition, e.g. one edited by hand after it was generated
+    ///
+    /// The document is parsed as if the model had written it, then the model scores each
+    /// section from its text alone (markers are removed first) and lists what is still missing.
+    pub async fn rescore_definition(&self, markdown: &str) -> Result<ProjectDefinition> {
+        let mut definition = self.parse_markdown_definition(markdown)?;
+        // Keep the spacing stable when a document is re-scored more than once
+        for section in &mut definition.sections {
+            section.content = section.content.trim().to_string();
+        }
+
+        let review = self
+            .llm_client
+            .review_definition(&definition.to_markdown_with_style(ConfidenceStyle::None))
+            .await?;
+        definition.apply_review(&review);
+        definition.timestamp = chrono::Utc::now();
+        Ok(definition)
+    }
+
     /// Parse the markdown project definition to extract sections and confidence levels
-    fn parse_markdown_definition(&self, markdown: &str) -> Result<ProjectDefinition> {
+    pub fn parse_markdown_definition(&self, markdown: &str) -> Result<ProjectDefinition> {
         // Extract the project name from the first heading
         let lines: Vec<&str> = markdown.lines().collect();
-        let project_name = lines
+        let heading_name = lines
             .iter()
             .find(|line| line.starts_with("# "))
-            .map(|line| line[2..].trim().to_string())
-            .unwrap_or_else(|| "LLM Project Definition".to_string());
+            .map(|line| line[2..].trim().to_string());
 
-        let mut definition = ProjectDefinition::new(project_name);
+        let mut definition = ProjectDefinition::new(DEFAULT_PROJECT_NAME);
 
         // Extract sections
         let mut current_section_title = String::new();
         let mut current_section_content = String::new();
-        let mut current_confidence = ConfidenceLevel::Medium;
+        let default_confidence = if self.omit_confidence {
+            ConfidenceLevel::NotApplicable
+        } else {
+            self.default_confidence
+        };
+        let mut current_confidence = default_confidence;
 
         for line in lines {
-            if line.starts_with("## ") {
+            if let Some(title_line) = line.strip_prefix("## ") {
                 // Save the previous section if it exists
                 if !current_section_title.is_empty() && !current_section_content.is_empty() {
                     definition.add_section(
@@ -184,7 +847,7 @@ impl OutputGenerator {
                 }
 
                 // Parse the new section title and confidence
-                let title_line = line[3..].trim();
+                let title_line = title_line.trim();
 
                 // Extract confidence from emojis or explicit markers
                 current_confidence = if title_line.contains("⭐") {
@@ -208,8 +871,12 @@ impl OutputGenerator {
                 } else if title_line.contains("(Confidence: 1/5)") {
                     ConfidenceLevel::VeryLow
                 } else {
-                    ConfidenceLevel::Medium // Default
+                    default_confidence
                 };
+                // Scores the model added anyway are stripped below but not kept
+                if self.omit_confidence {
+                    current_confidence = ConfidenceLevel::NotApplicable;
+                }
 
                 // Clean the title by removing confidence markers
                 current_section_title = title_line
@@ -241,6 +908,388 @@ impl OutputGenerator {
             );
         }
 
+        if definition.sections.is_empty() {
+            return Err(WizardError::Parse(
+                "No sections found; sections must start with a '## ' heading".to_string(),
+            ));
+        }
+
+        definition.merge_duplicate_sections(self.duplicate_sections);
+
+        // The model rarely writes a title, so also look for the name in the first section
+        if let Some(name) = heading_name.or_else(|| definition.name_from_first_section()) {
+            definition.name = name;
+        }
+
         Ok(definition)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::llm::scripted::Reply;
+    use crate::wizard::question::Question;
+    use std::time::Duration;
+
+    #[test]
+    fn raw_output_path_appends_to_the_file_name() {
+        assert_eq!(
+            raw_output_path("out/definition.md"),
+            PathBuf::from("out/definition.md.raw.md")
+        );
+    }
+
+    #[tokio::test]
+    async fn parse_failure_leaves_raw_response_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw_path = raw_output_path(dir.path().join("definition.md"));
+        let response = "I'm sorry, I can't write a project definition yet.";
+        let (client, _) = LlmClient::scripted([Reply::content(response)]);
+        let generator = OutputGenerator::new(client).with_raw_output_path(&raw_path);
+
+        let result = generator.generate_project_definition(&Context::new()).await;
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&raw_path).unwrap(), response);
+    }
+
+    #[tokio::test]
+    async fn split_generation_merges_section_groups() {
+        let (client, script) = LlmClient::scripted([
+            Reply::content(
+                "# Triage Bot\n\n## Project Name and Summary (Confidence: 5/5)\nA triage bot.\n\n\
+                 ## Use Cases and Goals (Confidence: 3/5)\nShorter waits.",
+            ),
+            Reply::content("## Prompt Engineering Strategy (Confidence: 2/5)\nFew-shot prompts."),
+        ]);
+        let generator = OutputGenerator::new(client).with_split_generation(true);
+
+        let definition = generator
+            .generate_project_definition(&Context::new())
+            .await
+            .unwrap();
+
+        assert_eq!(script.received().len(), DEFINITION_SECTION_GROUPS.len());
+        assert_eq!(definition.name, "Triage Bot");
+        let sections: Vec<(&str, u8)> = definition
+            .sections
+            .iter()
+            .map(|section| (section.title.as_str(), section.confidence as u8))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                ("Project Name and Summary", 5),
+                ("Use Cases and Goals", 3),
+                ("Prompt Engineering Strategy", 2),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn parallel_sections_keep_document_order() {
+        // Later sections answer first, so completion order is the reverse of document order
+        let count = DEFINITION_SECTIONS.len();
+        let replies = DEFINITION_SECTIONS.iter().enumerate().map(|(i, section)| {
+            Reply::content(format!(
+                "## {} (Confidence: 3/5)\nAbout {}.",
+                section.title, section.title
+            ))
+            .after(Duration::from_millis(10 * (count - i) as u64))
+        });
+        let (client, _) = LlmClient::scripted(replies);
+        let generator = OutputGenerator::new(client).with_parallel_sections(Some(4));
+
+        let definition = generator
+            .generate_project_definition(&Context::new())
+            .await
+            .unwrap();
+
+        let titles: Vec<&str> = definition
+            .sections
+            .iter()
+            .map(|section| section.title.as_str())
+            .collect();
+        let expected: Vec<&str> = DEFINITION_SECTIONS
+            .iter()
+            .map(|section| section.title)
+            .collect();
+        assert_eq!(titles, expected);
+        for section in &definition.sections {
+            assert_eq!(section.content.trim(), format!("About {}.", section.title));
+        }
+    }
+
+    /// Small definition with a low and a high confidence section
+    fn small_definition() -> ProjectDefinition {
+        let mut definition = ProjectDefinition::new("Triage Bot");
+        definition.add_section("Target Users", "Nurses.", ConfidenceLevel::Low);
+        definition.add_section("Use Cases", "Symptom triage.", ConfidenceLevel::High);
+        definition
+    }
+
+    /// Markdown rendering without the timestamp, using a confidence style
+    fn render(definition: &ProjectDefinition, confidence: ConfidenceStyle) -> String {
+        definition.to_styled_markdown(&MarkdownStyle {
+            confidence,
+            timestamp: false,
+            ..MarkdownStyle::default()
+        })
+    }
+
+    #[test]
+    fn ascii_and_none_confidence_styles() {
+        let definition = small_definition();
+
+        assert_eq!(
+            render(&definition, ConfidenceStyle::Ascii),
+            "# Triage Bot\n\n## Target Users [2/5]\n\nNurses.\n\n## Use Cases [4/5]\n\nSymptom triage.\n\n"
+        );
+        assert_eq!(
+            render(&definition, ConfidenceStyle::None),
+            "# Triage Bot\n\n## Target Users\n\nNurses.\n\n## Use Cases\n\nSymptom triage.\n\n"
+        );
+    }
+
+    #[test]
+    fn plain_text_strips_markdown_and_keeps_confidence() {
+        let mut definition = ProjectDefinition::new("Triage Bot");
+        definition.add_section(
+            "Target Users",
+            "Mostly **nurses** on *night shifts*:\n\n* Triage nurses\n* Ward staff",
+            ConfidenceLevel::Low,
+        );
+
+        let plain = markdown_to_plain_text(&render(&definition, ConfidenceStyle::Ascii));
+
+        assert_eq!(
+            plain,
+            "TRIAGE BOT\n\nTARGET USERS [2/5]\n\nMostly nurses on night shifts:\n\n- Triage nurses\n- Ward staff\n"
+        );
+    }
+
+    #[test]
+    fn configured_fallback_confidence_applies_to_unmarked_sections() {
+        let (client, _) = LlmClient::scripted([]);
+        let generator = OutputGenerator::new(client)
+            .with_default_confidence(ConfidenceLevel::from_value(1).unwrap());
+        let markdown = "# Triage Bot\n\n## Overview\n\nA triage assistant.\n\n\
+            ## Target Users (Confidence: 4/5)\n\nNurses.\n";
+
+        let definition = generator.parse_markdown_definition(markdown).unwrap();
+
+        let confidences: Vec<u8> = definition
+            .sections
+            .iter()
+            .map(|section| section.confidence as u8)
+            .collect();
+        assert_eq!(
+            confidences,
+            [ConfidenceLevel::VeryLow as u8, ConfidenceLevel::High as u8]
+        );
+    }
+
+    #[test]
+    fn repeated_section_is_merged_or_replaced() {
+        let markdown = "# Triage Bot\n\n\
+            ## 4. Required Inputs and Expected Outputs (Confidence: 2/5)\n\nSymptoms.\n\n\
+            ## 5. Constraints (Confidence: 3/5)\n\nNo diagnoses.\n\n\
+            ## 4. Required Inputs and Expected Outputs (Confidence: 4/5)\n\nA triage level.\n";
+        let parse = |policy| {
+            let (client, _) = LlmClient::scripted([]);
+            OutputGenerator::new(client)
+                .with_duplicate_sections(policy)
+                .parse_markdown_definition(markdown)
+                .unwrap()
+        };
+
+        let merged = parse(DuplicateSectionPolicy::Merge);
+        let kept = parse(DuplicateSectionPolicy::KeepLast);
+
+        assert_eq!(merged.sections.len(), 2);
+        let inputs = &merged.sections[0];
+        assert!(inputs.title.contains("Required Inputs"), "{}", inputs.title);
+        assert!(inputs.content.contains("Symptoms."), "{}", inputs.content);
+        assert!(
+            inputs.content.contains("A triage level."),
+            "{}",
+            inputs.content
+        );
+        assert_eq!(inputs.confidence as u8, ConfidenceLevel::High as u8);
+        assert!(merged.sections[1].title.contains("Constraints"));
+
+        assert_eq!(kept.sections.len(), 2);
+        assert!(!kept.sections[0].content.contains("Symptoms."));
+        assert!(kept.sections[0].content.contains("A triage level."));
+        assert_eq!(
+            kept.sections[0].confidence as u8,
+            ConfidenceLevel::High as u8
+        );
+    }
+
+    #[tokio::test]
+    async fn interrupted_stream_leaves_the_partial_definition_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw_path = raw_output_path(dir.path().join("definition.md"));
+        let delta = |text: &str| {
+            format!(
+                "data: {}\n\n",
+                serde_json::json!({ "choices": [{ "delta": { "content": text } }] })
+            )
+        };
+        let events = [
+            delta("# Triage Bot\n\n"),
+            ": keep-alive\n\n".to_string(),
+            delta("## Project Name and Summary (Confidence: 4/5)\nA triage"),
+            "data: {\"error\": {\"message\": \"Connection reset by upstream\"}}\n\n".to_string(),
+        ];
+        let (client, api) = LlmClient::scripted([Reply::Raw(200, events.concat())]);
+        let generator = OutputGenerator::new(client)
+            .with_stream(true)
+            .with_raw_output_path(&raw_path);
+
+        let error = generator
+            .generate_project_definition(&Context::new())
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("Connection reset"), "{}", error);
+        assert_eq!(api.received()[0].body["stream"], true);
+        assert_eq!(
+            std::fs::read_to_string(&raw_path).unwrap(),
+            "# Triage Bot\n\n## Project Name and Summary (Confidence: 4/5)\nA triage"
+        );
+    }
+
+    #[test]
+    fn house_style_changes_headings_numbering_and_timestamp() {
+        let mut definition = small_definition();
+        definition.timestamp = "2026-03-02T09:30:00Z".parse().unwrap();
+        let house_style = MarkdownStyle {
+            heading_level: 3,
+            numbered: true,
+            confidence: ConfidenceStyle::Ascii,
+            marker_placement: MarkerPlacement::Before,
+            timestamp: false,
+        };
+
+        let default = definition.to_styled_markdown(&MarkdownStyle {
+            confidence: ConfidenceStyle::Ascii,
+            ..MarkdownStyle::default()
+        });
+        let house = definition.to_styled_markdown(&house_style);
+
+        assert_eq!(
+            default,
+            "# Triage Bot\n\n*Generated on: 2026-03-02 09:30:00 UTC*\n\n\
+             ## Target Users [2/5]\n\nNurses.\n\n## Use Cases [4/5]\n\nSymptom triage.\n\n"
+        );
+        assert_eq!(
+            house,
+            "# Triage Bot\n\n\
+             ### [2/5] 1. Target Users\n\nNurses.\n\n### [4/5] 2. Use Cases\n\nSymptom triage.\n\n"
+        );
+        assert_eq!(
+            definition.to_markdown_with_style(ConfidenceStyle::Ascii),
+            default
+        );
+    }
+
+    #[tokio::test]
+    async fn edited_definition_is_rescored_from_the_review() {
+        let edited = "# Triage Bot\n\n\
+            ## Target Users (Confidence: 2/5)\n\nNight-shift nurses at three clinics, \
+            mostly triaging walk-in patients on tablets.\n\n\
+            ## Constraints (Confidence: 3/5)\n\nTBD\n";
+        let review = serde_json::json!({
+            "sections": [
+                { "section": "target users", "confidence": 5 },
+                { "section": "Constraints", "confidence": 1 }
+            ],
+            "open_questions": ["Which regulations apply?"]
+        });
+        let (client, api) = LlmClient::scripted([Reply::content(review.to_string())]);
+        let generator = OutputGenerator::new(client);
+
+        let definition = generator.rescore_definition(edited).await.unwrap();
+
+        let scores: Vec<(&str, u8)> = definition
+            .sections
+            .iter()
+            .map(|section| (section.title.as_str(), section.confidence as u8))
+            .collect();
+        assert_eq!(
+            scores,
+            [
+                ("Target Users", ConfidenceLevel::VeryHigh as u8),
+                ("Constraints", ConfidenceLevel::VeryLow as u8),
+                (OPEN_QUESTIONS_SECTION, ConfidenceLevel::NotApplicable as u8),
+            ]
+        );
+        assert_eq!(definition.sections[2].content, "- Which regulations apply?");
+        let prompt = api.received()[0].prompt();
+        assert!(prompt.contains("Night-shift nurses at three clinics"));
+        assert!(!prompt.contains("Confidence: 2/5"), "{}", prompt);
+    }
+
+    #[tokio::test]
+    async fn fixed_answers_become_a_parsed_definition_without_a_session() {
+        let (client, api) = LlmClient::scripted([Reply::content(
+            "# Clinic Helper\n\n## Project Name and Summary (Confidence: 4/5)\nA triage assistant.\n\n\
+             ## Target Users (Confidence: 5/5)\nNight-shift nurses.",
+        )]);
+        let generator = OutputGenerator::new(client);
+        let answers = vec![
+            Answer::new(
+                Question::free_text("q1", "Who are the primary users?"),
+                "Night-shift nurses",
+            ),
+            Answer::new_skipped(Question::free_text("q2", "What is the budget?")),
+        ];
+        let meta = ContextMeta {
+            domain: Some("Healthcare".to_string()),
+            project_name: Some("Triage Bot".to_string()),
+            ..ContextMeta::default()
+        };
+
+        let definition = generator
+            .generate_from_answers(answers, meta)
+            .await
+            .unwrap();
+
+        assert_eq!(definition.name, "Triage Bot");
+        let sections: Vec<(&str, u8)> = definition
+            .sections
+            .iter()
+            .map(|section| (section.title.as_str(), section.confidence as u8))
+            .collect();
+        assert_eq!(
+            sections,
+            [("Project Name and Summary", 4), ("Target Users", 5)]
+        );
+        let received = api.received();
+        assert_eq!(received.len(), 1);
+        let prompt = received[0].prompt();
+        assert!(prompt.contains("Domain: Healthcare"));
+        assert!(prompt.contains("Who are the primary users?"));
+        assert!(prompt.contains("Night-shift nurses"));
+    }
+
+    #[tokio::test]
+    async fn only_skipped_answers_are_not_sent() {
+        let (client, api) = LlmClient::scripted([]);
+        let generator = OutputGenerator::new(client);
+        let answers = vec![Answer::new_skipped(Question::free_text(
+            "q1",
+            "Who are the primary users?",
+        ))];
+
+        let result = generator
+            .generate_from_answers(answers, ContextMeta::default())
+            .await;
+
+        assert!(result.is_err());
+        assert!(api.received().is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+//! PDF module for the LLM-powered project definition wizard.
+//!
+//! This module renders a project definition as a simple text-layout PDF
+//! using the built-in PDF fonts. It is only available with the `pdf` feature.
+
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+use std::io::BufWriter;
+use std::path::Path;
+
+use super::atomic::write_atomic_with;
+use super::error::Result;
+use super::output::{ConfidenceStyle, ProjectDefinition};
+
+/// A4 page width in millimetres
+const PAGE_WIDTH: f32 = 210.0;
+/// A4 page height in millimetres
+const PAGE_HEIGHT: f32 = 297.0;
+/// Page margin in millimetres
+const MARGIN: f32 = 20.0;
+/// Font size of body text in points
+const BODY_SIZE: f32 = 10.0;
+/// Maximum number of characters per body line
+const BODY_LINE_CHARS: usize = 95;
+
+/// Writes lines of text top-to-bottom, starting new pages as needed
+struct PageWriter {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    y: f32,
+}
+
+impl PageWriter {
+    /// Create a writer for a new document with a single empty page
+    fn new(title: &str) -> Self {
+        let (doc, page, layer) =
+            PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        let layer = doc.get_page(page).get_layer(layer);
+
+        Self {
+            doc,
+            layer,
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    /// Write a single line of text, wrapping to a new page at the bottom margin
+    fn line(&mut self, text: &str, size: f32, font: &IndirectFontRef) {
+        // Line height of roughly 1.4em, converted from points to millimetres
+        let line_height = size * 1.4 * 0.3528;
+
+        if self.y - line_height < MARGIN {
+            let (page, layer) = self
+                .doc
+                .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+
+        self.y -= line_height;
+        self.layer
+            .use_text(printable(text), size, Mm(MARGIN), Mm(self.y), font);
+    }
+
+    /// Leave vertical space
+    fn gap(&mut self, mm: f32) {
+        self.y -= mm;
+    }
+}
+
+/// Keep only characters the built-in (Latin-1) fonts can render
+fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| if (c as u32) < 0x100 { c } else { '?' })
+        .collect()
+}
+
+/// Wrap text into lines of at most `width` characters, breaking on whitespace
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
+
+/// Render a project definition to a PDF file
+pub fn render(definition: &ProjectDefinition, path: &Path) -> Result<()> {
+    let mut writer = PageWriter::new(&definition.name);
+    let regular = writer.doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = writer.doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+    let italic = writer.doc.add_builtin_font(BuiltinFont::HelveticaOblique)?;
+
+    // Title and timestamp
+    for line in wrap(&definition.name, 50) {
+        writer.line(&line, 20.0, &bold);
+    }
+    writer.line(
+        &format!(
+            "Generated on: {}",
+            definition.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+        BODY_SIZE,
+        &italic,
+    );
+    writer.gap(4.0);
+
+    // Sections, with text confidence markers since the built-in fonts have no emoji
+    for section in &definition.sections {
+        let heading = match section.confidence.marker(ConfidenceStyle::Ascii) {
+            Some(marker) => format!("{} {}", section.title, marker),
+            None => section.title.clone(),
+        };
+        writer.gap(3.0);
+        for line in wrap(&heading, 60) {
+            writer.line(&line, 14.0, &bold);
+        }
+
+        for paragraph in section.content.lines() {
+            let paragraph = paragraph.trim().replace("**", "");
+            if paragraph.is_empty() {
+                writer.gap(2.0);
+                continue;
+            }
+            for line in wrap(&paragraph, BODY_LINE_CHARS) {
+                writer.line(&line, BODY_SIZE, &regular);
+            }
+        }
+    }
+
+    write_atomic_with(path, |file| {
+        writer.doc.save(&mut BufWriter::new(file))?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::output::ConfidenceLevel;
+
+    #[test]
+    fn small_definition_renders_a_pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("definition.pdf");
+        let mut definition = ProjectDefinition::new("Triage Bot");
+        definition.add_section(
+            "Target Users",
+            "Nurses in small clinics.",
+            ConfidenceLevel::Low,
+        );
+
+        render(&definition, &path).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.starts_with(b"%PDF-"));
+        assert!(bytes.len() > 100);
+    }
+}
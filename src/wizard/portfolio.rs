@@ -0,0 +1,140 @@
+//! Portfolio module for the LLM-powered project definition wizard.
+//!
+//! This module combines several project definitions into one Markdown
+//! document, each under its own top-level heading, with a table of contents.
+
+use super::output::{ConfidenceStyle, ProjectDefinition};
+
+/// Title of a portfolio document
+const PORTFOLIO_TITLE: &str = "# Project Portfolio";
+
+/// Marker opening the generated table of contents
+const TOC_START: &str = "<!-- toc -->";
+
+/// Marker closing the generated table of contents
+const TOC_END: &str = "<!-- /toc -->";
+
+/// Append a project definition to a portfolio document, returning the new document
+///
+/// `existing` may be an earlier portfolio, a single definition (which becomes the
+/// first project) or empty. The project's heading is made unique among the projects
+/// already in the document, and the table of contents is rebuilt.
+pub fn append_definition(
+    existing: &str,
+    definition: &ProjectDefinition,
+    style: ConfidenceStyle,
+) -> String {
+    // Keep only the projects, dropping the previous title and table of contents
+    let body = match existing.split_once(TOC_END) {
+        Some((_, projects)) => projects.trim(),
+        None => existing.trim(),
+    };
+
+    let mut headings = project_headings(body);
+    let heading = unique_heading(&definition.name, &headings);
+
+    // Replace the project's own title with the disambiguated heading
+    let markdown = definition.to_markdown_with_style(style);
+    let sections = markdown
+        .split_once('\n')
+        .map(|(_, rest)| rest)
+        .unwrap_or_default();
+    let project = format!("# {}\n{}", heading, sections);
+    headings.push(heading);
+
+    let mut document = format!("{}\n\n{}\n", PORTFOLIO_TITLE, TOC_START);
+    for heading in &headings {
+        document.push_str(&format!("- [{}](#{})\n", heading, anchor(heading)));
+    }
+    document.push_str(&format!("{}\n\n", TOC_END));
+
+    if !body.is_empty() {
+        document.push_str(body);
+        document.push_str("\n\n");
+    }
+    document.push_str(project.trim_end());
+    document.push('\n');
+
+    document
+}
+
+/// Get the top-level headings of the projects in a portfolio body
+fn project_headings(body: &str) -> Vec<String> {
+    let mut headings = Vec::new();
+    let mut in_code_block = false;
+
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        } else if !in_code_block && let Some(heading) = line.strip_prefix("# ") {
+            headings.push(heading.trim().to_string());
+        }
+    }
+
+    headings
+}
+
+/// Make a heading unique by adding a number, e.g. `Name (2)`
+fn unique_heading(name: &str, existing: &[String]) -> String {
+    let name = name.trim();
+    if !existing.iter().any(|heading| heading == name) {
+        return name.to_string();
+    }
+
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !existing.contains(candidate))
+        .unwrap()
+}
+
+/// Build the GitHub-style anchor for a heading
+fn anchor(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::output::ConfidenceLevel;
+
+    /// Definition with one section
+    fn definition(name: &str, section: &str) -> ProjectDefinition {
+        let mut definition = ProjectDefinition::new(name);
+        definition.add_section(section, "Details.", ConfidenceLevel::Medium);
+        definition
+    }
+
+    #[test]
+    fn appended_definitions_get_distinct_headings() {
+        let first = append_definition(
+            "",
+            &definition("Triage Bot", "Target Users"),
+            ConfidenceStyle::None,
+        );
+        let second = append_definition(
+            &first,
+            &definition("Triage Bot", "Use Cases"),
+            ConfidenceStyle::None,
+        );
+
+        assert_eq!(
+            project_headings(second.split_once(TOC_END).unwrap().1),
+            ["Triage Bot", "Triage Bot (2)"]
+        );
+        assert!(
+            second.contains("- [Triage Bot](#triage-bot)\n- [Triage Bot (2)](#triage-bot-2)\n")
+        );
+        let first_project = second.find("# Triage Bot\n").unwrap();
+        let second_project = second.find("# Triage Bot (2)\n").unwrap();
+        assert!(second[first_project..second_project].contains("## Target Users"));
+        assert!(second[second_project..].contains("## Use Cases"));
+    }
+}
@@ -0,0 +1,87 @@
+//! Prefill module for the LLM-powered project definition wizard.
+//!
+//! This module loads answers the user already knows from a JSON file and matches
+//! them to the questions as they are asked, so the prompts can start out with the
+//! known answer selected or typed in. The user still confirms or changes each one.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use super::Question;
+use super::context::topic_similarity;
+use super::error::{Result, WizardError};
+
+/// Share of keywords a known question must have in common with the asked one to match
+///
+/// Lower than the threshold for repeated questions, as every pre-filled answer is
+/// shown to the user to accept or change.
+const MATCH_SIMILARITY: f32 = 0.5;
+
+/// Known answers, keyed by question id or question text
+#[derive(Debug, Clone, Default)]
+pub struct Prefill {
+    /// Answers by question id or text
+    answers: BTreeMap<String, String>,
+}
+
+impl Prefill {
+    /// Load known answers from a JSON object mapping question ids or texts to answers
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(WizardError::io("open prefill file", path))?;
+        let answers: BTreeMap<String, String> = serde_json::from_reader(BufReader::new(file))
+            .map_err(WizardError::json(format!(
+                "prefill file {}",
+                path.display()
+            )))?;
+
+        Ok(Self { answers })
+    }
+
+    /// Get the number of known answers
+    pub fn len(&self) -> usize {
+        self.answers.len()
+    }
+
+    /// Check whether there are no known answers
+    pub fn is_empty(&self) -> bool {
+        self.answers.is_empty()
+    }
+
+    /// Find the known answer to a question
+    ///
+    /// Matches the question id first, then the question text ignoring case, and
+    /// finally the text sharing the most keywords with the question, since generated
+    /// questions are rarely worded the same way twice.
+    pub fn answer_for(&self, question: &Question) -> Option<&str> {
+        if let Some(answer) = self.answers.get(&question.id) {
+            return Some(answer);
+        }
+
+        let text = question.text.trim();
+        if let Some((_, answer)) = self
+            .answers
+            .iter()
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(text))
+        {
+            return Some(answer);
+        }
+
+        self.answers
+            .iter()
+            .map(|(key, answer)| (topic_similarity(key, text), answer))
+            .filter(|(similarity, _)| *similarity >= MATCH_SIMILARITY)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, answer)| answer.as_str())
+    }
+}
+
+/// Find the option matching a known answer, ignoring case and surrounding whitespace
+pub fn option_index(answer: &str, options: &[String]) -> Option<usize> {
+    let answer = answer.trim();
+    options
+        .iter()
+        .position(|option| option.trim().eq_ignore_ascii_case(answer))
+}
@@ -0,0 +1,76 @@
+//! Progress module for the LLM-powered project definition wizard.
+//!
+//! This module decides how progress is reported: as regular interactive
+//! output in a terminal, or as plain timestamped log lines without prompts
+//! when the output is redirected or the wizard runs in CI.
+
+use std::io::IsTerminal;
+
+/// How progress is reported to the user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressMode {
+    /// Interactive terminal output with prompts
+    Interactive,
+    /// Plain timestamped log lines without interactive prompts (for CI)
+    Lines,
+}
+
+impl ProgressMode {
+    /// Select the mode from the `--ci` flag and whether stdout is a terminal
+    pub fn select(force_ci: bool, stdout_is_terminal: bool) -> Self {
+        if force_ci || !stdout_is_terminal {
+            Self::Lines
+        } else {
+            Self::Interactive
+        }
+    }
+
+    /// Select the mode from the `--ci` flag, detecting whether stdout is a terminal
+    pub fn detect(force_ci: bool) -> Self {
+        Self::select(force_ci, std::io::stdout().is_terminal())
+    }
+
+    /// Check whether interactive prompts may be shown
+    pub fn is_interactive(&self) -> bool {
+        *self != Self::Lines
+    }
+
+    /// Format a progress message for this mode
+    pub fn format(&self, message: &str) -> String {
+        match self {
+            Self::Interactive => message.to_string(),
+            Self::Lines => format!("[{}] {}", chrono::Local::now().format("%H:%M:%S"), message),
+        }
+    }
+
+    /// Report a progress message
+    pub fn log(&self, message: &str) {
+        println!("{}", self.format(message));
+    }
+
+    /// Report a warning, on stderr so it stays out of the printed output
+    pub fn warn(&self, message: &str) {
+        eprintln!("{}", self.format(message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirected_stdout_selects_log_lines_without_prompts() {
+        assert_eq!(ProgressMode::select(false, true), ProgressMode::Interactive);
+        assert_eq!(ProgressMode::select(false, false), ProgressMode::Lines);
+        assert_eq!(ProgressMode::select(true, true), ProgressMode::Lines);
+
+        assert!(ProgressMode::select(false, true).is_interactive());
+        assert!(!ProgressMode::select(false, false).is_interactive());
+        assert!(
+            ProgressMode::Lines
+                .format("Generating question 3/10...")
+                .ends_with("] Generating question 3/10...")
+        );
+        assert_eq!(ProgressMode::Interactive.format("Done"), "Done");
+    }
+}
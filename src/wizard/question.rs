@@ -6,6 +6,8 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::wizard::error::{Result, WizardError};
+
 /// Enum representing different types of questions that can be asked.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum QuestionType {
@@ -30,6 +32,28 @@ impl fmt::Display for QuestionType {
     }
 }
 
+/// Where a question came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuestionSource {
+    /// One of the template's initial questions
+    Template,
+    /// Generated by the LLM
+    #[default]
+    Llm,
+    /// Written by the user
+    User,
+}
+
+impl fmt::Display for QuestionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuestionSource::Template => write!(f, "Template"),
+            QuestionSource::Llm => write!(f, "LLM"),
+            QuestionSource::User => write!(f, "User"),
+        }
+    }
+}
+
 /// Struct representing a question in the wizard.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Question {
@@ -45,6 +69,21 @@ pub struct Question {
     pub help_text: Option<String>,
     /// Unique identifier for the question
     pub id: String,
+    /// Where the question came from; questions saved before this was tracked were generated
+    #[serde(default)]
+    pub source: QuestionSource,
+    /// Whether the answer may contain personal data that must be kept out of logs
+    #[serde(default)]
+    pub sensitive: bool,
+    /// Only ask this template question if the question with the given id got the given answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<(String, String)>,
+    /// Suggested answer to free text questions, accepted by pressing Enter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_answer: Option<String>,
+    /// Why the question is being asked, shown above it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 impl Question {
@@ -61,6 +100,11 @@ impl Question {
             options: Some(options),
             scale: None,
             help_text: None,
+            source: QuestionSource::default(),
+            sensitive: false,
+            depends_on: None,
+            default_answer: None,
+            reason: None,
         }
     }
 
@@ -73,6 +117,11 @@ impl Question {
             options: Some(vec!["Yes".to_string(), "No".to_string()]),
             scale: None,
             help_text: None,
+            source: QuestionSource::default(),
+            sensitive: false,
+            depends_on: None,
+            default_answer: None,
+            reason: None,
         }
     }
 
@@ -85,6 +134,11 @@ impl Question {
             options: None,
             scale: Some((min, max)),
             help_text: None,
+            source: QuestionSource::default(),
+            sensitive: false,
+            depends_on: None,
+            default_answer: None,
+            reason: None,
         }
     }
 
@@ -97,6 +151,11 @@ impl Question {
             options: None,
             scale: None,
             help_text: None,
+            source: QuestionSource::default(),
+            sensitive: false,
+            depends_on: None,
+            default_answer: None,
+            reason: None,
         }
     }
 
@@ -105,8 +164,118 @@ impl Question {
         self.help_text = Some(help_text.into());
         self
     }
+
+    /// Set where the question came from
+    pub fn with_source(mut self, source: QuestionSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Mark whether the answer may contain personal data
+    pub fn with_sensitive(mut self, sensitive: bool) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    /// Suggest an answer the user can accept by pressing Enter
+    pub fn with_default_answer(mut self, default_answer: impl Into<String>) -> Self {
+        self.default_answer = Some(default_answer.into());
+        self
+    }
+
+    /// Explain why the question is being asked
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    /// Only ask the question if the question with the given id got the given answer
+    #[allow(
+        dead_code,
+        reason = "builder for templates defined in code; the wizard loads them from files"
+    )]
+    pub fn with_depends_on(
+        mut self,
+        question_id: impl Into<String>,
+        expected_answer: impl Into<String>,
+    ) -> Self {
+        self.depends_on = Some((question_id.into(), expected_answer.into()));
+        self
+    }
+
+    /// Check whether the question text mentions a topic, ignoring case
+    ///
+    /// A topic of several words also matches when each of its words appears
+    /// somewhere in the text, as in "budget" and "range" for "budget range".
+    pub fn mentions(&self, topic: &str) -> bool {
+        let text = self.text.to_lowercase();
+        let topic = topic.trim().to_lowercase();
+        if topic.is_empty() {
+            return false;
+        }
+
+        text.contains(&topic) || topic.split_whitespace().all(|word| text.contains(word))
+    }
+
+    /// Get the choices offered for the question, if it is answered by picking one
+    ///
+    /// Yes/no questions always offer "Yes" and "No", whatever options the model sent
+    /// along, and rating scale questions offer each value of their scale.
+    pub fn choices(&self) -> Option<Vec<String>> {
+        match self.question_type {
+            QuestionType::MultipleChoice => self.options.clone(),
+            QuestionType::YesNo => Some(vec!["Yes".to_string(), "No".to_string()]),
+            QuestionType::RatingScale => self
+                .scale
+                .map(|(min, max)| (min..=max).map(|n| n.to_string()).collect()),
+            QuestionType::FreeText => None,
+        }
+    }
+
+    /// Check that the question's fields are consistent with its type
+    pub fn validate(&self) -> Result<()> {
+        if self.text.trim().is_empty() {
+            return Err(WizardError::Invalid(format!(
+                "Question '{}' has empty text",
+                self.id
+            )));
+        }
+
+        match self.question_type {
+            QuestionType::MultipleChoice => match &self.options {
+                Some(options) if !options.is_empty() => {}
+                _ => {
+                    return Err(WizardError::Invalid(format!(
+                        "Multiple choice question '{}' has no options",
+                        self.id
+                    )));
+                }
+            },
+            QuestionType::RatingScale => match self.scale {
+                Some((min, max)) if min < max => {}
+                Some((min, max)) => {
+                    return Err(WizardError::Invalid(format!(
+                        "Rating scale question '{}' has an invalid scale {}-{}",
+                        self.id, min, max
+                    )));
+                }
+                None => {
+                    return Err(WizardError::Invalid(format!(
+                        "Rating scale question '{}' has no scale",
+                        self.id
+                    )));
+                }
+            },
+            QuestionType::YesNo | QuestionType::FreeText => {}
+        }
+
+        Ok(())
+    }
 }
 
+/// Function applied to each generated question before it is displayed
+pub type QuestionHook = Box<dyn Fn(&mut Question) + Send + Sync>;
+
 /// Struct for generating questions based on context
 pub struct QuestionGenerator {
     /// The LLM client used for generating questions
@@ -123,8 +292,33 @@ impl QuestionGenerator {
     pub async fn generate_next_question(
         &self,
         context: &crate::wizard::Context,
-    ) -> anyhow::Result<Question> {
+        max_questions: usize,
+    ) -> Result<Question> {
+        let progress = if max_questions == 0 {
+            1.0
+        } else {
+            context.answer_count() as f32 / max_questions as f32
+        };
+
         // Use the LLM to generate the next question based on the context
-        self.llm_client.generate_question(context).await
+        self.llm_client.generate_question(context, progress).await
+    }
+
+    /// Generate several candidate questions at once based on the current context
+    pub async fn generate_question_batch(
+        &self,
+        context: &crate::wizard::Context,
+        max_questions: usize,
+        count: usize,
+    ) -> Result<Vec<Question>> {
+        let progress = if max_questions == 0 {
+            1.0
+        } else {
+            context.answer_count() as f32 / max_questions as f32
+        };
+
+        self.llm_client
+            .generate_question_batch(context, progress, count)
+            .await
     }
 }
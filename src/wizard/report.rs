@@ -0,0 +1,298 @@
+//! Report module for the LLM-powered project definition wizard.
+//!
+//! This module renders a saved session as a single self-contained HTML file,
+//! with collapsible sections for the interview transcript and the generated
+//! definition, the session metrics and the questions left open.
+
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Parser, Tag, TagEnd};
+
+use super::metrics::SessionMetrics;
+use super::output::{ConfidenceStyle, ProjectDefinition};
+use super::session::Session;
+
+/// Inline stylesheet, so the report needs no other files
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:50rem;margin:2rem auto;\
+padding:0 1rem;line-height:1.5;color:#222}\
+details{border:1px solid #ddd;border-radius:6px;margin:1rem 0;padding:.5rem 1rem}\
+summary{cursor:pointer;font-weight:600;font-size:1.15rem}\
+.question{font-weight:600;margin-top:1rem}\
+.answer{margin:.25rem 0 0 1rem;white-space:pre-wrap}\
+.meta{color:#666;font-size:.9rem}\
+table{border-collapse:collapse}td,th{border:1px solid #ddd;padding:.25rem .75rem;text-align:left}\
+pre{background:#f6f6f6;padding:.75rem;overflow-x:auto}";
+
+/// Escape text for use in HTML content and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Get the HTML tag name of a heading level
+fn heading_tag(level: HeadingLevel) -> &'static str {
+    match level {
+        HeadingLevel::H1 => "h1",
+        HeadingLevel::H2 => "h2",
+        HeadingLevel::H3 => "h3",
+        HeadingLevel::H4 => "h4",
+        HeadingLevel::H5 => "h5",
+        HeadingLevel::H6 => "h6",
+    }
+}
+
+/// Render the Markdown of a section body as HTML
+///
+/// Covers the constructs the definitions use (paragraphs, headings, lists,
+/// emphasis, code and links); raw HTML in the Markdown is escaped, not passed through.
+fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::new();
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Paragraph => html.push_str("<p>"),
+                Tag::Heading { level, .. } => html.push_str(&format!("<{}>", heading_tag(level))),
+                Tag::BlockQuote(_) => html.push_str("<blockquote>"),
+                Tag::CodeBlock(CodeBlockKind::Fenced(_) | CodeBlockKind::Indented) => {
+                    html.push_str("<pre><code>")
+                }
+                Tag::List(Some(start)) => html.push_str(&format!("<ol start=\"{}\">", start)),
+                Tag::List(None) => html.push_str("<ul>"),
+                Tag::Item => html.push_str("<li>"),
+                Tag::Emphasis => html.push_str("<em>"),
+                Tag::Strong => html.push_str("<strong>"),
+                Tag::Strikethrough => html.push_str("<del>"),
+                Tag::Link { dest_url, .. } => {
+                    html.push_str(&format!("<a href=\"{}\">", escape(&dest_url)))
+                }
+                _ => {}
+            },
+            Event::End(tag) => match tag {
+                TagEnd::Paragraph => html.push_str("</p>\n"),
+                TagEnd::Heading(level) => html.push_str(&format!("</{}>\n", heading_tag(level))),
+                TagEnd::BlockQuote(_) => html.push_str("</blockquote>\n"),
+                TagEnd::CodeBlock => html.push_str("</code></pre>\n"),
+                TagEnd::List(true) => html.push_str("</ol>\n"),
+                TagEnd::List(false) => html.push_str("</ul>\n"),
+                TagEnd::Item => html.push_str("</li>\n"),
+                TagEnd::Emphasis => html.push_str("</em>"),
+                TagEnd::Strong => html.push_str("</strong>"),
+                TagEnd::Strikethrough => html.push_str("</del>"),
+                TagEnd::Link => html.push_str("</a>"),
+                _ => {}
+            },
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                html.push_str(&escape(&text))
+            }
+            Event::Code(code) => html.push_str(&format!("<code>{}</code>", escape(&code))),
+            Event::SoftBreak => html.push('\n'),
+            Event::HardBreak => html.push_str("<br>\n"),
+            Event::Rule => html.push_str("<hr>\n"),
+            _ => {}
+        }
+    }
+
+    html
+}
+
+/// Render the interview transcript, one question and answer after another
+fn render_transcript(session: &Session) -> String {
+    let context = &session.context;
+    let mut html = String::from("<details open>\n<summary>Interview transcript</summary>\n");
+
+    if let Some(hints) = &context.starting_hints {
+        html.push_str(&format!(
+            "<p class=\"meta\">Starting point: {}</p>\n",
+            escape(hints.trim())
+        ));
+    }
+    if let Some(summary) = &context.summary {
+        html.push_str(&format!(
+            "<p class=\"meta\">Earlier answers: {}</p>\n",
+            escape(summary.trim())
+        ));
+    }
+    if context.history.is_empty() {
+        html.push_str("<p>No questions answered yet.</p>\n");
+    }
+
+    let offset = context.summarized_count;
+    for (index, answer) in context.redacted_history().iter().enumerate() {
+        html.push_str(&format!(
+            "<p class=\"question\">Q{}: {}</p>\n",
+            offset + index + 1,
+            escape(&answer.question.text)
+        ));
+        let response = if answer.skipped {
+            "<em>Skipped</em>".to_string()
+        } else {
+            escape(answer.response.trim())
+        };
+        html.push_str(&format!("<div class=\"answer\">{}</div>\n", response));
+        if !answer.tags.is_empty() {
+            html.push_str(&format!(
+                "<p class=\"meta\">Tags: {}</p>\n",
+                escape(&answer.tags.join(", "))
+            ));
+        }
+    }
+
+    html.push_str("</details>\n");
+    html
+}
+
+/// Render the project definition with a confidence marker on each section
+fn render_definition(definition: Option<&ProjectDefinition>) -> String {
+    let mut html = String::from("<details open>\n<summary>Project definition</summary>\n");
+
+    let Some(definition) = definition else {
+        html.push_str("<p>No definition has been generated for this session yet.</p>\n");
+        html.push_str("</details>\n");
+        return html;
+    };
+
+    html.push_str(&format!(
+        "<p class=\"meta\">Generated on {}</p>\n",
+        definition.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+    ));
+    for section in &definition.sections {
+        let title = match section.confidence.marker(ConfidenceStyle::Emoji) {
+            Some(marker) => format!(
+                "{} <span title=\"Confidence {}/5\">{}</span>",
+                escape(&section.title),
+                section.confidence as u8,
+                marker
+            ),
+            None => escape(&section.title),
+        };
+        html.push_str(&format!("<h2>{}</h2>\n", title));
+        html.push_str(&markdown_to_html(&section.content));
+    }
+
+    html.push_str("</details>\n");
+    html
+}
+
+/// Render the session metrics as a short summary and a table of question types
+fn render_metrics(metrics: &SessionMetrics) -> String {
+    let mut html = String::from("<details>\n<summary>Session metrics</summary>\n");
+    html.push_str(&format!(
+        "<p>Questions asked: {} ({} skipped, {} edited)</p>\n",
+        metrics.questions, metrics.skipped, metrics.edited
+    ));
+
+    html.push_str("<table>\n<tr><th>Question type</th><th>Count</th><th>Share</th></tr>\n");
+    let total = metrics.question_types.total();
+    for (label, count) in metrics.question_types.labeled() {
+        let share = (count * 100 + total / 2).checked_div(total).unwrap_or(0);
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}%</td></tr>\n",
+            label, count, share
+        ));
+    }
+    html.push_str("</table>\n</details>\n");
+    html
+}
+
+/// Render the questions that were skipped during the interview, if any
+fn render_open_questions(session: &Session) -> Option<String> {
+    let skipped: Vec<&str> = session
+        .context
+        .history
+        .iter()
+        .filter(|answer| answer.skipped)
+        .map(|answer| answer.question.text.as_str())
+        .collect();
+    if skipped.is_empty() {
+        return None;
+    }
+
+    let mut html = String::from("<details open>\n<summary>Open questions</summary>\n<ul>\n");
+    for question in skipped {
+        html.push_str(&format!("<li>{}</li>\n", escape(question)));
+    }
+    html.push_str("</ul>\n</details>\n");
+    Some(html)
+}
+
+/// Render a session as a self-contained HTML report
+pub fn render(session: &Session) -> String {
+    let title = session
+        .definition
+        .as_ref()
+        .map(|definition| definition.name.clone())
+        .or_else(|| session.context.project_name.clone())
+        .unwrap_or_else(|| "Project Session Report".to_string());
+
+    let mut html = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+    html.push_str("<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape(&title)));
+    html.push_str(&format!("<style>{}</style>\n", STYLE));
+    html.push_str("</head>\n<body>\n");
+
+    html.push_str(&format!("<h1>{}</h1>\n", escape(&title)));
+    let mut overview = Vec::new();
+    if let Some(domain) = &session.context.domain {
+        overview.push(format!("Domain: {}", escape(domain)));
+    }
+    overview.push(format!("State: {:?}", session.state));
+    html.push_str(&format!("<p class=\"meta\">{}</p>\n", overview.join(" · ")));
+
+    html.push_str(&render_definition(session.definition.as_ref()));
+    if let Some(open_questions) = render_open_questions(session) {
+        html.push_str(&open_questions);
+    }
+    html.push_str(&render_transcript(session));
+    html.push_str(&render_metrics(&SessionMetrics::from_context(
+        &session.context,
+    )));
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::Question;
+    use crate::wizard::output::ConfidenceLevel;
+
+    #[test]
+    fn report_contains_the_transcript_and_the_definition() {
+        let mut session = Session::new();
+        session
+            .context
+            .add_answer(
+                Question::free_text("q1", "Who are the users?"),
+                "Night-shift <nurses>",
+            )
+            .unwrap();
+        let mut definition = ProjectDefinition::new("Triage Bot");
+        definition.add_section(
+            "Target Users",
+            "Nurses on **night shifts**.",
+            ConfidenceLevel::High,
+        );
+        session.definition = Some(definition);
+
+        let html = render(&session);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Triage Bot</title>"));
+        assert!(html.contains("Who are the users?"), "{}", html);
+        assert!(html.contains("Night-shift &lt;nurses&gt;"), "{}", html);
+        assert!(html.contains("<h2>Target Users <span title=\"Confidence 4/5\">"));
+        assert!(html.contains("<strong>night shifts</strong>"), "{}", html);
+        assert!(html.contains("<summary>Session metrics</summary>"));
+        assert_eq!(html.matches("<details").count(), 3);
+    }
+}
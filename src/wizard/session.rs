@@ -3,11 +3,25 @@
 //! This module manages the wizard session and coordinates the interaction
 //! between the different components.
 
-use anyhow::Result;
+use fs2::FileExt;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
 
-use super::{Context, LlmClient, OutputGenerator, Question, QuestionGenerator, Template};
+use super::atomic::write_atomic;
+use super::error::Result;
+use super::llm::{DomainGuess, ModelSettings, SectionConfidence};
+use super::metrics::SessionMetrics;
+use super::moderation::{Moderation, ModerationOutcome};
+use super::output::{
+    ConfidenceLevel, ConfidenceStyle, DEFAULT_PROJECT_NAME, DuplicateSectionPolicy, MarkdownStyle,
+    OutputFormat, ProjectDefinition, clean_project_name, markdown_to_plain_text, project_file_stem,
+};
+use super::portfolio;
+use super::question::{QuestionHook, QuestionType};
+use super::{
+    Context, LlmClient, OutputGenerator, Question, QuestionGenerator, Template, WizardError,
+};
 
 /// State of the wizard session
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -24,6 +38,85 @@ pub enum SessionState {
     Error,
 }
 
+/// Result of validating a session
+#[derive(Debug, Default)]
+pub struct SessionValidation {
+    /// Problems that make the session unusable
+    pub errors: Vec<String>,
+    /// Problems that are suspicious but not fatal
+    pub warnings: Vec<String>,
+}
+
+impl SessionValidation {
+    /// Check whether the session passed validation
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Advisory lock on a session file, released when dropped
+///
+/// The lock is taken on a `.<name>.lock` file next to the session, because saving
+/// replaces the session file itself.
+#[derive(Debug)]
+pub struct SessionLock {
+    /// The locked file
+    file: File,
+    /// Path of the session file the lock protects
+    session_path: PathBuf,
+}
+
+impl SessionLock {
+    /// Lock a session file, failing if another process holds the lock
+    pub fn acquire(session_path: &Path) -> Result<Self> {
+        let lock_path = Self::lock_path(session_path);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(WizardError::io("open lock file", &lock_path))?;
+
+        if file.try_lock_exclusive().is_err() {
+            return Err(WizardError::Locked(format!(
+                "Session {} is already open in another process (lock file: {})",
+                session_path.display(),
+                lock_path.display()
+            )));
+        }
+
+        Ok(Self {
+            file,
+            session_path: Self::normalize(session_path),
+        })
+    }
+
+    /// Check whether this lock protects the given session file
+    pub fn covers(&self, session_path: &Path) -> bool {
+        self.session_path == Self::normalize(session_path)
+    }
+
+    /// Get the path of the lock file for a session file
+    fn lock_path(session_path: &Path) -> PathBuf {
+        let file_name = session_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "session".to_string());
+        session_path.with_file_name(format!(".{}.lock", file_name))
+    }
+
+    /// Get a comparable form of a session path, which may not exist yet
+    fn normalize(session_path: &Path) -> PathBuf {
+        std::fs::canonicalize(session_path).unwrap_or_else(|_| session_path.to_path_buf())
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
 /// Session for the wizard
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Session {
@@ -33,25 +126,99 @@ pub struct Session {
     pub state: SessionState,
     /// Maximum number of questions to ask
     pub max_questions: usize,
+    /// Message of the error that stopped the session, if any
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Model settings the session was created with, reused when it is continued
+    #[serde(default)]
+    pub model: Option<ModelSettings>,
     /// Current question
     #[serde(skip)]
     pub current_question: Option<Question>,
     /// Project definition output
     #[serde(skip)]
     pub output: Option<String>,
+    /// Parsed project definition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub definition: Option<ProjectDefinition>,
+    /// Definition sections still to be asked about before regular questions, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub focus_areas: Vec<String>,
+    /// Generated questions waiting to be asked, in batch mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queued_questions: Vec<Question>,
+    /// Average confidence of each definition generated for the session, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub confidence_history: Vec<ConfidenceSnapshot>,
+    /// Lock held on the session file while it is open
+    #[serde(skip)]
+    pub lock: Option<SessionLock>,
+    /// Whether to replace the answers to sensitive questions by `[REDACTED]` when saving
+    #[serde(skip)]
+    pub redact_sensitive: bool,
+}
+
+/// Average confidence of a generated project definition, recorded to follow it across refinements
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfidenceSnapshot {
+    /// When the definition was generated
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Average section confidence (1-5)
+    pub average: f32,
 }
 
 const MAX_QUESTIONS: usize =25;
 
+/// Number of answers after which the domain is inferred if none was given
+pub const DOMAIN_INFERENCE_AFTER: usize = 2;
+
+/// Number of answers after which the model is asked for a project name
+pub const PROJECT_NAMING_AFTER: usize = 2;
+
+/// Minimum classification confidence for an inferred domain to be used
+pub const DOMAIN_INFERENCE_THRESHOLD: f32 = 0.6;
+
+/// Opening line of a free-form chat, recorded as the question of the first message
+pub const CHAT_OPENER: &str = "Is there anything else you'd like to clarify?";
+
+/// Tag added to answers given in a free-form chat
+pub const CHAT_TAG: &str = "chat";
+
+/// Repetitive questions in a row after which the model is considered out of questions
+pub const DEFAULT_EXHAUSTION_THRESHOLD: usize = 3;
+
+/// What happens to a question that isn't answered within the per-question time limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuestionTimeoutAction {
+    /// Skip the question
+    #[default]
+    Skip,
+    /// Accept the known or suggested answer, skipping the question if there is none
+    AcceptDefault,
+}
+
 impl Session {
     /// Create a new session
+    #[allow(
+        dead_code,
+        reason = "sessions are created with a name by SessionManager"
+    )]
     pub fn new() -> Self {
         Self {
             context: Context::new(),
             state: SessionState::Initial,
             max_questions: MAX_QUESTIONS, // Default max questions
+            error: None,
+            model: None,
             current_question: None,
             output: None,
+            definition: None,
+            focus_areas: Vec::new(),
+            queued_questions: Vec::new(),
+            confidence_history: Vec::new(),
+            lock: None,
+            redact_sensitive: false,
         }
     }
 
@@ -61,8 +228,16 @@ impl Session {
             context,
             state: SessionState::Initial,
             max_questions: MAX_QUESTIONS,
+            error: None,
+            model: None,
             current_question: None,
             output: None,
+            definition: None,
+            focus_areas: Vec::new(),
+            queued_questions: Vec::new(),
+            confidence_history: Vec::new(),
+            lock: None,
+            redact_sensitive: false,
         }
     }
 
@@ -75,8 +250,36 @@ impl Session {
             context,
             state: SessionState::Initial,
             max_questions: MAX_QUESTIONS,
+            error: None,
+            model: None,
             current_question: None,
             output: None,
+            definition: None,
+            focus_areas: Vec::new(),
+            queued_questions: Vec::new(),
+            confidence_history: Vec::new(),
+            lock: None,
+            redact_sensitive: false,
+        }
+    }
+
+    /// Record the average confidence of the stored definition, if it has confidence scores
+    pub fn record_confidence(&mut self) {
+        if let Some(definition) = &self.definition
+            && let Some(average) = definition.average_confidence()
+        {
+            self.confidence_history.push(ConfidenceSnapshot {
+                timestamp: definition.timestamp,
+                average,
+            });
+        }
+    }
+
+    /// Get the change in average confidence from the first recorded definition to the last
+    pub fn confidence_change(&self) -> Option<f32> {
+        match self.confidence_history.as_slice() {
+            [first, .., last] => Some(last.average - first.average),
+            _ => None,
         }
     }
 
@@ -86,18 +289,208 @@ impl Session {
         self
     }
 
+    /// Clear a previous error so the failed step can be retried
+    pub fn clear_error(&mut self) {
+        if self.state == SessionState::Error {
+            self.state = SessionState::Questioning;
+        }
+        self.error = None;
+    }
+
     /// Save the session to a file
+    ///
+    /// Fails if the file is open in another process. The lock is only taken for
+    /// the duration of the write unless this session already holds it.
     pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
-        let json = serde_json::to_string_pretty(self)?;
-        std::fs::write(path, json)?;
+        let path = path.as_ref();
+        let _lock = match &self.lock {
+            Some(lock) if lock.covers(path) => None,
+            _ => Some(SessionLock::acquire(path)?),
+        };
+
+        let json = if self.redact_sensitive {
+            let mut value =
+                serde_json::to_value(self).map_err(WizardError::serialize("session"))?;
+            value["context"]["history"] = serde_json::to_value(self.context.redacted_history())
+                .map_err(WizardError::serialize("session"))?;
+            serde_json::to_string_pretty(&value)
+        } else {
+            serde_json::to_string_pretty(self)
+        }
+        .map_err(WizardError::serialize("session"))?;
+        write_atomic(path, json)?;
         Ok(())
     }
 
+    /// Open a session file, locking it against other processes until the session is dropped
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let lock = SessionLock::acquire(path.as_ref())?;
+        let mut session = Self::load_from_file(path)?;
+        session.lock = Some(lock);
+        Ok(session)
+    }
+
     /// Load a session from a file
     pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self> {
-        let json = std::fs::read_to_string(path)?;
-        let session: Self = serde_json::from_str(&json)?;
-        Ok(session)
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(WizardError::io("read", path))?;
+        serde_json::from_str(&json).map_err(WizardError::json("session file"))
+    }
+
+    /// Check the session for internal consistency
+    pub fn validate(&self) -> SessionValidation {
+        let mut validation = SessionValidation::default();
+        let history_len = self.context.history.len();
+
+        if self.context.current_index > history_len {
+            validation.errors.push(format!(
+                "current_index {} is past the end of the history ({} answers)",
+                self.context.current_index, history_len
+            ));
+        }
+
+        for (i, answer) in self.context.history.iter().enumerate() {
+            if let Err(e) = answer.question.validate() {
+                validation.errors.push(format!("Answer {}: {}", i + 1, e));
+            }
+
+            if let Some(confidence) = answer.self_confidence
+                && !(1..=5).contains(&confidence)
+            {
+                validation.errors.push(format!(
+                    "Answer {}: self-reported confidence {} is outside 1-5",
+                    i + 1,
+                    confidence
+                ));
+            }
+        }
+
+        if self.max_questions < history_len {
+            validation.warnings.push(format!(
+                "max_questions ({}) is lower than the number of answers ({})",
+                self.max_questions, history_len
+            ));
+        }
+
+        validation
+    }
+}
+
+/// Answer in a saved session that matched a search
+#[derive(Debug, Clone)]
+pub struct SearchMatch {
+    /// Path of the session file
+    pub path: PathBuf,
+    /// Position of the answer in the session history (1-based)
+    pub number: usize,
+    /// The question text
+    pub question: String,
+    /// The user's response
+    pub response: String,
+}
+
+/// Results of searching a directory of saved sessions
+#[derive(Debug, Default)]
+pub struct SearchResults {
+    /// Answers that matched the query
+    pub matches: Vec<SearchMatch>,
+    /// Files that could not be read as sessions, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Sessions loaded from a directory
+#[derive(Debug, Default)]
+pub struct LoadedSessions {
+    /// Sessions that could be read, with their paths, in path order
+    pub sessions: Vec<(PathBuf, Session)>,
+    /// Files that could not be read as sessions, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Load every session file (`*.json`) in a directory, recording the files that can't be read
+pub fn load_sessions_in_dir(dir: impl AsRef<Path>) -> Result<LoadedSessions> {
+    let mut loaded = LoadedSessions::default();
+
+    let dir = dir.as_ref();
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(WizardError::io("read", dir))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        match Session::load_from_file(&path) {
+            Ok(session) => loaded.sessions.push((path, session)),
+            Err(e) => loaded.skipped.push((path, e.to_string())),
+        }
+    }
+
+    Ok(loaded)
+}
+
+/// Search the questions and responses of every session file in a directory (case-insensitive)
+pub fn search_sessions(dir: impl AsRef<Path>, query: &str) -> Result<SearchResults> {
+    let query = query.to_lowercase();
+    let loaded = load_sessions_in_dir(dir)?;
+    let mut results = SearchResults {
+        skipped: loaded.skipped,
+        ..SearchResults::default()
+    };
+
+    for (path, session) in loaded.sessions {
+        for (i, answer) in session.context.history.iter().enumerate() {
+            if answer.question.text.to_lowercase().contains(&query)
+                || answer.response.to_lowercase().contains(&query)
+            {
+                results.matches.push(SearchMatch {
+                    path: path.clone(),
+                    number: i + 1,
+                    question: answer.question.text.clone(),
+                    response: answer.response.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Get the sections whose projected confidence is below the threshold, weakest first
+pub fn sections_below_threshold(estimates: &[SectionConfidence], threshold: u8) -> Vec<String> {
+    let mut weak: Vec<&SectionConfidence> = estimates
+        .iter()
+        .filter(|estimate| estimate.confidence < threshold)
+        .collect();
+    weak.sort_by_key(|estimate| estimate.confidence);
+
+    weak.into_iter()
+        .map(|estimate| estimate.section.clone())
+        .collect()
+}
+
+/// Position of a question in the interview, as shown to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuestionPosition {
+    /// 1-based number of the question
+    pub number: usize,
+    /// Maximum number of questions in the session
+    pub max_questions: usize,
+    /// Number of questions skipped so far
+    pub skipped: usize,
+}
+
+impl std::fmt::Display for QuestionPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Question {} of up to {}",
+            self.number, self.max_questions
+        )?;
+        if self.skipped > 0 {
+            write!(f, ", {} skipped", self.skipped)?;
+        }
+        Ok(())
     }
 }
 
@@ -111,6 +504,39 @@ pub struct SessionManager {
     question_generator: QuestionGenerator,
     /// The output generator
     output_generator: OutputGenerator,
+    /// House style of the generated document, including how confidence levels are marked
+    markdown_style: MarkdownStyle,
+    /// File format used when exporting the project definition
+    output_format: OutputFormat,
+    /// Whether to add the session metrics as an appendix to the output
+    metrics_appendix: bool,
+    /// Post-processing applied to each generated question before it is displayed
+    question_hook: Option<QuestionHook>,
+    /// Repetitive questions in a row that signal the model has run out of questions (0 = never)
+    exhaustion_threshold: usize,
+    /// Number of repetitive questions generated in a row
+    repetitive_streak: usize,
+    /// Whether to ask the model for a running note after each answer
+    running_notes: bool,
+    /// Number of questions generated at once for the user to choose from, if batching
+    batch_size: Option<usize>,
+    /// Check applied to each answer before it is sent to the model
+    moderation: Moderation,
+    /// Follow-up and clarifying questions asked past the question limit
+    ///
+    /// Not saved with the session, so the saved limit stays the one the user chose.
+    extra_questions: usize,
+}
+
+/// Decision of the confidence gate before the next question
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfidenceGate {
+    /// Too few questions have been answered to check the sections yet
+    NotReached,
+    /// The section is projected below the threshold and should be asked about
+    FollowUp(String),
+    /// Every section is projected to reach the threshold
+    Passed,
 }
 
 impl SessionManager {
@@ -119,70 +545,573 @@ impl SessionManager {
         let question_generator = QuestionGenerator::new(llm_client.clone());
         let output_generator = OutputGenerator::new(llm_client.clone());
 
-        Self {
+        let manager = Self {
             session,
             llm_client,
             question_generator,
             output_generator,
+            markdown_style: MarkdownStyle::default(),
+            output_format: OutputFormat::default(),
+            metrics_appendix: false,
+            question_hook: None,
+            exhaustion_threshold: DEFAULT_EXHAUSTION_THRESHOLD,
+            repetitive_streak: 0,
+            running_notes: false,
+            batch_size: None,
+            moderation: Moderation::default(),
+            extra_questions: 0,
+        };
+        manager.protect_sensitive_answers();
+        manager
+    }
+
+    /// Keep the answers to sensitive questions, including earlier revisions, out of the transcript
+    pub fn protect_sensitive_answers(&self) {
+        for response in self.session.context.sensitive_responses() {
+            self.llm_client.redact_in_transcript(response);
         }
     }
 
+    /// Transform each generated question before it is displayed, e.g. to add a disclaimer
+    #[allow(
+        dead_code,
+        reason = "extension point for integrators; the CLI installs no hook"
+    )]
+    pub fn with_question_hook(
+        mut self,
+        hook: impl Fn(&mut Question) + Send + Sync + 'static,
+    ) -> Self {
+        self.question_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Set how many repetitive questions in a row signal that the model has run out of questions
+    ///
+    /// A threshold of 0 turns the detection off.
+    pub fn with_exhaustion_threshold(mut self, threshold: usize) -> Self {
+        self.exhaustion_threshold = threshold;
+        self
+    }
+
+    /// Generate several questions at once and queue them, letting the user choose which to answer
+    pub fn with_batch_questions(mut self, batch_size: Option<usize>) -> Self {
+        self.batch_size = batch_size.filter(|&size| size > 1);
+        self
+    }
+
+    /// Check each answer with the given moderation before it is sent to the model
+    pub fn with_moderation(mut self, moderation: Moderation) -> Self {
+        self.moderation = moderation;
+        self
+    }
+
+    /// Check an answer under the moderation policy before it is recorded
+    pub async fn moderate_answer(&self, response: &str) -> ModerationOutcome {
+        self.moderation.review(response).await
+    }
+
+    /// Ask the model for a running note after each answer, fed into later prompts
+    pub fn with_running_notes(mut self, running_notes: bool) -> Self {
+        self.running_notes = running_notes;
+        self
+    }
+
+    /// Add the session metrics as an appendix to the output
+    pub fn with_metrics_appendix(mut self, metrics_appendix: bool) -> Self {
+        self.metrics_appendix = metrics_appendix;
+        self
+    }
+
+    /// Set the file format used when exporting the project definition
+    pub fn with_output_format(mut self, format: OutputFormat) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// Render the generated document in a house style, keeping the confidence marker style
+    pub fn with_markdown_style(mut self, style: MarkdownStyle) -> Self {
+        self.markdown_style = MarkdownStyle {
+            confidence: self.markdown_style.confidence,
+            ..style
+        };
+        self
+    }
+
+    /// Set how confidence levels are marked in the generated document
+    pub fn with_confidence_style(mut self, style: ConfidenceStyle) -> Self {
+        self.markdown_style.confidence = style;
+        self
+    }
+
+    /// Save the raw LLM response for the project definition to a file before parsing
+    pub fn with_raw_output_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output_generator = self.output_generator.with_raw_output_path(path);
+        self
+    }
+
+    /// Set the confidence assumed for sections the model didn't mark with a score
+    pub fn with_default_confidence(mut self, confidence: ConfidenceLevel) -> Self {
+        self.output_generator = self.output_generator.with_default_confidence(confidence);
+        self
+    }
+
+    /// Stream the project definition, writing it to the raw output path as it arrives
+    pub fn with_stream(mut self, stream: bool) -> Self {
+        self.output_generator = self.output_generator.with_stream(stream);
+        self
+    }
+
+    /// Set what happens to sections whose title the model repeated
+    pub fn with_duplicate_sections(mut self, policy: DuplicateSectionPolicy) -> Self {
+        self.output_generator = self.output_generator.with_duplicate_sections(policy);
+        self
+    }
+
+    /// Generate the project definition without per-section confidence scores
+    pub fn with_omit_confidence(mut self, omit_confidence: bool) -> Self {
+        self.output_generator = self.output_generator.with_omit_confidence(omit_confidence);
+        self
+    }
+
+    /// Generate the project definition in several smaller requests
+    pub fn with_split_generation(mut self, split_generation: bool) -> Self {
+        self.output_generator = self
+            .output_generator
+            .with_split_generation(split_generation);
+        self
+    }
+
+    /// Generate each section of the project definition separately, at most `limit` at a time
+    pub fn with_parallel_sections(mut self, limit: Option<usize>) -> Self {
+        self.output_generator = self.output_generator.with_parallel_sections(limit);
+        self
+    }
+
     /// Start the session
     pub fn start(&mut self) {
         self.session.state = SessionState::Questioning;
     }
 
     /// Generate the next question
+    ///
+    /// The session only changes once the question has arrived, so dropping the future
+    /// before then cancels the generation without using up a question.
     pub async fn generate_next_question(&mut self) -> Result<&Question> {
         if self.session.state != SessionState::Questioning {
-            anyhow::bail!("Session is not in questioning state");
+            return Err(WizardError::State(
+                "Session is not in questioning state".to_string(),
+            ));
         }
 
-        if self.session.context.history.len() >= self.session.max_questions {
+        if self.session.context.answer_count() >= self.max_questions() {
             self.session.state = SessionState::Generating;
-            anyhow::bail!("Maximum number of questions reached");
+            return Err(WizardError::State(
+                "Maximum number of questions reached".to_string(),
+            ));
         }
 
-        let question = self
-            .question_generator
-            .generate_next_question(&self.session.context)
-            .await?;
+        let question = match self.batch_size {
+            Some(size) => {
+                if self.session.queued_questions.is_empty() {
+                    let mut questions = match self
+                        .question_generator
+                        .generate_question_batch(&self.session.context, self.max_questions(), size)
+                        .await
+                    {
+                        Ok(questions) => questions,
+                        Err(e) => return Err(self.fail(e)),
+                    };
+                    if let Some(hook) = &self.question_hook {
+                        for question in &mut questions {
+                            hook(question);
+                        }
+                    }
+                    self.session.queued_questions = questions;
+                }
+                self.session.queued_questions.remove(0)
+            }
+            None => {
+                let mut question = match self
+                    .question_generator
+                    .generate_next_question(&self.session.context, self.max_questions())
+                    .await
+                {
+                    Ok(question) => question,
+                    Err(e) => return Err(self.fail(e)),
+                };
+                if let Some(hook) = &self.question_hook {
+                    hook(&mut question);
+                }
+                question
+            }
+        };
+        if self.session.context.is_repetitive(&question) {
+            self.repetitive_streak += 1;
+        } else {
+            self.repetitive_streak = 0;
+        }
         self.session.current_question = Some(question);
 
         Ok(self.session.current_question.as_ref().unwrap())
     }
 
+    /// Generate a follow-up question targeting a weak area, extending the interview by one if needed
+    pub async fn generate_followup_question(&mut self, area: &str) -> Result<&Question> {
+        if self.session.state != SessionState::Questioning {
+            return Err(WizardError::State(
+                "Session is not in questioning state".to_string(),
+            ));
+        }
+
+        let mut question = match self
+            .llm_client
+            .generate_followup_question(&self.session.context, area)
+            .await
+        {
+            Ok(question) => question,
+            Err(e) => return Err(self.fail(e)),
+        };
+        if let Some(hook) = &self.question_hook {
+            hook(&mut question);
+        }
+        self.allow_extra_question();
+        self.session.current_question = Some(question);
+
+        Ok(self.session.current_question.as_ref().unwrap())
+    }
+
+    /// Generate a clarifying question about a vague last answer, extending the interview by one if needed
+    pub async fn generate_clarifying_question(&mut self, missing: &str) -> Result<&Question> {
+        if self.session.state != SessionState::Questioning {
+            return Err(WizardError::State(
+                "Session is not in questioning state".to_string(),
+            ));
+        }
+
+        let mut question = match self
+            .llm_client
+            .generate_clarifying_question(&self.session.context, missing)
+            .await
+        {
+            Ok(question) => question,
+            Err(e) => return Err(self.fail(e)),
+        };
+        if let Some(hook) = &self.question_hook {
+            hook(&mut question);
+        }
+        self.allow_extra_question();
+        self.session.current_question = Some(question);
+
+        Ok(self.session.current_question.as_ref().unwrap())
+    }
+
+    /// Ask the model whether the last answer needs clarifying, returning what it leaves unclear
+    ///
+    /// Only free-text answers are assessed; choices and skipped questions are never vague.
+    pub async fn assess_last_answer(&self) -> Result<Option<String>> {
+        let needs_assessment = self.session.context.history.last().is_some_and(|answer| {
+            !answer.skipped && matches!(answer.question.question_type, QuestionType::FreeText)
+        });
+        if !needs_assessment {
+            return Ok(None);
+        }
+
+        let assessment = self.llm_client.assess_answer(&self.session.context).await?;
+        Ok(assessment.gap())
+    }
+
+    /// Get the next of the template's initial questions to ask, if any
+    ///
+    /// Questions already answered are passed over, as are questions whose condition
+    /// (`depends_on`) isn't met by the answers so far.
+    pub fn next_template_question<'a>(&self, template: &'a Template) -> Option<&'a Question> {
+        let context = &self.session.context;
+        template.initial_questions.iter().find(|question| {
+            !context
+                .history
+                .iter()
+                .any(|answer| answer.question.id == question.id)
+                && context.condition_met(question)
+        })
+    }
+
+    /// Get the definition section to ask about next, if a refinement is in progress
+    pub fn next_focus_area(&self) -> Option<String> {
+        self.session.focus_areas.first().cloned()
+    }
+
+    /// Mark the current focus area as covered
+    pub fn complete_focus_area(&mut self) {
+        if !self.session.focus_areas.is_empty() {
+            self.session.focus_areas.remove(0);
+        }
+    }
+
+    /// Make room for one more question if the limit has been reached
+    fn allow_extra_question(&mut self) {
+        if self.question_count() >= self.max_questions() {
+            self.extra_questions += 1;
+        }
+    }
+
+    /// Decide whether to ask about a weak section before the next question
+    ///
+    /// The model is only asked to project the section confidence once at least
+    /// `min_questions` questions have been answered.
+    pub async fn confidence_gate(
+        &self,
+        threshold: u8,
+        min_questions: usize,
+    ) -> Result<ConfidenceGate> {
+        if self.question_count() < min_questions {
+            return Ok(ConfidenceGate::NotReached);
+        }
+
+        Ok(
+            match self.weak_sections(threshold).await?.into_iter().next() {
+                Some(section) => ConfidenceGate::FollowUp(section),
+                None => ConfidenceGate::Passed,
+            },
+        )
+    }
+
+    /// Get the sections projected to fall below the confidence threshold, weakest first
+    pub async fn weak_sections(&self, threshold: u8) -> Result<Vec<String>> {
+        let estimates = self
+            .llm_client
+            .probe_section_confidence(&self.session.context)
+            .await?;
+
+        Ok(sections_below_threshold(&estimates, threshold))
+    }
+
+    /// Infer the domain from the answers so far, once enough answers have been given
+    ///
+    /// Only runs when no domain is set, and only sets one from `domains` when the
+    /// classification is confident enough. Returns the domain that was set.
+    pub async fn infer_domain(&mut self, domains: &[String]) -> Result<Option<String>> {
+        if self.session.context.domain.is_some()
+            || self.session.context.answer_count() != DOMAIN_INFERENCE_AFTER
+        {
+            return Ok(None);
+        }
+
+        let guess = self
+            .llm_client
+            .classify_domain(&self.session.context, domains)
+            .await?;
+
+        Ok(self.apply_domain_guess(guess, domains))
+    }
+
+    /// Ask the model for a concise project name, once enough answers have been given
+    ///
+    /// Only runs when no name is set yet. Returns the name that was set.
+    pub async fn name_project(&mut self) -> Result<Option<String>> {
+        if self.session.context.project_name.is_some()
+            || self.session.context.answer_count() != PROJECT_NAMING_AFTER
+        {
+            return Ok(None);
+        }
+
+        let name = self
+            .llm_client
+            .generate_project_name(&self.session.context)
+            .await?;
+
+        self.session.context.project_name = clean_project_name(&name);
+        Ok(self.session.context.project_name.clone())
+    }
+
+    /// Get a file name stem for the outputs of the session, from the project name if there is one
+    pub fn file_stem(&self) -> Option<String> {
+        self.session
+            .context
+            .project_name
+            .as_deref()
+            .and_then(project_file_stem)
+    }
+
+    /// Add the persona's note on the latest answer to the running notes, if they are enabled
+    ///
+    /// Returns the note that was added.
+    pub async fn update_running_notes(&mut self) -> Result<Option<String>> {
+        if !self.running_notes || self.session.context.history.is_empty() {
+            return Ok(None);
+        }
+
+        let note = self.llm_client.generate_note(&self.session.context).await?;
+
+        let context = &mut self.session.context;
+        if context.add_note(&note) {
+            Ok(context.notes.last().cloned())
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Set the domain from a classification if it is a known domain above the threshold
+    pub fn apply_domain_guess(&mut self, guess: DomainGuess, domains: &[String]) -> Option<String> {
+        if guess.confidence < DOMAIN_INFERENCE_THRESHOLD {
+            return None;
+        }
+
+        let domain = domains
+            .iter()
+            .find(|domain| domain.eq_ignore_ascii_case(guess.domain.trim()))?
+            .clone();
+
+        self.session.context.domain = Some(domain.clone());
+        self.session
+            .context
+            .add_metadata("domain_confidence", format!("{:.2}", guess.confidence));
+        Some(domain)
+    }
+
     /// Answer the current question
+    #[allow(
+        dead_code,
+        reason = "the wizard always records the self-reported confidence"
+    )]
     pub fn answer_question(&mut self, response: impl Into<String>) -> Result<()> {
+        self.answer_question_with_confidence(response, None)
+    }
+
+    /// Answer the current question, recording the user's self-reported confidence
+    pub fn answer_question_with_confidence(
+        &mut self,
+        response: impl Into<String>,
+        self_confidence: Option<u8>,
+    ) -> Result<()> {
         if self.session.state != SessionState::Questioning {
-            anyhow::bail!("Session is not in questioning state");
+            return Err(WizardError::State(
+                "Session is not in questioning state".to_string(),
+            ));
         }
 
         if let Some(question) = self.session.current_question.take() {
-            self.session.context.add_answer(question, response);
-            Ok(())
+            let result = self.session.context.add_answer_with_confidence(
+                question.clone(),
+                response,
+                self_confidence,
+            );
+
+            // Keep the question so the user can still see what was refused
+            if result.is_err() {
+                self.session.current_question = Some(question);
+            } else if question.sensitive {
+                self.protect_sensitive_answers();
+            }
+            result
         } else {
-            anyhow::bail!("No current question to answer");
+            Err(WizardError::State(
+                "No current question to answer".to_string(),
+            ))
         }
     }
 
+    /// Record a free-form chat message as the answer to the model's last message, and get its reply
+    ///
+    /// The exchange is kept in the history as a free-text answer tagged `chat`, and
+    /// extends the question limit so chatting doesn't use up the structured questions.
+    pub async fn chat(&mut self, prompt: &str, message: &str) -> Result<String> {
+        if self.session.state != SessionState::Questioning {
+            return Err(WizardError::State(
+                "Session is not in questioning state".to_string(),
+            ));
+        }
+
+        let id = format!("chat_{}", self.session.context.history.len() + 1);
+        let context = &mut self.session.context;
+        context.add_answer(Question::free_text(id, prompt), message)?;
+        context.tag_last_answer(CHAT_TAG);
+        self.session.max_questions += 1;
+
+        self.llm_client.chat_reply(&self.session.context).await
+    }
+
+    /// Ask the LLM why the current question matters, without recording anything
+    pub async fn explain_current_question(&self) -> Result<String> {
+        let Some(question) = &self.session.current_question else {
+            return Err(WizardError::State(
+                "No current question to explain".to_string(),
+            ));
+        };
+
+        self.llm_client
+            .explain_question(&self.session.context, question)
+            .await
+    }
+
+    /// Skip the current question, recording it as skipped
+    pub fn skip_question(&mut self) -> Result<()> {
+        if self.session.state != SessionState::Questioning {
+            return Err(WizardError::State(
+                "Session is not in questioning state".to_string(),
+            ));
+        }
+
+        if let Some(question) = self.session.current_question.take() {
+            let result = self.session.context.skip_question(question.clone());
+            if result.is_err() {
+                self.session.current_question = Some(question);
+            }
+            result
+        } else {
+            Err(WizardError::State(
+                "No current question to skip".to_string(),
+            ))
+        }
+    }
+
+    /// Get the queued questions the user can choose instead of the current one
+    pub fn queued_questions(&self) -> &[Question] {
+        &self.session.queued_questions
+    }
+
+    /// Answer a queued question now instead of the current one, which goes back in the queue
+    pub fn choose_queued_question(&mut self, index: usize) -> Result<&Question> {
+        if index >= self.session.queued_questions.len() {
+            return Err(WizardError::State(format!(
+                "There is no queued question {}",
+                index + 1
+            )));
+        }
+
+        let chosen = self.session.queued_questions.remove(index);
+        if let Some(current) = self.session.current_question.replace(chosen) {
+            self.session.queued_questions.insert(0, current);
+        }
+        Ok(self.session.current_question.as_ref().unwrap())
+    }
+
     /// Go back to a previous question
+    ///
+    /// In batch mode the unanswered current question is queued again rather than lost.
     pub fn go_back(&mut self) -> Result<&Question> {
+        if self.batch_size.is_some()
+            && self.session.context.current_index > 0
+            && let Some(question) = self.session.current_question.take()
+        {
+            self.session.queued_questions.insert(0, question);
+        }
+
         if let Some(answer) = self.session.context.go_back() {
             self.session.current_question = Some(answer.question.clone());
             Ok(&answer.question)
         } else {
-            anyhow::bail!("Cannot go back further");
+            Err(WizardError::State("Cannot go back further".to_string()))
         }
     }
 
     /// Go forward to a next question (if we've gone back)
+    #[allow(dead_code, reason = "the wizard only offers going back")]
     pub fn go_forward(&mut self) -> Result<&Question> {
         if let Some(answer) = self.session.context.go_forward() {
             self.session.current_question = Some(answer.question.clone());
             Ok(&answer.question)
         } else {
-            anyhow::bail!("Cannot go forward further");
+            Err(WizardError::State("Cannot go forward further".to_string()))
         }
     }
 
@@ -190,39 +1119,162 @@ impl SessionManager {
     pub async fn generate_project_definition(&mut self) -> Result<String> {
         self.session.state = SessionState::Generating;
 
-        let project_definition = self
+        let mut project_definition = match self
             .output_generator
             .generate_project_definition(&self.session.context)
-            .await?;
-        let markdown = project_definition.to_markdown();
+            .await
+        {
+            Ok(project_definition) => project_definition,
+            Err(e) => return Err(self.fail(e)),
+        };
+
+        // Title the document with the session's project name, or keep the one the model chose
+        match &self.session.context.project_name {
+            Some(name) => project_definition.name = name.clone(),
+            None if project_definition.name != DEFAULT_PROJECT_NAME => {
+                self.session.context.project_name = Some(project_definition.name.clone());
+            }
+            None => {}
+        }
+        let markdown = self.render_output(&project_definition);
 
         self.session.output = Some(markdown.clone());
+        self.session.definition = Some(project_definition);
+        self.session.record_confidence();
         self.session.state = SessionState::Completed;
 
         Ok(markdown)
     }
 
+    /// Build a basic project definition from the answers alone, without calling the LLM
+    pub fn assemble_offline_definition(&mut self, name: impl Into<String>) -> String {
+        let project_definition = ProjectDefinition::from_answers(name, &self.session.context);
+        let markdown = self.render_output(&project_definition);
+
+        self.session.output = Some(markdown.clone());
+        self.session.definition = Some(project_definition);
+        self.session.state = SessionState::Completed;
+
+        markdown
+    }
+
+    /// Render the output document for a project definition, with the metrics appendix if enabled
+    fn render_output(&self, definition: &ProjectDefinition) -> String {
+        let mut markdown = definition.to_styled_markdown(&self.markdown_style);
+        if self.metrics_appendix {
+            markdown.push_str(&self.metrics().to_markdown());
+        }
+        markdown
+    }
+
+    /// Get statistics about the questions asked so far
+    pub fn metrics(&self) -> SessionMetrics {
+        SessionMetrics::from_context(&self.session.context)
+    }
+
+    /// Export the session metrics to a JSON file
+    pub fn export_metrics(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.metrics())
+            .map_err(WizardError::serialize("session metrics"))?;
+        write_atomic(path, json)
+    }
+
     /// Export the session output to a file
     pub fn export_output(&self, path: impl AsRef<Path>) -> Result<()> {
-        if let Some(output) = &self.session.output {
-            std::fs::write(path, output)?;
-            Ok(())
+        let Some(output) = &self.session.output else {
+            return Err(WizardError::State("No output to export".to_string()));
+        };
+
+        match self.output_format {
+            OutputFormat::Markdown => write_atomic(path, output),
+            OutputFormat::Text => write_atomic(path, markdown_to_plain_text(output)),
+        }
+    }
+
+    /// Append the project definition to a portfolio document, creating it if needed
+    pub fn append_to_portfolio(&self, path: impl AsRef<Path>) -> Result<()> {
+        let Some(definition) = &self.session.definition else {
+            return Err(WizardError::State("No output to export".to_string()));
+        };
+
+        let path = path.as_ref();
+        let existing = if path.exists() {
+            std::fs::read_to_string(path).map_err(WizardError::io("read", path))?
         } else {
-            anyhow::bail!("No output to export");
+            String::new()
+        };
+
+        let document =
+            portfolio::append_definition(&existing, definition, self.markdown_style.confidence);
+        write_atomic(path, document)
+    }
+
+    /// Export the project definition to a PDF file
+    #[cfg(feature = "pdf")]
+    pub fn export_pdf(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(definition) = &self.session.definition {
+            definition.to_pdf(path)
+        } else {
+            Err(WizardError::State("No output to export".to_string()))
+        }
+    }
+
+    /// Export the collected questions and answers, as JSON for `.json` paths or Markdown otherwise
+    pub fn export_questions(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let export = if path.extension().is_some_and(|ext| ext == "json") {
+            self.session.context.to_qa_json()?
+        } else {
+            self.session.context.to_qa_markdown()
+        };
+        write_atomic(path, export)?;
+        Ok(())
+    }
+
+    /// Get the position of the question being asked
+    ///
+    /// Edits don't change the number and skipped questions still count, so the
+    /// number only moves forward as questions are answered or skipped.
+    pub fn question_position(&self) -> QuestionPosition {
+        QuestionPosition {
+            number: self.question_count() + 1,
+            max_questions: self.max_questions(),
+            skipped: self.session.context.skipped_count(),
         }
     }
 
+    /// Get the number of automatic retries left in the session, if a budget is set
+    pub fn retries_remaining(&self) -> Option<usize> {
+        self.llm_client.retries_remaining()
+    }
+
     /// Get the current question count
     pub fn question_count(&self) -> usize {
-        self.session.context.history.len()
+        self.session.context.answer_count()
     }
 
-    /// Get the maximum number of questions
+    /// Get the maximum number of questions, including follow-ups asked past the limit
     pub fn max_questions(&self) -> usize {
-        self.session.max_questions
+        self.session.max_questions + self.extra_questions
+    }
+
+    /// Allow more questions to be asked in this session
+    pub fn extend_max_questions(&mut self, additional: usize) {
+        self.session.max_questions += additional;
+    }
+
+    /// Check whether the last questions generated repeat earlier ones often enough to stop
+    pub fn seems_exhausted(&self) -> bool {
+        self.exhaustion_threshold > 0 && self.repetitive_streak >= self.exhaustion_threshold
+    }
+
+    /// Continue the interview after an exhaustion warning, counting repeats afresh
+    pub fn reset_exhaustion(&mut self) {
+        self.repetitive_streak = 0;
     }
 
     /// Check if the session is completed
+    #[allow(dead_code, reason = "the wizard checks the session state directly")]
     pub fn is_completed(&self) -> bool {
         self.session.state == SessionState::Completed
     }
@@ -231,4 +1283,745 @@ impl SessionManager {
     pub fn has_error(&self) -> bool {
         self.session.state == SessionState::Error
     }
+
+    /// Put the session in the error state, recording the message, and hand the error back
+    fn fail(&mut self, error: WizardError) -> WizardError {
+        self.session.state = SessionState::Error;
+        self.session.error = Some(error.to_string());
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::llm::scripted::Reply;
+    use crate::wizard::question::QuestionSource;
+    use serde_json::json;
+
+    /// Session with two answered questions
+    fn answered_session() -> Session {
+        let mut session = Session::new();
+        for (id, text) in [("q1", "Who are the users?"), ("q2", "What is the goal?")] {
+            session
+                .context
+                .add_answer(Question::free_text(id, text), "An answer")
+                .unwrap();
+        }
+        session
+    }
+
+    #[test]
+    fn valid_session_passes_validation() {
+        let validation = answered_session().validate();
+
+        assert!(validation.is_valid(), "{:?}", validation.errors);
+        assert!(validation.warnings.is_empty());
+    }
+
+    #[test]
+    fn corrupted_index_fails_validation() {
+        let mut session = answered_session();
+        session.context.current_index = 5;
+
+        let validation = session.validate();
+
+        assert!(!validation.is_valid());
+        assert!(validation.errors[0].contains("current_index 5"));
+    }
+
+    /// Probe response projecting the given confidence for each section
+    fn probe(sections: &[(&str, u8)]) -> Reply {
+        let sections: Vec<_> = sections
+            .iter()
+            .map(|(section, confidence)| json!({ "section": section, "confidence": confidence }))
+            .collect();
+        Reply::content(json!({ "sections": sections }).to_string())
+    }
+
+    #[tokio::test]
+    async fn confidence_gate_waits_for_min_questions() {
+        // No reply is scripted, so probing the model would fail the test
+        let (client, _) = LlmClient::scripted([]);
+        let manager = SessionManager::new(answered_session(), client);
+
+        let gate = manager.confidence_gate(3, 3).await.unwrap();
+
+        assert_eq!(gate, ConfidenceGate::NotReached);
+    }
+
+    #[tokio::test]
+    async fn confidence_gate_follows_the_probe() {
+        let (client, _) = LlmClient::scripted([
+            probe(&[("Scope", 4), ("Risks", 2), ("Target Users", 1)]),
+            probe(&[("Scope", 4), ("Risks", 3), ("Target Users", 3)]),
+        ]);
+        let manager = SessionManager::new(answered_session(), client);
+
+        assert_eq!(
+            manager.confidence_gate(3, 2).await.unwrap(),
+            ConfidenceGate::FollowUp("Target Users".to_string())
+        );
+        assert_eq!(
+            manager.confidence_gate(3, 2).await.unwrap(),
+            ConfidenceGate::Passed
+        );
+    }
+
+    #[tokio::test]
+    async fn followup_past_the_limit_is_not_saved() {
+        let question =
+            json!({ "question_type": "FreeText", "question_text": "What could go wrong?" });
+        let (client, _) = LlmClient::scripted([Reply::content(question.to_string())]);
+        let mut manager = SessionManager::new(answered_session().with_max_questions(2), client);
+        manager.start();
+
+        manager.generate_followup_question("Risks").await.unwrap();
+
+        assert_eq!(manager.max_questions(), 3);
+        assert_eq!(manager.session.max_questions, 2);
+    }
+
+    #[tokio::test]
+    async fn failed_generation_sets_the_error_state() {
+        let (client, _) = LlmClient::scripted([Reply::Raw(
+            500,
+            r#"{"error":{"message":"Internal server error"}}"#.to_string(),
+        )]);
+        let mut manager = SessionManager::new(answered_session(), client);
+        manager.start();
+
+        let error = manager.generate_project_definition().await.unwrap_err();
+
+        assert!(manager.has_error());
+        assert_eq!(manager.session.state, SessionState::Error);
+        assert_eq!(
+            manager.session.error.as_deref(),
+            Some(error.to_string().as_str())
+        );
+        assert!(error.to_string().contains("Internal server error"));
+    }
+
+    /// Save a session with one answer to a file in the directory
+    fn save_answered(dir: &Path, file: &str, question: &str, response: &str) {
+        let mut session = Session::new();
+        session
+            .context
+            .add_answer(Question::free_text("q1", question), response)
+            .unwrap();
+        session.save_to_file(dir.join(file)).unwrap();
+    }
+
+    #[test]
+    fn search_finds_matches_across_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        save_answered(
+            dir.path(),
+            "a.json",
+            "Which regulations apply?",
+            "HIPAA and GDPR",
+        );
+        save_answered(dir.path(), "b.json", "Is the data covered by Hipaa?", "Yes");
+        save_answered(dir.path(), "c.json", "Who are the users?", "Nurses");
+        std::fs::write(dir.path().join("broken.json"), "{ not a session").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "HIPAA").unwrap();
+
+        let results = search_sessions(dir.path(), "hipaa").unwrap();
+
+        let matches: Vec<(PathBuf, usize, &str)> = results
+            .matches
+            .iter()
+            .map(|m| (m.path.clone(), m.number, m.response.as_str()))
+            .collect();
+        assert_eq!(
+            matches,
+            [
+                (dir.path().join("a.json"), 1, "HIPAA and GDPR"),
+                (dir.path().join("b.json"), 1, "Yes"),
+            ]
+        );
+        assert_eq!(results.skipped.len(), 1);
+        assert_eq!(results.skipped[0].0, dir.path().join("broken.json"));
+    }
+
+    #[tokio::test]
+    async fn scripted_classification_sets_the_domain() {
+        let domains = vec!["Healthcare".to_string(), "Finance".to_string()];
+        let (client, _) = LlmClient::scripted([
+            Reply::content(json!({ "domain": "Healthcare", "confidence": 0.9 }).to_string()),
+            Reply::content(json!({ "domain": "Finance", "confidence": 0.3 }).to_string()),
+        ]);
+        let mut confident = SessionManager::new(answered_session(), client.clone());
+        let mut unsure = SessionManager::new(answered_session(), client);
+
+        let inferred = confident.infer_domain(&domains).await.unwrap();
+        let guessed = unsure.infer_domain(&domains).await.unwrap();
+
+        assert_eq!(inferred.as_deref(), Some("Healthcare"));
+        assert_eq!(
+            confident.session.context.domain.as_deref(),
+            Some("Healthcare")
+        );
+        assert_eq!(guessed, None);
+        assert_eq!(unsure.session.context.domain, None);
+    }
+
+    #[tokio::test]
+    async fn refine_questions_are_about_the_weakest_sections() {
+        let mut definition = ProjectDefinition::new("Triage Bot");
+        definition.add_section("Target Users", "Nurses.", ConfidenceLevel::High);
+        definition.add_section("Data Retention", "Unclear.", ConfidenceLevel::VeryLow);
+        definition.add_section("Integrations", "Maybe the EHR.", ConfidenceLevel::Low);
+        let mut session = answered_session();
+        session.focus_areas = definition.weakest_sections(2);
+        session.definition = Some(definition);
+        let (client, api) = LlmClient::scripted([
+            Reply::content(json!({ "question_type": "FreeText", "question_text": "How long is data kept?" }).to_string()),
+            Reply::content(json!({ "question_type": "FreeText", "question_text": "Which systems are connected?" }).to_string()),
+        ]);
+        let mut session_manager = SessionManager::new(session, client);
+        session_manager.start();
+
+        while let Some(area) = session_manager.next_focus_area() {
+            session_manager
+                .generate_followup_question(&area)
+                .await
+                .unwrap();
+            session_manager.complete_focus_area();
+        }
+
+        let received = api.received();
+        assert_eq!(received.len(), 2);
+        assert!(received[0].prompt().contains("Data Retention"));
+        assert!(received[1].prompt().contains("Integrations"));
+        assert!(!received[0].prompt().contains("Target Users"));
+    }
+
+    #[tokio::test]
+    async fn numbering_counts_skips_and_ignores_back_and_edits() {
+        let questions = [
+            "Who are the users?",
+            "What is the budget?",
+            "Which platforms?",
+        ]
+        .map(|text| {
+            Reply::content(
+                json!({ "question_type": "FreeText", "question_text": text }).to_string(),
+            )
+        });
+        let (client, _) = LlmClient::scripted(questions);
+        let mut session_manager =
+            SessionManager::new(Session::new().with_max_questions(10), client);
+        session_manager.start();
+
+        session_manager.generate_next_question().await.unwrap();
+        assert_eq!(
+            session_manager.question_position().to_string(),
+            "Question 1 of up to 10"
+        );
+        session_manager.answer_question("Nurses").unwrap();
+        session_manager.generate_next_question().await.unwrap();
+        session_manager.skip_question().unwrap();
+        assert_eq!(
+            session_manager.question_position().to_string(),
+            "Question 3 of up to 10, 1 skipped"
+        );
+
+        session_manager.go_back().unwrap();
+        session_manager
+            .session
+            .context
+            .edit_answer(0, "Clinical staff")
+            .unwrap();
+        assert_eq!(
+            session_manager.question_position().to_string(),
+            "Question 3 of up to 10, 1 skipped"
+        );
+
+        session_manager.generate_next_question().await.unwrap();
+        session_manager.answer_question("Web").unwrap();
+        assert_eq!(
+            session_manager.question_position().to_string(),
+            "Question 4 of up to 10, 1 skipped"
+        );
+    }
+
+    #[tokio::test]
+    async fn why_fetches_an_explanation_without_changing_history() {
+        let (client, api) = LlmClient::scripted([
+            Reply::content(
+                json!({ "question_type": "FreeText", "question_text": "What is the budget?" })
+                    .to_string(),
+            ),
+            Reply::content(
+                "The budget decides the scope. Example: \"About 50k for the first year\".",
+            ),
+        ]);
+        let mut session_manager = SessionManager::new(answered_session(), client);
+        session_manager.start();
+        session_manager.generate_next_question().await.unwrap();
+        let history = session_manager.session.context.history.len();
+
+        let explanation = session_manager.explain_current_question().await.unwrap();
+
+        assert!(explanation.starts_with("The budget decides the scope."));
+        assert!(api.received()[1].prompt().contains("What is the budget?"));
+        assert_eq!(session_manager.session.context.history.len(), history);
+        assert_eq!(
+            session_manager
+                .session
+                .current_question
+                .as_ref()
+                .unwrap()
+                .text,
+            "What is the budget?"
+        );
+    }
+
+    #[test]
+    fn second_open_of_a_locked_session_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        answered_session().save_to_file(&path).unwrap();
+
+        let first = Session::open(&path).unwrap();
+        let error = Session::open(&path).unwrap_err();
+
+        assert!(
+            error.to_string().starts_with(&format!(
+                "Session {} is already open in another process",
+                path.display()
+            )),
+            "{}",
+            error
+        );
+        drop(first);
+        assert!(Session::open(&path).is_ok());
+    }
+
+    #[test]
+    fn offline_session_runs_the_template_questions_without_the_llm() {
+        let mut template = Template::new(
+            "Code Explainer",
+            "Explains code to developers",
+            "Software".into(),
+            "An assistant that explains unfamiliar code",
+        );
+        template.add_question(Question::free_text(
+            "q1",
+            "Which languages must be supported?",
+        ));
+        template.add_question(Question::yes_no(
+            "q2",
+            "Should explanations link to the docs?",
+        ));
+        let (client, api) = LlmClient::scripted([]);
+        let mut session_manager = SessionManager::new(Session::new(), client);
+
+        while let Some(question) = session_manager.next_template_question(&template) {
+            let response = match question.question_type {
+                QuestionType::YesNo => "Yes",
+                _ => "Rust and Python",
+            };
+            let question = question.clone().with_source(QuestionSource::Template);
+            session_manager
+                .session
+                .context
+                .add_answer(question, response)
+                .unwrap();
+        }
+        let markdown = session_manager.assemble_offline_definition(&template.name);
+
+        assert_eq!(session_manager.question_count(), 2);
+        assert!(markdown.starts_with("# Code Explainer"), "{}", markdown);
+        assert!(markdown.contains("Rust and Python"), "{}", markdown);
+        assert_eq!(session_manager.session.state, SessionState::Completed);
+        assert!(api.received().is_empty());
+    }
+
+    #[tokio::test]
+    async fn question_hook_changes_the_generated_question() {
+        let (client, _) = LlmClient::scripted([Reply::content(
+            json!({ "question_type": "FreeText", "question_text": "What is the budget?" })
+                .to_string(),
+        )]);
+        let mut session_manager = SessionManager::new(answered_session(), client)
+            .with_question_hook(|question| question.text = format!("[Finance] {}", question.text));
+        session_manager.start();
+
+        let question = session_manager.generate_next_question().await.unwrap();
+
+        assert_eq!(question.text, "[Finance] What is the budget?");
+        assert_eq!(
+            session_manager
+                .session
+                .current_question
+                .as_ref()
+                .unwrap()
+                .text,
+            "[Finance] What is the budget?"
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_are_tagged_with_the_question_source() {
+        let mut template = Template::new("Triage Bot", "Symptom triage", "Healthcare".into(), "");
+        template.add_question(Question::free_text("t1", "Who are the patients?"));
+        let (client, _) = LlmClient::scripted([Reply::content(
+            json!({ "question_type": "FreeText", "question_text": "What is the budget?" })
+                .to_string(),
+        )]);
+        let mut session_manager = SessionManager::new(Session::from_template(&template), client);
+        session_manager.start();
+
+        let seeded = session_manager
+            .next_template_question(&template)
+            .unwrap()
+            .clone();
+        session_manager
+            .session
+            .context
+            .add_answer(seeded, "Adults")
+            .unwrap();
+        session_manager.generate_next_question().await.unwrap();
+        session_manager.answer_question("50k").unwrap();
+
+        let sources: Vec<QuestionSource> = session_manager
+            .session
+            .context
+            .history
+            .iter()
+            .map(|answer| answer.question.source)
+            .collect();
+        assert_eq!(sources, [QuestionSource::Template, QuestionSource::Llm]);
+        let qa = session_manager.session.context.to_qa_markdown();
+        assert!(qa.contains("*Source: Template*"), "{}", qa);
+        assert!(qa.contains("*Source: LLM*"), "{}", qa);
+    }
+
+    #[test]
+    fn dependent_question_is_asked_only_when_its_condition_is_met() {
+        let mut template = Template::new(
+            "Clinic Assistant",
+            "Helps clinic staff",
+            "Healthcare".into(),
+            "An assistant for a clinic's front desk",
+        );
+        template.add_question(Question::yes_no("patients", "Do you handle patient data?"));
+        template.add_question(
+            Question::free_text("hipaa", "How do you meet HIPAA requirements?")
+                .with_depends_on("patients", "yes"),
+        );
+        template.add_question(Question::free_text("users", "Who are the users?"));
+
+        let asked = |first_answer: &str| {
+            let (client, _api) = LlmClient::scripted([]);
+            let mut session_manager = SessionManager::new(Session::new(), client);
+            let mut asked = Vec::new();
+            while let Some(question) = session_manager.next_template_question(&template) {
+                asked.push(question.id.clone());
+                let response = if question.id == "patients" {
+                    first_answer
+                } else {
+                    "Nurses"
+                };
+                session_manager
+                    .session
+                    .context
+                    .add_answer(question.clone(), response)
+                    .unwrap();
+            }
+            asked
+        };
+
+        assert_eq!(asked("No"), ["patients", "users"]);
+        assert_eq!(asked(" Yes "), ["patients", "hipaa", "users"]);
+    }
+
+    #[tokio::test]
+    async fn repeated_questions_signal_exhaustion() {
+        let questions = [
+            "Who are the primary users of the triage assistant?",
+            "What outcome should the triage assistant deliver?",
+            "Who are the main users of the triage assistant?",
+            "Anything else?",
+            "Who are the primary users of this triage assistant?",
+        ];
+        let (client, _) = LlmClient::scripted(questions.map(|text| {
+            Reply::content(
+                json!({ "question_type": "FreeText", "question_text": text }).to_string(),
+            )
+        }));
+        let mut session_manager =
+            SessionManager::new(Session::new(), client).with_exhaustion_threshold(3);
+        session_manager.start();
+
+        let mut exhausted = Vec::new();
+        for _ in questions {
+            session_manager.generate_next_question().await.unwrap();
+            exhausted.push(session_manager.seems_exhausted());
+            session_manager.answer_question("Nurses").unwrap();
+        }
+
+        assert_eq!(exhausted, [false, false, false, false, true]);
+        session_manager.reset_exhaustion();
+        assert!(!session_manager.seems_exhausted());
+    }
+
+    #[tokio::test]
+    async fn running_notes_accumulate_and_reach_later_prompts() {
+        let question = |text: &str| {
+            Reply::content(
+                json!({ "question_type": "FreeText", "question_text": text }).to_string(),
+            )
+        };
+        let (client, api) = LlmClient::scripted([
+            question("Who are the users?"),
+            Reply::content("- Users are triage nurses on night shifts."),
+            question("What is the goal?"),
+            Reply::content("Goal is faster triage, accuracy unclear."),
+            question("What is the budget?"),
+        ]);
+        let mut session_manager =
+            SessionManager::new(Session::new(), client).with_running_notes(true);
+        session_manager.start();
+
+        for response in ["Night-shift nurses", "Faster triage"] {
+            session_manager.generate_next_question().await.unwrap();
+            session_manager.answer_question(response).unwrap();
+            session_manager.update_running_notes().await.unwrap();
+        }
+        session_manager.generate_next_question().await.unwrap();
+
+        assert_eq!(
+            session_manager.session.context.notes,
+            [
+                "Users are triage nurses on night shifts.",
+                "Goal is faster triage, accuracy unclear."
+            ]
+        );
+        let received = api.received();
+        assert!(
+            received[2]
+                .prompt()
+                .contains("- Users are triage nurses on night shifts.")
+        );
+        let last_prompt = received[4].prompt();
+        assert!(last_prompt.contains("Your running notes on the project:"));
+        assert!(last_prompt.contains(
+            "- Users are triage nurses on night shifts.\n- Goal is faster triage, accuracy unclear."
+        ));
+    }
+
+    #[tokio::test]
+    async fn chosen_question_of_a_batch_is_answered_first() {
+        let batch = json!([
+            { "question_type": "FreeText", "question_text": "Who are the users?" },
+            { "question_type": "FreeText", "question_text": "What is the goal?" },
+            { "question_type": "YesNo", "question_text": "Is there a deadline?" }
+        ]);
+        let (client, api) = LlmClient::scripted([Reply::content(batch.to_string())]);
+        let mut session_manager =
+            SessionManager::new(Session::new(), client).with_batch_questions(Some(3));
+        session_manager.start();
+
+        session_manager.generate_next_question().await.unwrap();
+        let queued: Vec<&str> = session_manager
+            .queued_questions()
+            .iter()
+            .map(|question| question.text.as_str())
+            .collect();
+        assert_eq!(queued, ["What is the goal?", "Is there a deadline?"]);
+
+        let chosen = session_manager.choose_queued_question(1).unwrap();
+        assert_eq!(chosen.text, "Is there a deadline?");
+        session_manager.answer_question("No").unwrap();
+        let next = session_manager.generate_next_question().await.unwrap();
+        assert_eq!(next.text, "Who are the users?");
+
+        let history = &session_manager.session.context.history;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].question.text, "Is there a deadline?");
+        assert_eq!(session_manager.queued_questions().len(), 1);
+        assert_eq!(api.received().len(), 1);
+        assert!(
+            api.received()[0]
+                .prompt()
+                .contains("JSON array of 3 question objects")
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_messages_are_recorded_as_free_text_answers() {
+        let (client, api) = LlmClient::scripted([
+            Reply::content("Noted, night shifts. Do they work weekends too?"),
+            Reply::content("Thanks, weekends are covered."),
+        ]);
+        let mut session_manager = SessionManager::new(answered_session(), client);
+        session_manager.start();
+        let max_questions = session_manager.session.max_questions;
+
+        let reply = session_manager
+            .chat(CHAT_OPENER, "The nurses work night shifts")
+            .await
+            .unwrap();
+        session_manager
+            .chat(&reply, "Yes, weekends too")
+            .await
+            .unwrap();
+
+        let chat: Vec<(&str, &str)> = session_manager.session.context.history[2..]
+            .iter()
+            .map(|answer| {
+                assert!(matches!(
+                    answer.question.question_type,
+                    QuestionType::FreeText
+                ));
+                assert_eq!(answer.tags, [CHAT_TAG]);
+                (answer.question.text.as_str(), answer.response.as_str())
+            })
+            .collect();
+        assert_eq!(
+            chat,
+            [
+                (CHAT_OPENER, "The nurses work night shifts"),
+                (
+                    "Noted, night shifts. Do they work weekends too?",
+                    "Yes, weekends too"
+                ),
+            ]
+        );
+        assert_eq!(session_manager.session.max_questions, max_questions + 2);
+        assert!(api.received()[1].prompt().contains("Yes, weekends too"));
+    }
+
+    #[tokio::test]
+    async fn yes_no_question_ignores_stray_options_and_records_yes_or_no() {
+        let question = |text: &str| {
+            Reply::content(
+                json!({
+                    "question_type": "YesNo",
+                    "question_text": text,
+                    "options": ["Maybe", "Definitely", "Not sure"]
+                })
+                .to_string(),
+            )
+        };
+        let (client, _) = LlmClient::scripted([
+            question("Is there a deadline?"),
+            question("Is there a budget?"),
+        ]);
+        let mut session_manager = SessionManager::new(Session::new(), client);
+        session_manager.start();
+
+        for response in ["y", " FALSE "] {
+            let question = session_manager.generate_next_question().await.unwrap();
+            assert!(matches!(question.question_type, QuestionType::YesNo));
+            assert_eq!(question.choices().unwrap(), ["Yes", "No"]);
+            session_manager.answer_question(response).unwrap();
+        }
+
+        let responses: Vec<&str> = session_manager
+            .session
+            .context
+            .history
+            .iter()
+            .map(|answer| answer.response.as_str())
+            .collect();
+        assert_eq!(responses, ["Yes", "No"]);
+    }
+
+    #[tokio::test]
+    async fn insufficient_answer_gets_one_clarifier_past_the_limit() {
+        let (client, api) = LlmClient::scripted([
+            Reply::content(json!({ "sufficient": false, "missing": "which shifts" }).to_string()),
+            Reply::content(
+                json!({ "question_type": "FreeText", "question_text": "Which shifts do the nurses work?" })
+                    .to_string(),
+            ),
+            Reply::content(json!({ "sufficient": true }).to_string()),
+        ]);
+        let mut manager = SessionManager::new(answered_session().with_max_questions(2), client);
+        manager.start();
+
+        let missing = manager.assess_last_answer().await.unwrap();
+        assert_eq!(missing.as_deref(), Some("which shifts"));
+        assert!(api.received()[0].prompt().contains("**Answer:** An answer"));
+
+        let clarifier = manager
+            .generate_clarifying_question("which shifts")
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(clarifier.text, "Which shifts do the nurses work?");
+        assert!(api.received()[1].prompt().contains("which shifts"));
+        assert_eq!(manager.max_questions(), 3);
+
+        manager.answer_question("Nights and weekends").unwrap();
+        assert_eq!(manager.assess_last_answer().await.unwrap(), None);
+        assert_eq!(api.received().len(), 3);
+        assert_eq!(manager.question_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn only_free_text_answers_are_assessed() {
+        let (client, api) = LlmClient::scripted([]);
+        let mut session = Session::new();
+        session
+            .context
+            .add_answer(Question::yes_no("q1", "Is it offline first?"), "Yes")
+            .unwrap();
+        let mut manager = SessionManager::new(session, client);
+        manager.start();
+
+        assert_eq!(manager.assess_last_answer().await.unwrap(), None);
+        assert!(api.received().is_empty());
+    }
+
+    /// Scripted definition with the given confidence for its two sections
+    fn definition_reply(summary: u8, users: u8) -> Reply {
+        Reply::content(format!(
+            "# Triage Bot\n\n## Project Name and Summary (Confidence: {}/5)\nA triage bot.\n\n\
+             ## Target Users (Confidence: {}/5)\nNurses.",
+            summary, users
+        ))
+    }
+
+    #[tokio::test]
+    async fn two_generations_record_two_snapshots_and_the_trend() {
+        let (client, _) = LlmClient::scripted([definition_reply(2, 3), definition_reply(4, 4)]);
+        let mut manager = SessionManager::new(answered_session(), client);
+        manager.start();
+
+        manager.generate_project_definition().await.unwrap();
+        manager.generate_project_definition().await.unwrap();
+
+        let averages: Vec<f32> = manager
+            .session
+            .confidence_history
+            .iter()
+            .map(|snapshot| snapshot.average)
+            .collect();
+        assert_eq!(averages, [2.5, 4.0]);
+        assert_eq!(manager.session.confidence_change(), Some(1.5));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        manager.session.save_to_file(&path).unwrap();
+        let reloaded = Session::load_from_file(&path).unwrap();
+        assert_eq!(reloaded.confidence_history.len(), 2);
+        assert_eq!(reloaded.confidence_change(), Some(1.5));
+    }
+
+    #[test]
+    fn single_generation_has_no_trend_yet() {
+        let mut session = answered_session();
+        assert_eq!(session.confidence_change(), None);
+
+        session.definition = Some(ProjectDefinition::new("Triage Bot"));
+        session.record_confidence();
+
+        assert!(session.confidence_history.is_empty());
+        assert_eq!(session.confidence_change(), None);
+    }
 }
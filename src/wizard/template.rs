@@ -3,11 +3,12 @@
 //! This module provides predefined templates and presets for different
 //! types of LLM-based applications.
 
-use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::path::Path;
 
+use super::error::Result;
+use super::question::QuestionSource;
 use super::{Config, Context, Question};
 
 pub(crate) type Domain = String;
@@ -25,12 +26,16 @@ pub struct Template {
     pub starting_hints: String,
     /// Initial questions for the template
     pub initial_questions: Vec<Question>,
-    /// Metadata for the template
-    pub metadata: HashMap<String, String>,
+    /// Metadata for the template, ordered by key so output is stable between runs
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl Template {
     /// Create a new template
+    #[allow(
+        dead_code,
+        reason = "builder for templates defined in code; the wizard loads them from files"
+    )]
     pub fn new(
         name: impl Into<String>,
         description: impl Into<String>,
@@ -43,16 +48,25 @@ impl Template {
             domain,
             starting_hints: starting_hints.into(),
             initial_questions: Vec::new(),
-            metadata: HashMap::new(),
+            metadata: BTreeMap::new(),
         }
     }
 
     /// Add an initial question to the template
+    #[allow(
+        dead_code,
+        reason = "builder for templates defined in code; the wizard loads them from files"
+    )]
     pub fn add_question(&mut self, question: Question) {
-        self.initial_questions.push(question);
+        self.initial_questions
+            .push(question.with_source(QuestionSource::Template));
     }
 
     /// Add metadata to the template
+    #[allow(
+        dead_code,
+        reason = "builder for templates defined in code; the wizard loads them from files"
+    )]
     pub fn add_metadata(&mut self, key: impl Into<String>, value: impl Into<String>) {
         self.metadata.insert(key.into(), value.into());
     }
@@ -72,6 +86,33 @@ impl Template {
     }
 }
 
+/// Trimmed view of a template for listings, without the full question objects
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateSummary {
+    /// Name of the template
+    pub name: String,
+    /// Domain of the template
+    pub domain: Domain,
+    /// Description of the template
+    pub description: String,
+    /// Metadata for the template
+    pub metadata: BTreeMap<String, String>,
+    /// Number of initial questions in the template
+    pub question_count: usize,
+}
+
+impl From<&Template> for TemplateSummary {
+    fn from(template: &Template) -> Self {
+        Self {
+            name: template.name.clone(),
+            domain: template.domain.clone(),
+            description: template.description.clone(),
+            metadata: template.metadata.clone(),
+            question_count: template.initial_questions.len(),
+        }
+    }
+}
+
 /// Repository of templates
 pub struct TemplateRepository {
     /// Available templates
@@ -86,24 +127,24 @@ impl TemplateRepository {
         // Try to load configuration from default path
         let config = Self::load_default_config().unwrap_or_else(|_| Config::default());
 
-        let mut repo = Self {
+        Self {
             templates: Vec::new(),
             config,
-        };
-
-        repo
+        }
     }
 
     /// Create a new template repository with configuration from a specific path
+    #[allow(
+        dead_code,
+        reason = "the wizard loads templates from the default configuration path"
+    )]
     pub fn with_config<P: AsRef<Path>>(config_path: P) -> Result<Self> {
         let config = Config::load_from_file(config_path)?;
 
-        let mut repo = Self {
+        Ok(Self {
             templates: Vec::new(),
             config,
-        };
-
-        Ok(repo)
+        })
     }
 
     /// Load configuration from the default path
@@ -121,7 +162,13 @@ impl TemplateRepository {
         self.config.domains.clone()
     }
 
+    /// Get the domains in the language of a locale, or the English ones if it has no list
+    pub fn get_domains_for(&self, locale: Option<&str>) -> Vec<Domain> {
+        self.config.domains_for(locale).to_vec()
+    }
+
     /// Add a template to the repository
+    #[allow(dead_code, reason = "extension point for templates defined in code")]
     pub fn add_template(&mut self, template: Template) {
         self.templates.push(template);
     }
@@ -136,13 +183,50 @@ impl TemplateRepository {
         &self.templates
     }
 
-    /// Get templates by domain
-    pub fn get_templates_by_domain(&self, domain: &Domain) -> Vec<&Template> {
+    /// Get templates by domain, ignoring case and surrounding whitespace
+    ///
+    /// Custom domains are typed by hand, so `robotics` matches a template saved as `Robotics`.
+    #[allow(
+        dead_code,
+        reason = "lookup for callers outside the interactive wizard"
+    )]
+    pub fn get_templates_by_domain(&self, domain: &str) -> Vec<&Template> {
+        let domain = domain.trim();
         self.templates
             .iter()
-            .filter(|t| match (&t.domain, domain) {
-                (a, b) => a == b,
-            })
+            .filter(|t| t.domain.trim().eq_ignore_ascii_case(domain))
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_domain_round_trips_and_matches_in_filtering() {
+        let template = Template::new(
+            "Warehouse Robot",
+            "Picks orders",
+            "Robotics".into(),
+            "A robot that picks orders in a warehouse",
+        );
+
+        let json = serde_json::to_string(&template).unwrap();
+        let loaded: Template = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.domain, "Robotics");
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+
+        let mut repo = TemplateRepository::new();
+        repo.add_template(loaded);
+        let names = |domain: &str| -> Vec<String> {
+            repo.get_templates_by_domain(domain)
+                .into_iter()
+                .map(|template| template.name.clone())
+                .collect()
+        };
+        assert_eq!(names("Robotics"), ["Warehouse Robot"]);
+        assert_eq!(names(" robotics "), ["Warehouse Robot"]);
+        assert!(names("Healthcare").is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+//! Transcript module for the LLM-powered project definition wizard.
+//!
+//! This module records every request sent to the LLM and the raw response
+//! received, one JSON object per line, so a session can be audited or replayed.
+//! Answers to sensitive questions are replaced by `[REDACTED]` before writing.
+
+use chrono::Utc;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::context::REDACTED;
+use super::error::{Result, WizardError};
+use super::llm::ChatMessage;
+
+/// Answers shorter than this are only redacted where they follow a colon (as in `A3: Yes`),
+/// so that common short words elsewhere in the prompt stay readable
+const MIN_FREE_REDACTION_CHARS: usize = 4;
+
+/// One LLM call in the transcript
+#[derive(Debug, Serialize)]
+pub struct TranscriptEntry<'a> {
+    /// Model the request was sent to
+    pub model: &'a str,
+    /// Sampling temperature of the request
+    pub temperature: f32,
+    /// Messages sent to the model
+    pub messages: &'a [ChatMessage],
+    /// HTTP status of the response, if one was received
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Raw response body, if one was received
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<&'a str>,
+    /// Error that prevented a response, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Append-only JSON Lines log of LLM calls, shared by all clones of the client
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    /// The transcript file, opened for appending
+    file: Arc<Mutex<File>>,
+    /// Path of the transcript file, for error messages
+    path: PathBuf,
+    /// Sensitive texts replaced by `[REDACTED]` in every entry
+    redactions: Arc<Mutex<Vec<String>>>,
+}
+
+impl Transcript {
+    /// Open a transcript file for appending, creating it if needed
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(WizardError::io("open transcript", path))?;
+
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+            path: path.to_path_buf(),
+            redactions: Arc::default(),
+        })
+    }
+
+    /// Keep a sensitive text out of all entries recorded from now on
+    pub fn redact(&self, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+
+        if let Ok(mut redactions) = self.redactions.lock()
+            && !redactions.iter().any(|redaction| redaction == text)
+        {
+            redactions.push(text.to_string());
+        }
+    }
+
+    /// Replace every registered sensitive text in the given text
+    fn redact_text(&self, text: &str) -> String {
+        let Ok(redactions) = self.redactions.lock() else {
+            return text.to_string();
+        };
+
+        let mut text = text.to_string();
+        for redaction in redactions.iter() {
+            text = if redaction.chars().count() >= MIN_FREE_REDACTION_CHARS {
+                text.replace(redaction.as_str(), REDACTED)
+            } else {
+                text.replace(&format!(": {}", redaction), &format!(": {}", REDACTED))
+            };
+        }
+        text
+    }
+
+    /// Append an entry to the transcript, stamped with the current time
+    pub fn record(&self, entry: &TranscriptEntry<'_>) -> Result<()> {
+        #[derive(Serialize)]
+        struct Line<'a> {
+            timestamp: String,
+            #[serde(flatten)]
+            entry: TranscriptEntry<'a>,
+        }
+
+        let messages: Vec<ChatMessage> = entry
+            .messages
+            .iter()
+            .map(|message| ChatMessage {
+                role: message.role.clone(),
+                content: self.redact_text(&message.content),
+            })
+            .collect();
+        let response = entry.response.map(|response| self.redact_text(response));
+
+        let mut line = serde_json::to_string(&Line {
+            timestamp: Utc::now().to_rfc3339(),
+            entry: TranscriptEntry {
+                model: entry.model,
+                temperature: entry.temperature,
+                messages: &messages,
+                status: entry.status,
+                response: response.as_deref(),
+                error: entry.error.as_deref().map(|error| self.redact_text(error)),
+            },
+        })
+        .map_err(WizardError::serialize("transcript entry"))?;
+        line.push('\n');
+
+        // Write each entry in one call so concurrent requests never interleave lines
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| WizardError::State("Transcript lock was poisoned".to_string()))?;
+        file.write_all(line.as_bytes())
+            .and_then(|()| file.flush())
+            .map_err(WizardError::io("write transcript", &self.path))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wizard::LlmClient;
+    use crate::wizard::llm::scripted::Reply;
+    use crate::wizard::session::{Session, SessionManager};
+    use serde_json::{Value, json};
+
+    #[tokio::test]
+    async fn scripted_session_logs_one_entry_per_call() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.jsonl");
+        let question = |text: &str| {
+            Reply::content(
+                json!({ "question_type": "FreeText", "question_text": text }).to_string(),
+            )
+        };
+        let (client, api) = LlmClient::scripted([
+            question("Who are the users?"),
+            question("What is the goal?"),
+        ]);
+        let client = client.with_transcript(Some(Transcript::open(&path).unwrap()));
+        let mut session_manager = SessionManager::new(Session::new(), client);
+        session_manager.start();
+
+        session_manager.generate_next_question().await.unwrap();
+        session_manager.answer_question("Nurses").unwrap();
+        session_manager.generate_next_question().await.unwrap();
+
+        let entries: Vec<Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), api.received().len());
+        assert_eq!(entries.len(), 2);
+        for entry in &entries {
+            assert!(entry["timestamp"].is_string());
+            assert_eq!(entry["status"], 200);
+            assert!(!entry["messages"].as_array().unwrap().is_empty());
+        }
+        assert!(
+            entries[1]["response"]
+                .as_str()
+                .unwrap()
+                .contains("What is the goal?")
+        );
+    }
+
+    #[tokio::test]
+    async fn sensitive_answer_is_redacted_in_the_transcript_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.jsonl");
+        let (client, api) = LlmClient::scripted([
+            Reply::content(
+                json!({
+                    "question_type": "FreeText",
+                    "question_text": "Which patient identifier do you use?",
+                    "sensitive": true
+                })
+                .to_string(),
+            ),
+            Reply::content(
+                json!({ "question_type": "FreeText", "question_text": "Who are the users?" })
+                    .to_string(),
+            ),
+        ]);
+        let client = client.with_transcript(Some(Transcript::open(&path).unwrap()));
+        let mut session_manager = SessionManager::new(Session::new(), client);
+        session_manager.start();
+
+        session_manager.generate_next_question().await.unwrap();
+        session_manager.answer_question("MRN-004217").unwrap();
+        session_manager.generate_next_question().await.unwrap();
+
+        let transcript = std::fs::read_to_string(&path).unwrap();
+        assert!(!transcript.contains("MRN-004217"));
+        assert!(transcript.contains(REDACTED));
+        assert_eq!(
+            session_manager.session.context.history[0].response,
+            "MRN-004217"
+        );
+        assert!(api.received()[1].prompt().contains("MRN-004217"));
+    }
+}